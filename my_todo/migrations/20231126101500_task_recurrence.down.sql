@@ -0,0 +1,6 @@
+drop index tasks_series_id_idx;
+
+alter table tasks
+    drop column recurrence,
+    drop column due_date,
+    drop column series_id;
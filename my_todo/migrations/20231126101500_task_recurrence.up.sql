@@ -0,0 +1,6 @@
+alter table tasks
+    add column recurrence text,
+    add column due_date date,
+    add column series_id integer;
+
+create index tasks_series_id_idx on tasks (series_id);
@@ -0,0 +1,6 @@
+create table webhooks (
+    id serial primary key,
+    url text not null,
+    secret text not null,
+    events text not null
+);
@@ -0,0 +1,4 @@
+drop index tasks_position_idx;
+
+alter table tasks
+    drop column position;
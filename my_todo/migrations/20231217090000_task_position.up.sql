@@ -0,0 +1,10 @@
+-- 並び順のキーはバイト順で比較する
+alter table tasks
+    add column position text collate "C" not null default '';
+-- 既存のタスクはこれまでと同じく新しい順に並べる
+update tasks
+    set position = lpad((99999999 - id)::text, 8, '0') || 'V';
+alter table tasks
+    alter column position drop default;
+
+create index tasks_position_idx on tasks (position);
@@ -0,0 +1,4 @@
+alter table labels
+    drop column color,
+    drop column description,
+    drop column position;
@@ -0,0 +1,7 @@
+alter table labels
+    add column color text,
+    add column description text,
+    add column position integer not null default 0;
+-- 既存のラベルはこれまでと同じく id の順に並べる
+update labels
+    set position = id;
@@ -0,0 +1,2 @@
+alter table task_labels
+    drop column attached_at;
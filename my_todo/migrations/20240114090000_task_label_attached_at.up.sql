@@ -0,0 +1,6 @@
+-- 既存の行はいつ付けたか分からないので null のままにする
+alter table task_labels
+    add column attached_at timestamptz;
+
+alter table task_labels
+    alter column attached_at set default now();
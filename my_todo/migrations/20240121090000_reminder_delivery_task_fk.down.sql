@@ -0,0 +1,2 @@
+alter table reminder_deliveries
+    drop constraint reminder_deliveries_task_id_fkey;
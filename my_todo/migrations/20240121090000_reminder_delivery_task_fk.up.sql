@@ -0,0 +1,7 @@
+-- 削除済みのタスクの記録は使わないので，消してから制約を付ける
+delete from reminder_deliveries
+where not exists (select 1 from tasks where tasks.id = reminder_deliveries.task_id);
+
+alter table reminder_deliveries
+    add constraint reminder_deliveries_task_id_fkey
+    foreign key (task_id) references tasks (id) on delete cascade;
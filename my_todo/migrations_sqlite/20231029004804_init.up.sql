@@ -0,0 +1,5 @@
+create table tasks (
+    id integer primary key autoincrement,
+    text text not null,
+    completed boolean not null default false
+)
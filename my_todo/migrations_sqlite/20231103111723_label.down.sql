@@ -0,0 +1,3 @@
+drop table task_labels;
+
+drop table labels;
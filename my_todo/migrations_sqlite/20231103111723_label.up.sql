@@ -0,0 +1,10 @@
+create table labels (
+    id integer primary key autoincrement,
+    name text not null
+);
+
+create table task_labels (
+    id integer primary key autoincrement,
+    task_id integer not null references tasks (id) deferrable initially deferred,
+    label_id integer not null references labels (id) deferrable initially deferred
+);
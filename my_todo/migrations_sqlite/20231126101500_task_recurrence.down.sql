@@ -0,0 +1,8 @@
+drop index tasks_series_id_idx;
+
+alter table tasks
+    drop column recurrence;
+alter table tasks
+    drop column due_date;
+alter table tasks
+    drop column series_id;
@@ -0,0 +1,8 @@
+alter table tasks
+    add column recurrence text;
+alter table tasks
+    add column due_date date;
+alter table tasks
+    add column series_id integer;
+
+create index tasks_series_id_idx on tasks (series_id);
@@ -0,0 +1,7 @@
+create table reminder_deliveries (
+    task_id integer not null,
+    kind text not null,
+    due_date date not null,
+    delivered_at text not null default current_timestamp,
+    primary key (task_id, kind, due_date)
+);
@@ -0,0 +1,8 @@
+-- 並び順のキーはバイト順で比較する
+alter table tasks
+    add column position text not null default '';
+-- 既存のタスクはこれまでと同じく新しい順に並べる
+update tasks
+    set position = substr('00000000' || (99999999 - id), -8, 8) || 'V';
+
+create index tasks_position_idx on tasks (position);
@@ -0,0 +1,9 @@
+create table projects (
+    id integer primary key autoincrement,
+    name text not null
+);
+
+alter table tasks
+    add column project_id integer references projects (id);
+
+create index tasks_project_id_idx on tasks (project_id);
@@ -0,0 +1,6 @@
+alter table labels
+    drop column color;
+alter table labels
+    drop column description;
+alter table labels
+    drop column position;
@@ -0,0 +1,9 @@
+alter table labels
+    add column color text;
+alter table labels
+    add column description text;
+alter table labels
+    add column position integer not null default 0;
+-- 既存のラベルはこれまでと同じく id の順に並べる
+update labels
+    set position = id;
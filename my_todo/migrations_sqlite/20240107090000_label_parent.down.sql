@@ -0,0 +1,4 @@
+drop index labels_parent_id_idx;
+
+alter table labels
+    drop column parent_id;
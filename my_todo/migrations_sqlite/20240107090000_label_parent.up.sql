@@ -0,0 +1,4 @@
+alter table labels
+    add column parent_id integer references labels (id);
+
+create index labels_parent_id_idx on labels (parent_id);
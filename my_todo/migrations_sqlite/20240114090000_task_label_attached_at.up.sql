@@ -0,0 +1,4 @@
+-- 既存の行はいつ付けたか分からないので null のままにする
+-- sqlite では追加する列の既定値に current_timestamp を使えないので，挿入時に指定する
+alter table task_labels
+    add column attached_at text;
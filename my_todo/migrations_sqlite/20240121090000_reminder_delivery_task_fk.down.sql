@@ -0,0 +1,15 @@
+create table reminder_deliveries_old (
+    task_id integer not null,
+    kind text not null,
+    due_date date not null,
+    delivered_at text not null default current_timestamp,
+    primary key (task_id, kind, due_date)
+);
+
+insert into reminder_deliveries_old (task_id, kind, due_date, delivered_at)
+select task_id, kind, due_date, delivered_at
+from reminder_deliveries;
+
+drop table reminder_deliveries;
+
+alter table reminder_deliveries_old rename to reminder_deliveries;
@@ -0,0 +1,17 @@
+-- sqlite では既存の表に外部キーを足せないので作り直す，削除済みのタスクの記録は捨てる
+create table reminder_deliveries_new (
+    task_id integer not null references tasks (id) on delete cascade,
+    kind text not null,
+    due_date date not null,
+    delivered_at text not null default current_timestamp,
+    primary key (task_id, kind, due_date)
+);
+
+insert into reminder_deliveries_new (task_id, kind, due_date, delivered_at)
+select task_id, kind, due_date, delivered_at
+from reminder_deliveries
+where task_id in (select id from tasks);
+
+drop table reminder_deliveries;
+
+alter table reminder_deliveries_new rename to reminder_deliveries;
@@ -0,0 +1,251 @@
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+use my_todo::repositories::{
+    label::{CreateLabel, Label, LabelRepository, LabelRepositoryForDb, LabelRepositoryForSqlite},
+    maintenance::{
+        duplicate_labels, export, import, Dump, MaintenanceRepository, MaintenanceRepositoryForDb,
+        MaintenanceRepositoryForSqlite,
+    },
+    migrate::{revert_last, run_migrations},
+    project::{ProjectRepository, ProjectRepositoryForDb, ProjectRepositoryForSqlite},
+    task::{CreateTask, TaskRepository, TaskRepositoryForDb, TaskRepositoryForSqlite, UpdateTask},
+    RepositoryError, MIGRATOR, SQLITE_MIGRATOR,
+};
+use sqlx::{migrate::Migrate, migrate::Migrator, Database, PgPool, Pool, SqlitePool};
+use tracing_subscriber::EnvFilter;
+
+/// DATABASE_URL の DB を直接メンテナンスする
+#[derive(Debug, Parser)]
+#[command(name = "my_todo-admin")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// マイグレーションの適用と取り消し
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// デモ用のラベルとタスクを登録する
+    Seed,
+    /// 全てのプロジェクト，ラベル，タスクを JSON で出力する
+    Export {
+        /// 省略時は標準出力
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// export した JSON を取り込む，id は振り直される
+    /// 同名のラベルは既存のものを使う
+    Import {
+        /// 省略時は標準入力
+        file: Option<PathBuf>,
+    },
+    /// タスクかラベルが存在しない task_labels の行を表示する
+    Orphans {
+        /// 表示した行を削除する
+        #[arg(long)]
+        delete: bool,
+    },
+    /// 大文字小文字と前後の空白だけが違うラベルを，最も古いものにまとめる
+    MergeLabels {
+        /// まとめる対象を表示するだけで変更しない
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// 削除したタスクやラベルの行が使っていた領域を回収する
+    /// 削除は物理削除なので論理削除されたタスクは無く，DB の VACUUM を実行する
+    Vacuum,
+}
+
+#[derive(Debug, Subcommand)]
+enum MigrateCommand {
+    /// 未適用のマイグレーションを適用する
+    Run,
+    /// 最後に適用したマイグレーションを 1 つ戻す
+    Revert,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv().ok();
+    let log_level = env::var("RUST_LOG").unwrap_or("info,sqlx=warn".to_string());
+    env::set_var("RUST_LOG", log_level);
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+    // DATABASE_URL のスキームで DB を切り替える
+    if database_url.starts_with("sqlite:") {
+        let pool = SqlitePool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        run(
+            cli.command,
+            &pool,
+            &SQLITE_MIGRATOR,
+            &ProjectRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool.clone()),
+            &LabelRepositoryForSqlite::new(pool.clone()),
+            &MaintenanceRepositoryForSqlite::new(pool.clone()),
+        )
+        .await
+    } else {
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        run(
+            cli.command,
+            &pool,
+            &MIGRATOR,
+            &ProjectRepositoryForDb::new(pool.clone()),
+            &TaskRepositoryForDb::new(pool.clone()),
+            &LabelRepositoryForDb::new(pool.clone()),
+            &MaintenanceRepositoryForDb::new(pool.clone()),
+        )
+        .await
+    }
+}
+
+async fn run<DB, P, T, L, M>(
+    command: Command,
+    pool: &Pool<DB>,
+    migrator: &Migrator,
+    projects: &P,
+    tasks: &T,
+    labels: &L,
+    maintenance: &M,
+) -> anyhow::Result<()>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+    P: ProjectRepository,
+    T: TaskRepository,
+    L: LabelRepository,
+    M: MaintenanceRepository,
+{
+    match command {
+        Command::Migrate(MigrateCommand::Run) => run_migrations(pool, migrator).await?,
+        Command::Migrate(MigrateCommand::Revert) => match revert_last(pool, migrator).await? {
+            Some(version) => println!("reverted {}", version),
+            None => println!("no migration to revert"),
+        },
+        Command::Seed => seed(tasks, labels).await?,
+        Command::Export { output } => {
+            let json = serde_json::to_string_pretty(&export(projects, labels, tasks).await?)?;
+            match output {
+                Some(path) => fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+        }
+        Command::Import { file } => {
+            let json = match file {
+                Some(path) => fs::read_to_string(path)?,
+                None => {
+                    let mut json = String::new();
+                    io::stdin().read_to_string(&mut json)?;
+                    json
+                }
+            };
+            let dump = Dump::from_json(&json)?;
+            let counts = (dump.projects.len(), dump.labels.len(), dump.tasks.len());
+            import(projects, labels, tasks, dump).await?;
+            println!(
+                "imported {} projects, {} labels and {} tasks",
+                counts.0, counts.1, counts.2
+            );
+        }
+        Command::Orphans { delete } => {
+            let rows = maintenance.orphaned_task_labels().await?;
+            let mut stdout = io::stdout().lock();
+            for row in &rows {
+                writeln!(
+                    stdout,
+                    "task_labels.id={} task_id={} label_id={}",
+                    row.id, row.task_id, row.label_id
+                )?;
+            }
+            if delete && !rows.is_empty() {
+                let ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+                let deleted = maintenance.delete_task_labels(&ids).await?;
+                writeln!(stdout, "deleted {} rows", deleted)?;
+            }
+        }
+        Command::MergeLabels { dry_run } => {
+            for group in duplicate_labels(labels).await? {
+                let (target, sources) = group.split_first().unwrap();
+                let names = sources
+                    .iter()
+                    .map(|label| format!("{:?}({})", label.name, label.id))
+                    .collect::<Vec<_>>();
+                print!("{} -> {:?}({})", names.join(", "), target.name, target.id);
+                if dry_run {
+                    println!();
+                    continue;
+                }
+                let ids = sources.iter().map(|label| label.id).collect::<Vec<_>>();
+                let merged = labels.merge(target.id, &ids).await?;
+                println!(": {} tasks updated", merged.len());
+            }
+        }
+        Command::Vacuum => {
+            maintenance.vacuum().await?;
+            println!("vacuumed");
+        }
+    }
+    Ok(())
+}
+
+/// 同名のラベルが既にあればそれを使う
+async fn find_or_create_label<L: LabelRepository>(
+    labels: &L,
+    name: String,
+) -> anyhow::Result<Label> {
+    match labels.create(CreateLabel::new(name.clone())).await {
+        Ok(label) => Ok(label),
+        Err(e) => match e.downcast_ref::<RepositoryError>() {
+            Some(RepositoryError::Duplicate(id)) => Ok(Label::new(*id, name)),
+            _ => Err(e),
+        },
+    }
+}
+
+async fn seed<T, L>(tasks: &T, labels: &L) -> anyhow::Result<()>
+where
+    T: TaskRepository,
+    L: LabelRepository,
+{
+    let work = find_or_create_label(labels, "work".to_string()).await?;
+    let home = find_or_create_label(labels, "home".to_string()).await?;
+    let errand = find_or_create_label(labels, "errand".to_string()).await?;
+
+    let demo = [
+        ("Write the weekly report", vec![work.id], true),
+        ("Review pull requests", vec![work.id], false),
+        ("Buy groceries", vec![home.id, errand.id], false),
+        ("Pick up the parcel", vec![errand.id], false),
+        ("Water the plants", vec![home.id], true),
+    ];
+    let count = demo.len();
+    for (text, label_ids, completed) in demo {
+        let task = tasks
+            .create(CreateTask::new(text.to_string(), label_ids))
+            .await?;
+        if completed {
+            tasks
+                .update(task.id, UpdateTask::new(None, Some(true), None), None)
+                .await?;
+        }
+    }
+    println!("seeded 3 labels and {} tasks", count);
+    Ok(())
+}
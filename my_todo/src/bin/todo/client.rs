@@ -0,0 +1,158 @@
+use my_todo::repositories::{
+    label::{CreateLabel, Label},
+    task::{CreateTask, TaskEntity, UpdateTask},
+};
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+
+/// `my_todo` サーバーの API クライアント
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    /// 2xx 以外はステータスとレスポンスボディをエラーにする
+    async fn send(&self, req: RequestBuilder) -> anyhow::Result<Response> {
+        let res = req.send().await?;
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            anyhow::bail!("server returned {}: {}", status, body);
+        }
+        Ok(res)
+    }
+
+    async fn send_json<T: DeserializeOwned>(&self, req: RequestBuilder) -> anyhow::Result<T> {
+        Ok(self.send(req).await?.json().await?)
+    }
+
+    pub async fn create_task(&self, payload: &CreateTask) -> anyhow::Result<TaskEntity> {
+        self.send_json(self.http.post(self.url("/task")).json(payload))
+            .await
+    }
+
+    pub async fn all_tasks(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        self.send_json(self.http.get(self.url("/task"))).await
+    }
+
+    pub async fn update_task(&self, id: i32, payload: &UpdateTask) -> anyhow::Result<TaskEntity> {
+        self.send_json(
+            self.http
+                .patch(self.url(&format!("/task/{}", id)))
+                .json(payload),
+        )
+        .await
+    }
+
+    pub async fn delete_task(&self, id: i32) -> anyhow::Result<()> {
+        self.send(self.http.delete(self.url(&format!("/task/{}", id))))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_label(&self, payload: &CreateLabel) -> anyhow::Result<Label> {
+        self.send_json(self.http.post(self.url("/label")).json(payload))
+            .await
+    }
+
+    pub async fn all_labels(&self) -> anyhow::Result<Vec<Label>> {
+        self.send_json(self.http.get(self.url("/label"))).await
+    }
+
+    pub async fn delete_label(&self, id: i32) -> anyhow::Result<()> {
+        self.send(self.http.delete(self.url(&format!("/label/{}", id))))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use my_todo::{
+        create_app,
+        repositories::{
+            label::LabelRepositoryForMemory, memory::MemoryStore,
+            project::ProjectRepositoryForMemory, task::TaskRepositoryForMemory,
+            webhook::WebhookRepositoryForMemory,
+        },
+        webhook::{WebhookConfig, Webhooks},
+        AppConfig,
+    };
+    use std::net::{SocketAddr, TcpListener};
+
+    /// インメモリのサーバーを空いているポートで起動する
+    fn spawn_server() -> Client {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let store = MemoryStore::new();
+        let app = create_app(
+            TaskRepositoryForMemory::with_store(store.clone()),
+            LabelRepositoryForMemory::with_store(store.clone()),
+            ProjectRepositoryForMemory::with_store(store.clone()),
+            Webhooks::new(
+                WebhookRepositoryForMemory::with_store(store),
+                WebhookConfig::from_env(),
+            ),
+            AppConfig::default(),
+        );
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service_with_connect_info::<SocketAddr, _>()),
+        );
+        Client::new(&format!("http://{}", addr))
+    }
+
+    #[tokio::test]
+    async fn should_manage_tasks_and_labels() {
+        let client = spawn_server();
+
+        let label = client
+            .create_label(&CreateLabel::new("cli".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(client.all_labels().await.unwrap(), vec![label.clone()]);
+
+        let task = client
+            .create_task(&CreateTask::new("from cli".to_string(), vec![label.id]))
+            .await
+            .unwrap();
+        assert_eq!(task.labels, vec![label.clone()]);
+
+        let task = client
+            .update_task(task.id, &UpdateTask::new(None, Some(true), Some(vec![])))
+            .await
+            .unwrap();
+        assert!(task.completed);
+        assert_eq!(client.all_tasks().await.unwrap(), vec![task.clone()]);
+
+        client.delete_task(task.id).await.unwrap();
+        client.delete_label(label.id).await.unwrap();
+        assert!(client.all_tasks().await.unwrap().is_empty());
+        assert!(client.all_labels().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_return_error_on_failure_status() {
+        let client = spawn_server();
+        let err = client
+            .update_task(1, &UpdateTask::new(None, Some(true), None))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("404"));
+    }
+}
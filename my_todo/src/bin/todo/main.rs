@@ -0,0 +1,127 @@
+mod client;
+mod output;
+
+use clap::{Parser, Subcommand};
+use client::Client;
+use my_todo::repositories::{
+    label::CreateLabel,
+    task::{CreateTask, UpdateTask},
+};
+use output::{render_all, render_one, Format};
+
+/// my_todo サーバーのタスクとラベルを操作する
+#[derive(Debug, Parser)]
+#[command(name = "todo")]
+struct Cli {
+    /// my_todo サーバーの URL
+    #[arg(long, env = "TODO_SERVER", default_value = "http://localhost:3000")]
+    server: String,
+    #[arg(short, long, value_enum, default_value_t = Format::Table)]
+    output: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// タスクを追加する
+    Add {
+        text: String,
+        /// 付けるラベルの id
+        #[arg(short, long = "label")]
+        labels: Vec<i32>,
+    },
+    /// タスクの一覧
+    Ls,
+    /// タスクを完了にする
+    Done { id: i32 },
+    /// タスクを編集する
+    Edit {
+        id: i32,
+        #[arg(long)]
+        text: Option<String>,
+        /// ラベルをこの id の一覧で置き換える
+        #[arg(short, long = "label", conflicts_with = "clear_labels")]
+        labels: Vec<i32>,
+        /// ラベルを全て外す
+        #[arg(long)]
+        clear_labels: bool,
+        /// 未完了に戻す
+        #[arg(long)]
+        undone: bool,
+    },
+    /// タスクを削除する
+    Rm { id: i32 },
+    /// ラベルの操作
+    #[command(subcommand)]
+    Label(LabelCommand),
+}
+
+#[derive(Debug, Subcommand)]
+enum LabelCommand {
+    /// ラベルを追加する
+    Add { name: String },
+    /// ラベルの一覧
+    Ls,
+    /// ラベルを削除する
+    Rm { id: i32 },
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let client = Client::new(&cli.server);
+    let format = cli.output;
+
+    let output = match cli.command {
+        Command::Add { text, labels } => {
+            let task = client.create_task(&CreateTask::new(text, labels)).await?;
+            Some(render_one(format, &task)?)
+        }
+        Command::Ls => Some(render_all(format, &client.all_tasks().await?)?),
+        Command::Done { id } => {
+            let task = client
+                .update_task(id, &UpdateTask::new(None, Some(true), None))
+                .await?;
+            Some(render_one(format, &task)?)
+        }
+        Command::Edit {
+            id,
+            text,
+            labels,
+            clear_labels,
+            undone,
+        } => {
+            let labels = if clear_labels {
+                Some(vec![])
+            } else if labels.is_empty() {
+                None
+            } else {
+                Some(labels)
+            };
+            let completed = undone.then_some(false);
+            let task = client
+                .update_task(id, &UpdateTask::new(text, completed, labels))
+                .await?;
+            Some(render_one(format, &task)?)
+        }
+        Command::Rm { id } => {
+            client.delete_task(id).await?;
+            None
+        }
+        Command::Label(LabelCommand::Add { name }) => {
+            let label = client.create_label(&CreateLabel::new(name)).await?;
+            Some(render_one(format, &label)?)
+        }
+        Command::Label(LabelCommand::Ls) => Some(render_all(format, &client.all_labels().await?)?),
+        Command::Label(LabelCommand::Rm { id }) => {
+            client.delete_label(id).await?;
+            None
+        }
+    };
+
+    if let Some(output) = output {
+        println!("{}", output);
+    }
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use clap::ValueEnum;
+use my_todo::repositories::{label::Label, task::TaskEntity};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// 表の 1 行として表示できる型
+pub trait Row: Serialize {
+    const HEADERS: &'static [&'static str];
+    fn cells(&self) -> Vec<String>;
+}
+
+impl Row for TaskEntity {
+    const HEADERS: &'static [&'static str] = &["ID", "DONE", "TEXT", "LABELS"];
+
+    fn cells(&self) -> Vec<String> {
+        let labels = self
+            .labels
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect::<Vec<_>>();
+        vec![
+            self.id.to_string(),
+            if self.completed { "x" } else { "" }.to_string(),
+            self.text.clone(),
+            labels.join(", "),
+        ]
+    }
+}
+
+impl Row for Label {
+    const HEADERS: &'static [&'static str] = &["ID", "NAME"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.id.to_string(), self.name.clone()]
+    }
+}
+
+pub fn render_one<T: Row>(format: Format, item: &T) -> anyhow::Result<String> {
+    match format {
+        Format::Table => Ok(table(std::slice::from_ref(item))),
+        Format::Json => Ok(serde_json::to_string_pretty(item)?),
+    }
+}
+
+pub fn render_all<T: Row>(format: Format, items: &[T]) -> anyhow::Result<String> {
+    match format {
+        Format::Table => Ok(table(items)),
+        Format::Json => Ok(serde_json::to_string_pretty(items)?),
+    }
+}
+
+fn table<T: Row>(items: &[T]) -> String {
+    let rows = items.iter().map(Row::cells).collect::<Vec<_>>();
+    let mut widths = T::HEADERS
+        .iter()
+        .map(|header| header.chars().count())
+        .collect::<Vec<_>>();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: &[String]| {
+        let padded = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{}{}", cell, " ".repeat(width - cell.chars().count())))
+            .collect::<Vec<_>>();
+        padded.join("  ").trim_end().to_string()
+    };
+    let headers = T::HEADERS
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![line(&headers)];
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_render_task_table() {
+        let tasks = vec![
+            TaskEntity {
+                id: 10,
+                text: "write docs".to_string(),
+                completed: true,
+                version: 2,
+                labels: vec![
+                    Label::new(1, "work".to_string()),
+                    Label::new(2, "urgent".to_string()),
+                ],
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
+            },
+            TaskEntity {
+                id: 9,
+                text: "buy milk".to_string(),
+                completed: false,
+                version: 1,
+                labels: vec![],
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
+            },
+        ];
+        assert_eq!(
+            render_all(Format::Table, &tasks).unwrap(),
+            [
+                "ID  DONE  TEXT        LABELS",
+                "10  x     write docs  work, urgent",
+                "9         buy milk",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn should_render_json() {
+        let label = Label::new(1, "work".to_string());
+        let json = render_one(Format::Json, &label).unwrap();
+        assert_eq!(serde_json::from_str::<Label>(&json).unwrap(), label);
+    }
+}
@@ -0,0 +1,56 @@
+use std::time::Instant;
+
+/// 現在時刻，テストでは進め方を制御できる時計に差し替える
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> Instant;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    /// `advance` した分だけ進む時計
+    #[derive(Clone)]
+    pub struct MockClock {
+        start: Instant,
+        elapsed: Arc<Mutex<Duration>>,
+    }
+
+    impl MockClock {
+        pub fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Arc::default(),
+            }
+        }
+
+        pub fn advance(&self, duration: Duration) {
+            *self.elapsed.lock().unwrap() += duration;
+        }
+    }
+
+    impl Default for MockClock {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.start + *self.elapsed.lock().unwrap()
+        }
+    }
+}
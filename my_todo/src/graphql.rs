@@ -0,0 +1,434 @@
+use crate::repositories::{
+    label::{CreateLabel, Label, LabelRepository},
+    task::{CreateTask, TaskEntity, TaskRepository, UpdateTask},
+    RepositoryError,
+};
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    http::GraphiQLSource,
+    ComplexObject, Context, EmptySubscription, Error, ErrorExtensions, Object, Result, Schema,
+};
+use axum::{async_trait, extract::Extension, response::Html, Json};
+use std::{collections::HashMap, sync::Arc};
+use validator::Validate;
+
+pub const GRAPHQL_PATH: &str = "/graphql";
+// Label.tasks と Task.labels は互いに辿れるので，入れ子の深さとフィールドの数を制限する
+// 深さは GraphiQL の introspection が通るように決める
+const MAX_DEPTH: usize = 15;
+const MAX_COMPLEXITY: usize = 1000;
+// ラベルごとのタスクの一覧は要素数が分からないので，この数だけあるものとして数える
+const TASKS_PER_LABEL: usize = 10;
+
+pub type AppSchema<T, L> = Schema<QueryRoot<T, L>, MutationRoot<T, L>, EmptySubscription>;
+
+pub fn build_schema<T: TaskRepository, L: LabelRepository>(
+    task_repository: Arc<T>,
+    label_repository: Arc<L>,
+) -> AppSchema<T, L> {
+    let loader = DataLoader::new(TasksByLabel(task_repository.clone()), tokio::spawn);
+    Schema::build(
+        QueryRoot {
+            task_repository: task_repository.clone(),
+            label_repository: label_repository.clone(),
+        },
+        MutationRoot {
+            task_repository,
+            label_repository,
+        },
+        EmptySubscription,
+    )
+    .data(loader)
+    .limit_depth(MAX_DEPTH)
+    .limit_complexity(MAX_COMPLEXITY)
+    .finish()
+}
+
+pub async fn graphql<T: TaskRepository, L: LabelRepository>(
+    Extension(schema): Extension<AppSchema<T, L>>,
+    Json(req): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(req).await)
+}
+
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint(GRAPHQL_PATH).finish())
+}
+
+pub struct QueryRoot<T, L> {
+    task_repository: Arc<T>,
+    label_repository: Arc<L>,
+}
+
+#[Object(name = "Query")]
+impl<T: TaskRepository, L: LabelRepository> QueryRoot<T, L> {
+    async fn tasks(&self) -> Result<Vec<TaskEntity>> {
+        self.task_repository.all().await.map_err(repository_error)
+    }
+
+    /// 存在しない場合は null
+    async fn task(&self, id: i32) -> Result<Option<TaskEntity>> {
+        match self.task_repository.find(id).await {
+            Ok(task) => Ok(Some(task)),
+            Err(e) => match e.downcast_ref::<RepositoryError>() {
+                Some(RepositoryError::NotFound(_)) => Ok(None),
+                _ => Err(repository_error(e)),
+            },
+        }
+    }
+
+    /// 繰り返しで作られた一連のタスク
+    async fn occurrences(&self, id: i32) -> Result<Vec<TaskEntity>> {
+        self.task_repository
+            .occurrences(id)
+            .await
+            .map_err(repository_error)
+    }
+
+    async fn labels(&self) -> Result<Vec<Label>> {
+        self.label_repository.all().await.map_err(repository_error)
+    }
+}
+
+pub struct MutationRoot<T, L> {
+    task_repository: Arc<T>,
+    label_repository: Arc<L>,
+}
+
+#[Object(name = "Mutation")]
+impl<T: TaskRepository, L: LabelRepository> MutationRoot<T, L> {
+    async fn create_task(&self, input: CreateTask) -> Result<TaskEntity> {
+        validate(&input)?;
+        self.task_repository
+            .create(input)
+            .await
+            .map_err(repository_error)
+    }
+
+    /// `version` を指定した場合は REST の If-Match と同じく一致しなければ失敗する
+    async fn update_task(
+        &self,
+        id: i32,
+        input: UpdateTask,
+        version: Option<i32>,
+    ) -> Result<TaskEntity> {
+        validate(&input)?;
+        self.task_repository
+            .update(id, input, version)
+            .await
+            .map_err(repository_error)
+    }
+
+    async fn delete_task(&self, id: i32, version: Option<i32>) -> Result<bool> {
+        self.task_repository
+            .delete(id, version)
+            .await
+            .map_err(repository_error)?;
+        Ok(true)
+    }
+
+    async fn create_label(&self, name: String) -> Result<Label> {
+        let payload = CreateLabel::new(name);
+        validate(&payload)?;
+        self.label_repository
+            .create(payload)
+            .await
+            .map_err(repository_error)
+    }
+
+    async fn delete_label(&self, id: i32) -> Result<bool> {
+        self.label_repository
+            .delete(id)
+            .await
+            .map_err(repository_error)?;
+        Ok(true)
+    }
+}
+
+#[ComplexObject]
+impl Label {
+    /// このラベルが付いたタスク
+    #[graphql(complexity = "TASKS_PER_LABEL * child_complexity")]
+    async fn tasks(&self, ctx: &Context<'_>) -> Result<Vec<TaskEntity>> {
+        let loader = ctx.data_unchecked::<DataLoader<TasksByLabel>>();
+        Ok(loader.load_one(self.id).await?.unwrap_or_default())
+    }
+
+    async fn task_count(&self, ctx: &Context<'_>) -> Result<usize> {
+        Ok(self.tasks(ctx).await?.len())
+    }
+}
+
+/// Label の resolver からは repository の型引数が見えないため dyn で持つ
+#[async_trait]
+trait TaskSource: Send + Sync {
+    async fn all_tasks(&self) -> anyhow::Result<Vec<TaskEntity>>;
+}
+
+#[async_trait]
+impl<T: TaskRepository> TaskSource for T {
+    async fn all_tasks(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        self.all().await
+    }
+}
+
+/// 同じクエリ内のラベルごとのタスク取得をまとめ，全タスクの取得 1 回で済ませる
+pub struct TasksByLabel(Arc<dyn TaskSource>);
+
+impl Loader<i32> for TasksByLabel {
+    type Value = Vec<TaskEntity>;
+    type Error = Arc<anyhow::Error>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Vec<TaskEntity>>, Self::Error> {
+        let tasks = self.0.all_tasks().await.map_err(Arc::new)?;
+        let mut tasks_by_label = keys
+            .iter()
+            .map(|id| (*id, vec![]))
+            .collect::<HashMap<_, Vec<_>>>();
+        for task in tasks {
+            for label in &task.labels {
+                if let Some(tasks) = tasks_by_label.get_mut(&label.id) {
+                    tasks.push(task.clone());
+                }
+            }
+        }
+        Ok(tasks_by_label)
+    }
+}
+
+fn validate(input: &impl Validate) -> Result<()> {
+    input.validate().map_err(|e| {
+        Error::new(format!("Validation error: [{}]", e))
+            .extend_with(|_, ext| ext.set("code", "BAD_USER_INPUT"))
+    })
+}
+
+/// RepositoryError の種類を extensions.code で返す
+fn repository_error(e: anyhow::Error) -> Error {
+    let code = match e.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::NotFound(_)) => "NOT_FOUND",
+        Some(RepositoryError::Duplicate(_)) => "DUPLICATE",
+        Some(RepositoryError::VersionMismatch(_)) => "VERSION_MISMATCH",
+        Some(RepositoryError::Conflict(_)) => "CONFLICT",
+        _ => "INTERNAL_SERVER_ERROR",
+    };
+    Error::new(e.to_string()).extend_with(|_, ext| ext.set("code", code))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::LabelRepositoryForMemory,
+        memory::MemoryStore,
+        task::{MoveTask, TaskRecord, TaskRepositoryForMemory},
+    };
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// all の呼び出し回数を数える
+    #[derive(Clone)]
+    struct CountingTaskRepository {
+        inner: TaskRepositoryForMemory,
+        all_calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl TaskRepository for CountingTaskRepository {
+        async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+            self.inner.create(payload).await
+        }
+        async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+            self.inner.find(id).await
+        }
+        async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+            self.all_calls.fetch_add(1, Ordering::SeqCst);
+            self.inner.all().await
+        }
+        async fn update_with_next(
+            &self,
+            id: i32,
+            payload: UpdateTask,
+            version: Option<i32>,
+        ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+            self.inner.update_with_next(id, payload, version).await
+        }
+        async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+            self.inner.occurrences(id).await
+        }
+        async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+            self.inner.move_task(id, payload).await
+        }
+        async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+            self.inner.delete(id, version).await
+        }
+        async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+            self.inner.records().await
+        }
+        async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+            self.inner.restore(record).await
+        }
+    }
+
+    fn schema() -> (
+        AppSchema<CountingTaskRepository, LabelRepositoryForMemory>,
+        Arc<AtomicUsize>,
+    ) {
+        let store = MemoryStore::new();
+        let all_calls = Arc::new(AtomicUsize::new(0));
+        let tasks = CountingTaskRepository {
+            inner: TaskRepositoryForMemory::with_store(store.clone()),
+            all_calls: all_calls.clone(),
+        };
+        let labels = LabelRepositoryForMemory::with_store(store);
+        (build_schema(Arc::new(tasks), Arc::new(labels)), all_calls)
+    }
+
+    async fn execute<T: TaskRepository, L: LabelRepository>(
+        schema: &AppSchema<T, L>,
+        query: &str,
+    ) -> Value {
+        let res = schema.execute(query).await;
+        serde_json::to_value(res).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_batch_task_lookups_of_labels() {
+        let (schema, all_calls) = schema();
+        execute(
+            &schema,
+            r#"mutation {
+                work: createLabel(name: "work") { id }
+                home: createLabel(name: "home") { id }
+                empty: createLabel(name: "empty") { id }
+            }"#,
+        )
+        .await;
+        execute(
+            &schema,
+            r#"mutation {
+                a: createTask(input: { text: "a", labels: [1, 2] }) { id }
+                b: createTask(input: { text: "b", labels: [1] }) { id }
+            }"#,
+        )
+        .await;
+
+        let res = execute(
+            &schema,
+            "{ labels { name taskCount tasks { text labels { name } } } }",
+        )
+        .await;
+        assert_eq!(
+            res,
+            json!({ "data": { "labels": [
+                { "name": "work", "taskCount": 2, "tasks": [
+                    { "text": "b", "labels": [{ "name": "work" }] },
+                    { "text": "a", "labels": [{ "name": "work" }, { "name": "home" }] },
+                ] },
+                { "name": "home", "taskCount": 1, "tasks": [
+                    { "text": "a", "labels": [{ "name": "work" }, { "name": "home" }] },
+                ] },
+                { "name": "empty", "taskCount": 0, "tasks": [] },
+            ] } })
+        );
+        assert_eq!(all_calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn should_update_and_delete_task() {
+        let (schema, _) = schema();
+        execute(
+            &schema,
+            r#"mutation { createTask(input: { text: "before", labels: [] }) { id } }"#,
+        )
+        .await;
+
+        let res = execute(
+            &schema,
+            r#"mutation {
+                updateTask(id: 1, version: 1, input: { text: "after", completed: true }) {
+                    text completed version
+                }
+            }"#,
+        )
+        .await;
+        assert_eq!(
+            res["data"]["updateTask"],
+            json!({ "text": "after", "completed": true, "version": 2 })
+        );
+
+        let res = execute(&schema, "mutation { deleteTask(id: 1, version: 1) }").await;
+        assert_eq!(
+            res["errors"][0]["extensions"]["code"],
+            json!("VERSION_MISMATCH")
+        );
+
+        let res = execute(&schema, "mutation { deleteTask(id: 1, version: 2) }").await;
+        assert_eq!(res["data"]["deleteTask"], json!(true));
+        let res = execute(&schema, "{ task(id: 1) { id } }").await;
+        assert_eq!(res["data"]["task"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn should_reject_invalid_input() {
+        let (schema, _) = schema();
+        let res = execute(
+            &schema,
+            r#"mutation { createTask(input: { text: "", labels: [] }) { id } }"#,
+        )
+        .await;
+        assert_eq!(
+            res["errors"][0]["extensions"]["code"],
+            json!("BAD_USER_INPUT")
+        );
+
+        let res = execute(
+            &schema,
+            r#"mutation { createTask(input: { text: "task", labels: [99] }) { id } }"#,
+        )
+        .await;
+        assert_eq!(res["errors"][0]["extensions"]["code"], json!("NOT_FOUND"));
+    }
+
+    #[tokio::test]
+    async fn should_reject_too_deep_or_complex_query() {
+        let (schema, all_calls) = schema();
+        // ラベルとタスクを往復するとタスクの一覧の分だけ重く数える
+        let res = execute(
+            &schema,
+            "{ labels { tasks { labels { tasks { labels { tasks { id } } } } } } }",
+        )
+        .await;
+        assert_eq!(res["data"], Value::Null);
+        assert_eq!(res["errors"][0]["message"], json!("Query is too complex."));
+        assert_eq!(all_calls.load(Ordering::SeqCst), 0);
+
+        let nested = (0..MAX_DEPTH).fold("name".to_string(), |inner, _| {
+            format!("ofType {{ {} }}", inner)
+        });
+        let res = execute(
+            &schema,
+            &format!(r#"{{ __type(name: "Task") {{ {} }} }}"#, nested),
+        )
+        .await;
+        assert_eq!(res["data"], Value::Null);
+        assert_eq!(
+            res["errors"][0]["message"],
+            json!("Query is nested too deep.")
+        );
+
+        // GraphiQL の introspection と同じく ofType を 7 段辿る程度は通す
+        let type_ref = (0..7).fold("kind name".to_string(), |inner, _| {
+            format!("kind name ofType {{ {} }}", inner)
+        });
+        let res = execute(
+            &schema,
+            &format!(
+                "{{ __schema {{ types {{ fields {{ args {{ type {{ {0} }} }} type {{ {0} }} }} }} }} }}",
+                type_ref
+            ),
+        )
+        .await;
+        assert_eq!(res.get("errors"), None);
+    }
+}
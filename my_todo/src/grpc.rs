@@ -0,0 +1,508 @@
+use crate::repositories::{
+    label::{CreateLabel, Label, LabelRepository},
+    task::{CreateTask, MoveTask, TaskEntity, TaskRepository, UpdateTask},
+    RepositoryError,
+};
+use chrono::NaiveDate;
+use tonic::{transport::server::Router, transport::Server, Request, Response, Status};
+use validator::Validate;
+
+use proto::{
+    label_service_server::{LabelService, LabelServiceServer},
+    task_service_server::{TaskService, TaskServiceServer},
+};
+
+pub mod proto {
+    tonic::include_proto!("my_todo");
+}
+
+/// REST と同じ repository を使う gRPC サーバー
+pub fn create_grpc_server<T: TaskRepository, L: LabelRepository>(
+    task_repository: T,
+    label_repository: L,
+) -> Router {
+    Server::builder()
+        .add_service(TaskServiceServer::new(GrpcTaskService {
+            repository: task_repository,
+        }))
+        .add_service(LabelServiceServer::new(GrpcLabelService {
+            repository: label_repository,
+        }))
+}
+
+struct GrpcTaskService<T> {
+    repository: T,
+}
+
+#[tonic::async_trait]
+impl<T: TaskRepository> TaskService for GrpcTaskService<T> {
+    async fn create_task(
+        &self,
+        request: Request<proto::CreateTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let request = request.into_inner();
+        let payload = CreateTask::new(request.text, request.labels)
+            .with_recurrence(request.recurrence, parse_date(request.due_date)?)
+            .with_project(request.project_id);
+        validate(&payload)?;
+        let task = self
+            .repository
+            .create(payload)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(task.into()))
+    }
+
+    async fn find_task(
+        &self,
+        request: Request<proto::FindTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let task = self
+            .repository
+            .find(request.into_inner().id)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(task.into()))
+    }
+
+    async fn list_tasks(
+        &self,
+        _request: Request<proto::ListTasksRequest>,
+    ) -> Result<Response<proto::ListTasksResponse>, Status> {
+        let tasks = self.repository.all().await.map_err(status_from_error)?;
+        Ok(Response::new(proto::ListTasksResponse {
+            tasks: tasks.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn update_task(
+        &self,
+        request: Request<proto::UpdateTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let request = request.into_inner();
+        let payload = UpdateTask::new(
+            request.text,
+            request.completed,
+            request.labels.map(|labels| labels.ids),
+        )
+        .with_recurrence(request.recurrence, parse_date(request.due_date)?)
+        .with_project(request.project_id);
+        validate(&payload)?;
+        let task = self
+            .repository
+            .update(request.id, payload, request.version)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(task.into()))
+    }
+
+    async fn move_task(
+        &self,
+        request: Request<proto::MoveTaskRequest>,
+    ) -> Result<Response<proto::Task>, Status> {
+        let request = request.into_inner();
+        let payload = MoveTask::new(request.after, request.before);
+        validate(&payload)?;
+        let task = self
+            .repository
+            .move_task(request.id, payload)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(task.into()))
+    }
+
+    async fn list_occurrences(
+        &self,
+        request: Request<proto::FindTaskRequest>,
+    ) -> Result<Response<proto::ListTasksResponse>, Status> {
+        let tasks = self
+            .repository
+            .occurrences(request.into_inner().id)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(proto::ListTasksResponse {
+            tasks: tasks.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn delete_task(
+        &self,
+        request: Request<proto::DeleteTaskRequest>,
+    ) -> Result<Response<proto::DeleteTaskResponse>, Status> {
+        let request = request.into_inner();
+        self.repository
+            .delete(request.id, request.version)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(proto::DeleteTaskResponse {}))
+    }
+}
+
+struct GrpcLabelService<L> {
+    repository: L,
+}
+
+#[tonic::async_trait]
+impl<L: LabelRepository> LabelService for GrpcLabelService<L> {
+    async fn create_label(
+        &self,
+        request: Request<proto::CreateLabelRequest>,
+    ) -> Result<Response<proto::Label>, Status> {
+        let request = request.into_inner();
+        let payload = CreateLabel::new(request.name)
+            .with_details(request.color, request.description)
+            .with_position(request.position)
+            .with_parent(request.parent_id);
+        validate(&payload)?;
+        let label = self
+            .repository
+            .create(payload)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(label.into()))
+    }
+
+    async fn list_labels(
+        &self,
+        _request: Request<proto::ListLabelsRequest>,
+    ) -> Result<Response<proto::ListLabelsResponse>, Status> {
+        let labels = self.repository.all().await.map_err(status_from_error)?;
+        Ok(Response::new(proto::ListLabelsResponse {
+            labels: labels.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn delete_label(
+        &self,
+        request: Request<proto::DeleteLabelRequest>,
+    ) -> Result<Response<proto::DeleteLabelResponse>, Status> {
+        self.repository
+            .delete(request.into_inner().id)
+            .await
+            .map_err(status_from_error)?;
+        Ok(Response::new(proto::DeleteLabelResponse {}))
+    }
+}
+
+impl From<TaskEntity> for proto::Task {
+    fn from(task: TaskEntity) -> Self {
+        Self {
+            id: task.id,
+            text: task.text,
+            completed: task.completed,
+            version: task.version,
+            labels: task.labels.into_iter().map(Into::into).collect(),
+            recurrence: task.recurrence,
+            due_date: task.due_date.map(|date| date.to_string()),
+            series_id: task.series_id,
+            project_id: task.project_id,
+        }
+    }
+}
+
+impl From<Label> for proto::Label {
+    fn from(label: Label) -> Self {
+        Self {
+            id: label.id,
+            name: label.name,
+            color: label.color,
+            description: label.description,
+            position: label.position,
+            parent_id: label.parent_id,
+        }
+    }
+}
+
+// tonic のメソッドがそのまま返す型に合わせる
+#[allow(clippy::result_large_err)]
+fn validate(payload: &impl Validate) -> Result<(), Status> {
+    payload
+        .validate()
+        .map_err(|e| Status::invalid_argument(format!("Validation error: [{}]", e)))
+}
+
+#[allow(clippy::result_large_err)]
+fn parse_date(date: Option<String>) -> Result<Option<NaiveDate>, Status> {
+    date.map(|date| {
+        date.parse()
+            .map_err(|e| Status::invalid_argument(format!("invalid due_date [{}]: {}", date, e)))
+    })
+    .transpose()
+}
+
+fn status_from_error(e: anyhow::Error) -> Status {
+    match e.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::NotFound(_)) => Status::not_found(e.to_string()),
+        Some(RepositoryError::Duplicate(_)) => Status::already_exists(e.to_string()),
+        Some(RepositoryError::VersionMismatch(_) | RepositoryError::Conflict(_)) => {
+            Status::failed_precondition(e.to_string())
+        }
+        _ => Status::internal(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::LabelRepositoryForMemory,
+        memory::MemoryStore,
+        project::{CreateProject, ProjectRepository, ProjectRepositoryForMemory},
+        task::TaskRepositoryForMemory,
+    };
+    use proto::{label_service_client::LabelServiceClient, task_service_client::TaskServiceClient};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{transport::Channel, Code};
+
+    /// インメモリの gRPC サーバーを空いているポートで起動する
+    async fn spawn_server(
+        store: MemoryStore,
+    ) -> (TaskServiceClient<Channel>, LabelServiceClient<Channel>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = create_grpc_server(
+            TaskRepositoryForMemory::with_store(store.clone()),
+            LabelRepositoryForMemory::with_store(store),
+        );
+        tokio::spawn(server.serve_with_incoming(TcpListenerStream::new(listener)));
+
+        let channel = Channel::from_shared(format!("http://{}", addr))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        (
+            TaskServiceClient::new(channel.clone()),
+            LabelServiceClient::new(channel),
+        )
+    }
+
+    #[tokio::test]
+    async fn should_manage_tasks_and_labels() {
+        let (mut tasks, mut labels) = spawn_server(MemoryStore::new()).await;
+
+        let label = labels
+            .create_label(proto::CreateLabelRequest {
+                name: "grpc".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let task = tasks
+            .create_task(proto::CreateTaskRequest {
+                text: "from grpc".to_string(),
+                labels: vec![label.id],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(task.labels, vec![label.clone()]);
+
+        let task = tasks
+            .update_task(proto::UpdateTaskRequest {
+                id: task.id,
+                text: None,
+                completed: Some(true),
+                labels: Some(proto::LabelIds { ids: vec![] }),
+                version: Some(task.version),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(task.completed);
+        assert!(task.labels.is_empty());
+        let found = tasks
+            .find_task(proto::FindTaskRequest { id: task.id })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(found, task);
+
+        tasks
+            .delete_task(proto::DeleteTaskRequest {
+                id: task.id,
+                version: None,
+            })
+            .await
+            .unwrap();
+        labels
+            .delete_label(proto::DeleteLabelRequest { id: label.id })
+            .await
+            .unwrap();
+        let all_tasks = tasks
+            .list_tasks(proto::ListTasksRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(all_tasks.tasks.is_empty());
+        let all_labels = labels
+            .list_labels(proto::ListLabelsRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(all_labels.labels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_map_errors_to_status_codes() {
+        let (mut tasks, mut labels) = spawn_server(MemoryStore::new()).await;
+
+        let status = tasks
+            .create_task(proto::CreateTaskRequest {
+                text: "".to_string(),
+                labels: vec![],
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let status = tasks
+            .find_task(proto::FindTaskRequest { id: 1 })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        let task = tasks
+            .create_task(proto::CreateTaskRequest {
+                text: "task".to_string(),
+                labels: vec![],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let status = tasks
+            .delete_task(proto::DeleteTaskRequest {
+                id: task.id,
+                version: Some(task.version + 1),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        let request = || proto::CreateLabelRequest {
+            name: "dup".to_string(),
+            ..Default::default()
+        };
+        labels.create_label(request()).await.unwrap();
+        let status = labels.create_label(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::AlreadyExists);
+    }
+
+    #[tokio::test]
+    async fn should_carry_label_details_projects_and_order() {
+        let store = MemoryStore::new();
+        let project = ProjectRepositoryForMemory::with_store(store.clone())
+            .create(CreateProject::new("grpc".to_string()))
+            .await
+            .unwrap();
+        let (mut tasks, mut labels) = spawn_server(store).await;
+
+        let parent = labels
+            .create_label(proto::CreateLabelRequest {
+                name: "parent".to_string(),
+                color: Some("#00ff00".to_string()),
+                description: Some("details".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(parent.color.as_deref(), Some("#00ff00"));
+        assert_eq!(parent.description.as_deref(), Some("details"));
+        let child = labels
+            .create_label(proto::CreateLabelRequest {
+                name: "child".to_string(),
+                position: Some(5),
+                parent_id: Some(parent.id),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!((child.position, child.parent_id), (5, Some(parent.id)));
+        let status = labels
+            .create_label(proto::CreateLabelRequest {
+                name: "invalid".to_string(),
+                color: Some("green".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let first = tasks
+            .create_task(proto::CreateTaskRequest {
+                text: "first".to_string(),
+                project_id: Some(project.id),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(first.project_id, Some(project.id));
+        let status = tasks
+            .create_task(proto::CreateTaskRequest {
+                text: "missing project".to_string(),
+                project_id: Some(project.id + 1),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        // 新しいタスクは先頭に置かれるので，最初のタスクの後ろへ移す
+        let second = tasks
+            .create_task(proto::CreateTaskRequest {
+                text: "second".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let moved = tasks
+            .move_task(proto::MoveTaskRequest {
+                id: second.id,
+                after: Some(first.id),
+                before: None,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(moved, second);
+        let order = tasks
+            .list_tasks(proto::ListTasksRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .tasks
+            .iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec![first.id, second.id]);
+        let status = tasks
+            .move_task(proto::MoveTaskRequest {
+                id: second.id,
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let updated = tasks
+            .update_task(proto::UpdateTaskRequest {
+                id: first.id,
+                project_id: Some(0),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(updated.project_id, None);
+    }
+}
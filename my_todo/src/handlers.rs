@@ -1,14 +1,19 @@
 use axum::{
     async_trait,
     extract::{FromRequest, RequestParts},
-    http::StatusCode,
+    http::{header::IF_MATCH, StatusCode},
     BoxError, Json,
 };
 use serde::de::DeserializeOwned;
 use validator::Validate;
 
+use crate::repositories::RepositoryError;
+
+pub mod health;
 pub mod label;
+pub mod project;
 pub mod task;
+pub mod webhook;
 
 #[derive(Debug)]
 pub struct ValidatedJson<T>(T);
@@ -35,3 +40,85 @@ where
         Ok(ValidatedJson(value))
     }
 }
+
+/// `If-Match` ヘッダから取り出した期待する version の候補
+/// ヘッダが無い，もしくは `*` の場合は None
+/// If-Match は強い比較なので弱い ETag (`W/"1"`) は候補に含めない
+#[derive(Debug)]
+pub struct IfMatch(Option<Vec<i32>>);
+
+impl IfMatch {
+    pub fn versions(&self) -> Option<&[i32]> {
+        self.0.as_deref()
+    }
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for IfMatch
+where
+    B: Send,
+{
+    type Rejection = (StatusCode, String);
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let values = match req.headers() {
+            Some(headers) if headers.contains_key(IF_MATCH) => headers.get_all(IF_MATCH),
+            _ => return Ok(IfMatch(None)),
+        };
+        let mut versions = vec![];
+        for value in values {
+            let value = value.to_str().unwrap_or_default().trim();
+            if value == "*" {
+                return Ok(IfMatch(None));
+            }
+            let parsed = parse_entity_tags(value).ok_or_else(|| {
+                let message = format!("If-Match parse error: [{}]", value);
+                (StatusCode::BAD_REQUEST, message)
+            })?;
+            versions.extend(parsed);
+        }
+        Ok(IfMatch(Some(versions)))
+    }
+}
+
+/// カンマ区切りの ETag の並びから強い ETag の version を取り出す
+/// 数値でない ETag はどの version とも一致しないので読み飛ばす
+fn parse_entity_tags(value: &str) -> Option<Vec<i32>> {
+    let mut versions = vec![];
+    let mut rest = value;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            return Some(versions);
+        }
+        let (weak, tag) = match rest.strip_prefix("W/") {
+            Some(tag) => (true, tag),
+            None => (false, rest),
+        };
+        let tag = tag.strip_prefix('"')?;
+        let end = tag.find('"')?;
+        if !weak {
+            if let Ok(version) = tag[..end].parse::<i32>() {
+                versions.push(version);
+            }
+        }
+        rest = tag[end + 1..].trim_start();
+        if !rest.is_empty() && !rest.starts_with(',') {
+            return None;
+        }
+    }
+}
+
+/// repository のエラーを全てのハンドラで同じステータスコードにする
+pub fn status_from_error(e: anyhow::Error) -> StatusCode {
+    match e.downcast_ref::<RepositoryError>() {
+        Some(RepositoryError::NotFound(_)) => StatusCode::NOT_FOUND,
+        Some(RepositoryError::VersionMismatch(_)) => StatusCode::PRECONDITION_FAILED,
+        Some(RepositoryError::Duplicate(_) | RepositoryError::Conflict(_)) => StatusCode::CONFLICT,
+        Some(RepositoryError::Unexpected(_)) | None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
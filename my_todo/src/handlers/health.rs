@@ -0,0 +1,176 @@
+use axum::{async_trait, extract::Extension, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    Connection, Database, Pool,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[async_trait]
+pub trait ReadinessCheck: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+    async fn check(&self) -> anyhow::Result<()>;
+}
+
+/// `/readyz` で実行するチェックの一覧
+#[derive(Clone, Default)]
+pub struct Readiness {
+    checks: Vec<Arc<dyn ReadinessCheck>>,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check<C: ReadinessCheck>(mut self, check: C) -> Self {
+        self.checks.push(Arc::new(check));
+        self
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    name: &'static str,
+    status: CheckStatus,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    status: CheckStatus,
+    checks: Vec<CheckReport>,
+}
+
+pub async fn healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+pub async fn readyz(readiness: Option<Extension<Readiness>>) -> impl IntoResponse {
+    let checks = readiness
+        .map(|Extension(readiness)| readiness.checks)
+        .unwrap_or_default();
+
+    let mut reports = vec![];
+    for check in checks {
+        let start = Instant::now();
+        let res = tokio::time::timeout(CHECK_TIMEOUT, check.check())
+            .await
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("timed out after {:?}", CHECK_TIMEOUT)));
+        let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+        reports.push(match res {
+            Ok(()) => CheckReport {
+                name: check.name(),
+                status: CheckStatus::Ok,
+                latency_ms,
+                error: None,
+            },
+            Err(e) => CheckReport {
+                name: check.name(),
+                status: CheckStatus::Error,
+                latency_ms,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+
+    let ready = reports
+        .iter()
+        .all(|report| report.status == CheckStatus::Ok);
+    let (status_code, status) = if ready {
+        (StatusCode::OK, CheckStatus::Ok)
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, CheckStatus::Error)
+    };
+    (
+        status_code,
+        Json(ReadinessReport {
+            status,
+            checks: reports,
+        }),
+    )
+}
+
+pub struct DatabaseCheck<DB: Database> {
+    pool: Pool<DB>,
+}
+
+impl<DB: Database> DatabaseCheck<DB> {
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl<DB: Database> ReadinessCheck for DatabaseCheck<DB> {
+    fn name(&self) -> &'static str {
+        "database"
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        self.pool.acquire().await?.ping().await?;
+        Ok(())
+    }
+}
+
+/// バイナリに含まれる全てのマイグレーションが適用済みか確認する
+pub struct MigrationCheck<DB: Database> {
+    pool: Pool<DB>,
+    migrator: &'static Migrator,
+}
+
+impl<DB: Database> MigrationCheck<DB> {
+    pub fn new(pool: Pool<DB>, migrator: &'static Migrator) -> Self {
+        Self { pool, migrator }
+    }
+}
+
+#[async_trait]
+impl<DB> ReadinessCheck for MigrationCheck<DB>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    fn name(&self) -> &'static str {
+        "migrations"
+    }
+
+    async fn check(&self) -> anyhow::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        if let Some(version) = conn.dirty_version().await? {
+            anyhow::bail!("migration {} is partially applied", version);
+        }
+        let applied = conn
+            .list_applied_migrations()
+            .await?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect::<Vec<_>>();
+
+        let pending = self
+            .migrator
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| migration.version.to_string())
+            .collect::<Vec<_>>();
+        if !pending.is_empty() {
+            anyhow::bail!("pending migrations: [{}]", pending.join(", "));
+        }
+        Ok(())
+    }
+}
@@ -1,36 +1,179 @@
 use std::sync::Arc;
 
-use crate::repositories::label::LabelRepository;
+use crate::repositories::label::{
+    self, CreateLabel, Label, LabelRepository, LabelTree, MergeLabels, UpdateLabel,
+};
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     http::StatusCode,
     response::IntoResponse,
     Json,
 };
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use utoipa::ToSchema;
 
-use super::ValidatedJson;
+use super::{status_from_error, ValidatedJson};
 
+#[utoipa::path(
+    post,
+    path = "/label",
+    tag = "label",
+    request_body = CreateLabel,
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for a repeated request")),
+    responses(
+        (status = 201, description = "Label created", body = Label),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Parent label not found"),
+        (status = 409, description = "Another label has the name"),
+    )
+)]
 pub async fn create_label<T: LabelRepository>(
     ValidatedJson(payload): ValidatedJson<CreateLabel>,
     Extension(repository): Extension<Arc<T>>,
 ) -> Result<impl IntoResponse, StatusCode> {
     let label = repository
-        .create(payload.name)
+        .create(payload)
         .await
-        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+        .map_err(status_from_error)?;
 
     Ok((StatusCode::CREATED, Json(label)))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct LabelQuery {
+    /// 親子関係で入れ子にして返す
+    #[serde(default)]
+    tree: bool,
+}
+
+/// `GET /label` の結果，tree を指定すると入れ子になる
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LabelList {
+    Flat(Vec<Label>),
+    Tree(Vec<LabelTree>),
+}
+
+#[utoipa::path(
+    get,
+    path = "/label",
+    tag = "label",
+    params(("tree" = Option<bool>, Query, description = "Nest labels under their parents")),
+    responses((status = 200, description = "All labels in list order, a [LabelTree] when `tree` is set", body = LabelList))
+)]
 pub async fn all_labels<T: LabelRepository>(
+    Query(query): Query<LabelQuery>,
     Extension(repository): Extension<Arc<T>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let labels = repository.all().await.unwrap();
+    let labels = repository
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    let labels = if query.tree {
+        LabelList::Tree(label::tree(labels))
+    } else {
+        LabelList::Flat(labels)
+    };
     Ok((StatusCode::OK, Json(labels)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/label/stats",
+    tag = "label",
+    responses((status = 200, description = "Task counts per label, in list order", body = [LabelStats]))
+)]
+pub async fn label_stats<T: LabelRepository>(
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let stats = repository
+        .stats()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/label/{id}",
+    tag = "label",
+    request_body = UpdateLabel,
+    params(("id" = i32, Path, description = "Label id")),
+    responses(
+        (status = 200, description = "Label updated", body = Label),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Label or parent label not found"),
+        (status = 409, description = "Another label has the name, or the parent would make a cycle"),
+    )
+)]
+pub async fn update_label<T: LabelRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<UpdateLabel>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let label = repository
+        .update(id, payload)
+        .await
+        .map_err(status_from_error)?;
+    Ok((StatusCode::OK, Json(label)))
+}
+
+/// `POST /label/{id}/merge` の結果
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct MergedLabels {
+    /// 統合元のラベルが外れたタスクの数
+    /// 既に統合先のラベルが付いていて，統合元が外れただけのタスクも含む
+    pub affected_tasks: u64,
+    /// 削除した統合元のラベルの id，昇順
+    pub removed_labels: Vec<i32>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/label/{id}/merge",
+    tag = "label",
+    request_body = MergeLabels,
+    params(("id" = i32, Path, description = "Label to keep")),
+    responses(
+        (status = 200, description = "Source labels merged into the label and deleted, `affected_tasks` also counts tasks that already had the label", body = MergedLabels),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Label not found"),
+        (status = 409, description = "The label is one of the sources or nested under one"),
+    )
+)]
+pub async fn merge_labels<T: LabelRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<MergeLabels>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let affected_tasks = repository
+        .merge(id, &payload.sources)
+        .await
+        .map_err(status_from_error)?
+        .len() as u64;
+    let mut removed_labels = payload.sources;
+    removed_labels.sort_unstable();
+    removed_labels.dedup();
+    Ok((
+        StatusCode::OK,
+        Json(MergedLabels {
+            affected_tasks,
+            removed_labels,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/label/{id}",
+    tag = "label",
+    params(("id" = i32, Path, description = "Label id")),
+    responses(
+        (status = 204, description = "Label deleted"),
+        (status = 404, description = "Label not found"),
+        (status = 409, description = "The label still has child labels or is attached to tasks"),
+    )
+)]
 pub async fn delete_label<T: LabelRepository>(
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
@@ -39,12 +182,5 @@ pub async fn delete_label<T: LabelRepository>(
         .delete(id)
         .await
         .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Validate)]
-pub struct CreateLabel {
-    #[validate(length(min = 1, message = "Can not be empty"))]
-    #[validate(length(max = 100, message = "Over text length"))]
-    name: String,
+        .unwrap_or_else(status_from_error)
 }
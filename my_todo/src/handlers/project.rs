@@ -0,0 +1,140 @@
+use super::{status_from_error, ValidatedJson};
+use crate::repositories::{
+    project::{CreateProject, ProjectRepository, UpdateProject},
+    task::TaskRepository,
+};
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+#[utoipa::path(
+    post,
+    path = "/project",
+    tag = "project",
+    request_body = CreateProject,
+    responses(
+        (status = 201, description = "Project created", body = Project),
+        (status = 400, description = "Invalid payload"),
+    )
+)]
+pub async fn create_project<P: ProjectRepository>(
+    ValidatedJson(payload): ValidatedJson<CreateProject>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let project = repository
+        .create(payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::CREATED, Json(project)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/project",
+    tag = "project",
+    responses((status = 200, description = "All projects", body = [Project]))
+)]
+pub async fn all_projects<P: ProjectRepository>(
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let projects = repository
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::OK, Json(projects)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/project/{id}",
+    tag = "project",
+    params(("id" = i32, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "Project found", body = Project),
+        (status = 404, description = "Project not found"),
+    )
+)]
+pub async fn find_project<P: ProjectRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let project = repository.find(id).await.map_err(status_from_error)?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/project/{id}",
+    tag = "project",
+    request_body = UpdateProject,
+    params(("id" = i32, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "Project updated", body = Project),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Project not found"),
+    )
+)]
+pub async fn update_project<P: ProjectRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<UpdateProject>,
+    Extension(repository): Extension<Arc<P>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let project = repository
+        .update(id, payload)
+        .await
+        .map_err(status_from_error)?;
+    Ok((StatusCode::OK, Json(project)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/project/{id}",
+    tag = "project",
+    params(("id" = i32, Path, description = "Project id")),
+    responses(
+        (status = 204, description = "Project deleted"),
+        (status = 404, description = "Project not found"),
+        (status = 409, description = "Project still has tasks, move them with PATCH /task/{id} first"),
+    )
+)]
+pub async fn delete_project<P: ProjectRepository>(
+    Path(id): Path<i32>,
+    Extension(repository): Extension<Arc<P>>,
+) -> StatusCode {
+    repository
+        .delete(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or_else(status_from_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/project/{id}/tasks",
+    tag = "project",
+    params(("id" = i32, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "Tasks in the project, in list order", body = [TaskEntity]),
+        (status = 404, description = "Project not found"),
+    )
+)]
+pub async fn project_tasks<P: ProjectRepository, T: TaskRepository>(
+    Path(id): Path<i32>,
+    Extension(projects): Extension<Arc<P>>,
+    Extension(tasks): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    projects.find(id).await.map_err(status_from_error)?;
+    // キャッシュを効かせるため，絞り込みは all の結果に対して行う
+    let tasks: Vec<_> = tasks
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?
+        .into_iter()
+        .filter(|task| task.project_id == Some(id))
+        .collect();
+    Ok((StatusCode::OK, Json(tasks)))
+}
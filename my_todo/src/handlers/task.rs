@@ -1,13 +1,29 @@
-use super::ValidatedJson;
-use crate::repositories::task::{CreateTask, TaskRepository, UpdateTask};
+use super::{etag, status_from_error, IfMatch, ValidatedJson};
+use crate::repositories::{
+    label::{self, LabelRepository},
+    task::{CreateTask, MoveTask, TaskRepository, UpdateTask},
+};
 use axum::{
-    extract::{Extension, Path},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Extension, Path, Query},
+    http::{header::ETAG, StatusCode},
+    response::{Headers, IntoResponse},
     Json,
 };
+use serde::Deserialize;
 use std::sync::Arc;
 
+#[utoipa::path(
+    post,
+    path = "/task",
+    tag = "task",
+    request_body = CreateTask,
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for a repeated request")),
+    responses(
+        (status = 201, description = "Task created", body = TaskEntity),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Label or project not found"),
+    )
+)]
 pub async fn create_task<T: TaskRepository>(
     ValidatedJson(payload): ValidatedJson<CreateTask>,
     Extension(repository): Extension<Arc<T>>,
@@ -15,44 +31,211 @@ pub async fn create_task<T: TaskRepository>(
     let task = repository
         .create(payload)
         .await
-        .or(Err(StatusCode::NOT_FOUND))?;
+        .map_err(status_from_error)?;
     Ok((StatusCode::CREATED, Json(task)))
 }
 
+#[utoipa::path(
+    get,
+    path = "/task/{id}",
+    tag = "task",
+    params(("id" = i32, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "Task found", body = TaskEntity,
+            headers(("ETag" = String, description = "Current version of the task"))),
+        (status = 404, description = "Task not found"),
+    )
+)]
 pub async fn find_task<T: TaskRepository>(
     Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let task = repository.find(id).await.or(Err(StatusCode::NOT_FOUND))?;
-    Ok((StatusCode::OK, Json(task)))
+    let task = repository.find(id).await.map_err(status_from_error)?;
+    Ok((
+        StatusCode::OK,
+        Headers([(ETAG, etag(task.version))]),
+        Json(task),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TaskQuery {
+    /// このラベルが付いたタスクだけを返す
+    label: Option<i32>,
+    /// label の子孫のラベルが付いたタスクも含める
+    #[serde(default)]
+    descendants: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/task",
+    tag = "task",
+    params(
+        ("label" = Option<i32>, Query, description = "Only tasks with this label"),
+        ("descendants" = Option<bool>, Query, description = "Also match labels nested under `label`"),
+    ),
+    responses(
+        (status = 200, description = "All tasks", body = [TaskEntity]),
+        (status = 404, description = "Label not found"),
+    )
+)]
+pub async fn all_tasks<T: TaskRepository, L: LabelRepository>(
+    Query(query): Query<TaskQuery>,
+    Extension(repository): Extension<Arc<T>>,
+    Extension(labels): Extension<Arc<L>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let mut tasks = repository
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    if let Some(id) = query.label {
+        // descendants の指定に関わらず，存在しないラベルは 404
+        let labels = labels
+            .all()
+            .await
+            .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+        if !labels.iter().any(|label| label.id == id) {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let ids = if query.descendants {
+            label::descendants(&labels, id)
+        } else {
+            vec![id]
+        };
+        tasks.retain(|task| task.labels.iter().any(|label| ids.contains(&label.id)));
+    }
+    Ok((StatusCode::OK, Json(tasks)))
 }
 
-pub async fn all_tasks<T: TaskRepository>(
+#[utoipa::path(
+    get,
+    path = "/task/{id}/occurrences",
+    tag = "task",
+    params(("id" = i32, Path, description = "Id of any task in the series")),
+    responses(
+        (status = 200, description = "Tasks spawned by the same recurrence, oldest first", body = [TaskEntity]),
+        (status = 404, description = "Task not found"),
+    )
+)]
+pub async fn task_occurrences<T: TaskRepository>(
+    Path(id): Path<i32>,
     Extension(repository): Extension<Arc<T>>,
 ) -> Result<impl IntoResponse, StatusCode> {
-    let tasks = repository.all().await.unwrap();
+    let tasks = repository
+        .occurrences(id)
+        .await
+        .map_err(status_from_error)?;
     Ok((StatusCode::OK, Json(tasks)))
 }
 
+#[utoipa::path(
+    patch,
+    path = "/task/{id}",
+    tag = "task",
+    request_body = UpdateTask,
+    params(
+        ("id" = i32, Path, description = "Task id"),
+        ("If-Match" = Option<String>, Header, description = "ETag returned by GET /task/{id}"),
+    ),
+    responses(
+        (status = 201, description = "Task updated", body = TaskEntity,
+            headers(("ETag" = String, description = "New version of the task"))),
+        (status = 400, description = "Invalid payload"),
+        (status = 404, description = "Task not found"),
+        (status = 412, description = "Task was modified by another request"),
+    )
+)]
 pub async fn update_task<T: TaskRepository>(
     Path(id): Path<i32>,
+    if_match: IfMatch,
     ValidatedJson(payload): ValidatedJson<UpdateTask>,
     Extension(repository): Extension<Arc<T>>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    let version = expected_version(repository.as_ref(), id, &if_match).await?;
     let task = repository
-        .update(id, payload)
+        .update(id, payload, version)
         .await
-        .or(Err(StatusCode::NOT_FOUND))?;
-    Ok((StatusCode::CREATED, Json(task)))
+        .map_err(status_from_error)?;
+    Ok((
+        StatusCode::CREATED,
+        Headers([(ETAG, etag(task.version))]),
+        Json(task),
+    ))
 }
 
+#[utoipa::path(
+    post,
+    path = "/task/{id}/move",
+    tag = "task",
+    request_body = MoveTask,
+    params(("id" = i32, Path, description = "Task id")),
+    responses(
+        (status = 200, description = "Task moved, the version is unchanged", body = TaskEntity),
+        (status = 400, description = "Neither after nor before is given"),
+        (status = 404, description = "Task or neighbour not found"),
+        (status = 409, description = "After and before are not adjacent"),
+    )
+)]
+pub async fn move_task<T: TaskRepository>(
+    Path(id): Path<i32>,
+    ValidatedJson(payload): ValidatedJson<MoveTask>,
+    Extension(repository): Extension<Arc<T>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let task = repository
+        .move_task(id, payload)
+        .await
+        .map_err(status_from_error)?;
+    Ok((StatusCode::OK, Json(task)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/task/{id}",
+    tag = "task",
+    params(
+        ("id" = i32, Path, description = "Task id"),
+        ("If-Match" = Option<String>, Header, description = "ETag returned by GET /task/{id}"),
+    ),
+    responses(
+        (status = 204, description = "Task deleted"),
+        (status = 404, description = "Task not found"),
+        (status = 412, description = "Task was modified by another request"),
+    )
+)]
 pub async fn delete_task<T: TaskRepository>(
     Path(id): Path<i32>,
+    if_match: IfMatch,
     Extension(repository): Extension<Arc<T>>,
 ) -> StatusCode {
+    let version = match expected_version(repository.as_ref(), id, &if_match).await {
+        Ok(version) => version,
+        Err(status) => return status,
+    };
     repository
-        .delete(id)
+        .delete(id, version)
         .await
         .map(|_| StatusCode::NO_CONTENT)
-        .unwrap_or(StatusCode::NOT_FOUND)
+        .unwrap_or_else(status_from_error)
+}
+
+/// If-Match の候補から repository に渡す version を決める
+/// 候補が複数ある場合は現在の version が含まれていればそれを渡し，更新時に改めて比較させる
+async fn expected_version<T: TaskRepository>(
+    repository: &T,
+    id: i32,
+    if_match: &IfMatch,
+) -> Result<Option<i32>, StatusCode> {
+    match if_match.versions() {
+        None => Ok(None),
+        Some([version]) => Ok(Some(*version)),
+        Some(versions) => {
+            let task = repository.find(id).await.map_err(status_from_error)?;
+            if versions.contains(&task.version) {
+                Ok(Some(task.version))
+            } else {
+                Err(StatusCode::PRECONDITION_FAILED)
+            }
+        }
+    }
 }
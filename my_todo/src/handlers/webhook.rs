@@ -0,0 +1,82 @@
+use super::{status_from_error, ValidatedJson};
+use crate::repositories::webhook::{CreateWebhook, WebhookRepository};
+use crate::webhook::Webhooks;
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+
+#[utoipa::path(
+    post,
+    path = "/webhook",
+    tag = "webhook",
+    request_body = CreateWebhook,
+    responses(
+        (status = 201, description = "Webhook subscribed, the secret is not returned", body = Webhook),
+        (status = 400, description = "Invalid payload"),
+    )
+)]
+pub async fn create_webhook<W: WebhookRepository>(
+    ValidatedJson(payload): ValidatedJson<CreateWebhook>,
+    Extension(webhooks): Extension<Webhooks<W>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let webhook = webhooks
+        .repository()
+        .create(payload)
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhook",
+    tag = "webhook",
+    responses((status = 200, description = "All webhooks", body = [Webhook]))
+)]
+pub async fn all_webhooks<W: WebhookRepository>(
+    Extension(webhooks): Extension<Webhooks<W>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let all = webhooks
+        .repository()
+        .all()
+        .await
+        .or(Err(StatusCode::INTERNAL_SERVER_ERROR))?;
+    Ok((StatusCode::OK, Json(all)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/webhook/{id}",
+    tag = "webhook",
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 404, description = "Webhook not found"),
+    )
+)]
+pub async fn delete_webhook<W: WebhookRepository>(
+    Path(id): Path<i32>,
+    Extension(webhooks): Extension<Webhooks<W>>,
+) -> StatusCode {
+    webhooks
+        .repository()
+        .delete(id)
+        .await
+        .map(|_| StatusCode::NO_CONTENT)
+        .unwrap_or_else(status_from_error)
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhook/deliveries",
+    tag = "webhook",
+    responses((status = 200, description = "Recent delivery attempts kept in memory, newest first", body = [Attempt]))
+)]
+pub async fn webhook_deliveries<W: WebhookRepository>(
+    Extension(webhooks): Extension<Webhooks<W>>,
+) -> impl IntoResponse {
+    Json(webhooks.attempts())
+}
@@ -0,0 +1,1466 @@
+pub mod clock;
+pub mod graphql;
+pub mod grpc;
+pub mod handlers;
+pub mod metrics;
+pub mod middlewares;
+pub mod openapi;
+pub mod reminder;
+pub mod repositories;
+pub mod webhook;
+
+use crate::graphql::{build_schema, graphiql, graphql, GRAPHQL_PATH};
+use crate::handlers::{
+    health::{healthz, readyz},
+    label::{all_labels, create_label, delete_label, label_stats, merge_labels, update_label},
+    project::{
+        all_projects, create_project, delete_project, find_project, project_tasks, update_project,
+    },
+    task::{
+        all_tasks, create_task, delete_task, find_task, move_task, task_occurrences, update_task,
+    },
+    webhook::{all_webhooks, create_webhook, delete_webhook, webhook_deliveries},
+};
+use crate::metrics::metrics;
+use crate::middlewares::{
+    idempotency::{idempotency, IdempotencyStore, IDEMPOTENCY_KEY},
+    metrics::MetricsLayer,
+    rate_limit::{RateLimitConfig, RateLimitLayer},
+    request_id::{make_span, MakeRequestUuid},
+};
+use crate::openapi::{openapi_json, swagger_ui, swagger_ui_index, OPENAPI_PATH};
+use crate::repositories::{
+    label::LabelRepository, project::ProjectRepository, task::TaskRepository,
+    webhook::WebhookRepository,
+};
+use crate::webhook::Webhooks;
+use axum::{
+    body::Body,
+    extract::Extension,
+    middleware,
+    routing::{delete, get, patch, post},
+    Router,
+};
+use std::sync::Arc;
+
+use hyper::header::{HeaderName, CONTENT_TYPE, ETAG, IF_MATCH};
+use tower::ServiceBuilder;
+use tower_http::{
+    cors::{Any, CorsLayer, Origin},
+    request_id::{PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
+
+/// ミドルウェアの設定，環境変数からの読み込みは main で行う
+#[derive(Debug, Clone, Default)]
+pub struct AppConfig {
+    pub rate_limits: RateLimitConfig,
+    pub idempotency: IdempotencyStore,
+}
+
+pub fn create_app<
+    Task: TaskRepository,
+    Label: LabelRepository,
+    Project: ProjectRepository,
+    Webhook: WebhookRepository,
+>(
+    task_repository: Task,
+    label_repository: Label,
+    project_repository: Project,
+    webhooks: Webhooks<Webhook>,
+    config: AppConfig,
+) -> Router {
+    let idempotency_store = config.idempotency;
+    let x_request_id = HeaderName::from_static("x-request-id");
+    let task_repository = Arc::new(task_repository);
+    let label_repository = Arc::new(label_repository);
+    let project_repository = Arc::new(project_repository);
+    let schema = build_schema(task_repository.clone(), label_repository.clone());
+    Router::new()
+        .route("/", get(root))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .route(OPENAPI_PATH, get(openapi_json))
+        .route("/swagger-ui", get(swagger_ui_index))
+        .route("/swagger-ui/*tail", get(swagger_ui))
+        .route(
+            "/task",
+            post(create_task::<Task>).get(all_tasks::<Task, Label>),
+        )
+        .route(
+            "/task/:id",
+            get(find_task::<Task>)
+                .delete(delete_task::<Task>)
+                .patch(update_task::<Task>),
+        )
+        .route("/task/:id/occurrences", get(task_occurrences::<Task>))
+        .route("/task/:id/move", post(move_task::<Task>))
+        .route(
+            "/label",
+            post(create_label::<Label>).get(all_labels::<Label>),
+        )
+        .route("/label/stats", get(label_stats::<Label>))
+        .route("/label/:id/merge", post(merge_labels::<Label>))
+        .route(
+            "/label/:id",
+            patch(update_label::<Label>).delete(delete_label::<Label>),
+        )
+        .route(
+            "/project",
+            post(create_project::<Project>).get(all_projects::<Project>),
+        )
+        .route(
+            "/project/:id",
+            get(find_project::<Project>)
+                .patch(update_project::<Project>)
+                .delete(delete_project::<Project>),
+        )
+        .route("/project/:id/tasks", get(project_tasks::<Project, Task>))
+        .route(
+            "/webhook",
+            post(create_webhook::<Webhook>).get(all_webhooks::<Webhook>),
+        )
+        .route("/webhook/:id", delete(delete_webhook::<Webhook>))
+        .route("/webhook/deliveries", get(webhook_deliveries::<Webhook>))
+        .route(GRAPHQL_PATH, get(graphiql).post(graphql::<Task, Label>))
+        .route_layer(MetricsLayer)
+        .layer(Extension(task_repository))
+        .layer(Extension(label_repository))
+        .layer(Extension(project_repository))
+        .layer(Extension(schema))
+        .layer(Extension(webhooks))
+        .layer(middleware::from_fn(move |req, next| {
+            idempotency(req, next, idempotency_store.clone())
+        }))
+        .layer(RateLimitLayer::new(config.rate_limits))
+        .layer(
+            CorsLayer::new()
+                .allow_origin(Origin::exact("http://localhost:3001".parse().unwrap()))
+                .allow_methods(Any)
+                .allow_headers(vec![
+                    CONTENT_TYPE,
+                    IF_MATCH,
+                    IDEMPOTENCY_KEY,
+                    x_request_id.clone(),
+                ])
+                .expose_headers(vec![ETAG, x_request_id.clone()]),
+        )
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::new(
+                    x_request_id.clone(),
+                    MakeRequestUuid,
+                ))
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(make_span::<Body>)
+                        .on_response(DefaultOnResponse::new().level(Level::INFO)),
+                )
+                .layer(PropagateRequestIdLayer::new(x_request_id)),
+        )
+}
+
+async fn root() -> &'static str {
+    "Hello, World!"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::handlers::{
+        health::{Readiness, ReadinessCheck},
+        label::MergedLabels,
+    };
+    use crate::repositories::{
+        label::{CreateLabel, Label, LabelRepositoryForMemory, LabelStats, LabelTree},
+        memory::MemoryStore,
+        metered::Metered,
+        project::ProjectRepositoryForMemory,
+        task::{CreateTask, TaskEntity, TaskRepositoryForMemory},
+        webhook::WebhookRepositoryForMemory,
+        webhooked::Webhooked,
+    };
+    use crate::webhook::{test_utils::Receiver, WebhookConfig};
+    use axum::{
+        async_trait,
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+        response::Response,
+    };
+    use tower::ServiceExt;
+
+    fn build_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
+            .body(Body::from(json_body))
+            .unwrap()
+    }
+
+    fn build_req_with_empty(path: &str, method: Method) -> Request<Body> {
+        Request::builder()
+            .uri(path)
+            .method(method)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn res_to_task(res: Response) -> TaskEntity {
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        let task = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Task instance. body: {}", body));
+        task
+    }
+
+    async fn res_to_label(res: Response) -> Label {
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        let label = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Label instance. body: {}", body));
+        label
+    }
+
+    fn webhooks() -> Webhooks<WebhookRepositoryForMemory> {
+        Webhooks::new(
+            WebhookRepositoryForMemory::default(),
+            WebhookConfig::new(1, std::time::Duration::ZERO, 10),
+        )
+    }
+
+    fn projects() -> ProjectRepositoryForMemory {
+        ProjectRepositoryForMemory::default()
+    }
+
+    fn label_fixture() -> (Vec<Label>, Vec<i32>) {
+        let id = 999;
+        (vec![Label::new(id, String::from("test label"))], vec![id])
+    }
+
+    #[tokio::test]
+    async fn should_created_task() {
+        let (labels, _) = label_fixture();
+        let expected = TaskEntity::new(1, "should_return_created_task".to_string(), labels.clone());
+
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "should_return_created_task", "labels": [999] }"#.to_string(),
+        );
+        let res = create_app(
+            TaskRepositoryForMemory::new(labels),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let task = res_to_task(res).await;
+        assert_eq!(expected, task);
+    }
+
+    #[tokio::test]
+    async fn should_find_task() {
+        let (labels, label_ids) = label_fixture();
+        let expected = TaskEntity::new(1, "should_find_task".to_string(), labels.clone());
+
+        let task_repository = TaskRepositoryForMemory::new(labels.clone());
+        task_repository
+            .create(CreateTask::new("should_find_task".to_string(), label_ids))
+            .await
+            .expect("failed create task");
+        let req = build_req_with_empty("/task/1", Method::GET);
+        let res = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let task = res_to_task(res).await;
+        assert_eq!(expected, task);
+    }
+
+    #[tokio::test]
+    async fn should_get_all_tasks() {
+        let (labels, label_ids) = label_fixture();
+        let expected = TaskEntity::new(1, "should_get_all_tasks".to_string(), labels.clone());
+
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new(
+                "should_get_all_tasks".to_string(),
+                label_ids,
+            ))
+            .await
+            .expect("failed create task");
+        let req = build_req_with_empty("/task", Method::GET);
+        let res = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        let tasks: Vec<TaskEntity> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Task instance. body: {}", body));
+        assert_eq!(vec![expected], tasks);
+    }
+
+    #[tokio::test]
+    async fn should_update_task() {
+        let (labels, label_ids) = label_fixture();
+        let expected = TaskEntity {
+            version: 2,
+            ..TaskEntity::new(1, "should_update_task".to_string(), labels.clone())
+        };
+
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new("before_update_task".to_string(), label_ids))
+            .await
+            .expect("failed create task");
+        let req = build_req_with_json(
+            "/task/1",
+            Method::PATCH,
+            r#"{
+                "text": "should_update_task",
+                "completed": false
+            }"#
+            .to_string(),
+        );
+        let res = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let task = res_to_task(res).await;
+        assert_eq!(expected, task);
+    }
+
+    #[tokio::test]
+    async fn should_return_etag_on_find_task() {
+        let (labels, label_ids) = label_fixture();
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new(
+                "should_return_etag_on_find_task".to_string(),
+                label_ids,
+            ))
+            .await
+            .expect("failed create task");
+        let req = build_req_with_empty("/task/1", Method::GET);
+        let res = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(res.headers()[header::ETAG], "\"1\"");
+    }
+
+    #[tokio::test]
+    async fn should_reject_update_task_with_stale_if_match() {
+        let (labels, label_ids) = label_fixture();
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new("before_update_task".to_string(), label_ids))
+            .await
+            .expect("failed create task");
+        let app = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let mut req = build_req_with_json(
+            "/task/1",
+            Method::PATCH,
+            r#"{ "completed": true }"#.to_string(),
+        );
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"1\"".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!(res.headers()[header::ETAG], "\"2\"");
+
+        let mut req = build_req_with_json(
+            "/task/1",
+            Method::PATCH,
+            r#"{ "completed": false }"#.to_string(),
+        );
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"1\"".parse().unwrap());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_answer_500_when_repository_fails() {
+        let pool = crate::repositories::test_utils::sqlite_memory_pool().await;
+        let app = create_app(
+            crate::repositories::task::TaskRepositoryForSqlite::new(pool.clone()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+        pool.close().await;
+
+        for req in [
+            build_req_with_empty("/task/1", Method::GET),
+            build_req_with_empty("/task/1/occurrences", Method::GET),
+            build_req_with_json(
+                "/task",
+                Method::POST,
+                r#"{ "text": "task", "labels": [] }"#.to_string(),
+            ),
+            build_req_with_json(
+                "/task/1",
+                Method::PATCH,
+                r#"{ "completed": true }"#.to_string(),
+            ),
+            build_req_with_empty("/task/1", Method::DELETE),
+        ] {
+            let uri = req.uri().clone();
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res.status(), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_compare_if_match_lists_strongly() {
+        let (labels, label_ids) = label_fixture();
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new("before_update_task".to_string(), label_ids))
+            .await
+            .expect("failed create task");
+        let app = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        for (if_match, status) in [
+            ("W/\"1\"", StatusCode::PRECONDITION_FAILED),
+            ("\"3\", W/\"1\"", StatusCode::PRECONDITION_FAILED),
+            ("\"3\", \"1\"", StatusCode::CREATED),
+            ("\"other\",\"2\"", StatusCode::CREATED),
+            ("1", StatusCode::BAD_REQUEST),
+        ] {
+            let mut req = build_req_with_json(
+                "/task/1",
+                Method::PATCH,
+                r#"{ "completed": true }"#.to_string(),
+            );
+            req.headers_mut()
+                .insert(header::IF_MATCH, if_match.parse().unwrap());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(status, res.status(), "If-Match: {}", if_match);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_reject_delete_task_with_stale_if_match() {
+        let (labels, label_ids) = label_fixture();
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new("should_delete_task".to_string(), label_ids))
+            .await
+            .expect("failed create task");
+        let mut req = build_req_with_empty("/task/1", Method::DELETE);
+        req.headers_mut()
+            .insert(header::IF_MATCH, "\"2\"".parse().unwrap());
+        let res = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::PRECONDITION_FAILED, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_delete_task() {
+        let (labels, label_ids) = label_fixture();
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        task_repository
+            .create(CreateTask::new("should_delete_task".to_string(), label_ids))
+            .await
+            .expect("failed create task");
+        let req = build_req_with_empty("/task/1", Method::DELETE);
+        let res = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_move_task() {
+        let task_repository = TaskRepositoryForMemory::new(vec![]);
+        for text in ["first", "second", "third"] {
+            task_repository
+                .create(CreateTask::new(text.to_string(), vec![]))
+                .await
+                .expect("failed create task");
+        }
+        let app = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let req = build_req_with_json(
+            "/task/1/move",
+            Method::POST,
+            r#"{ "after": 3 }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert_eq!(res_to_task(res).await.version, 1);
+
+        let req = build_req_with_empty("/task", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let tasks: Vec<TaskEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            tasks.iter().map(|task| task.id).collect::<Vec<_>>(),
+            vec![3, 1, 2]
+        );
+
+        for (body, status) in [
+            (r#"{}"#, StatusCode::BAD_REQUEST),
+            (r#"{ "after": 2, "before": 3 }"#, StatusCode::CONFLICT),
+            (r#"{ "before": 999 }"#, StatusCode::NOT_FOUND),
+        ] {
+            let req = build_req_with_json("/task/1/move", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(status, res.status(), "{}", body);
+        }
+    }
+
+    #[tokio::test]
+    async fn should_replay_created_task_with_same_idempotency_key() {
+        let (labels, _) = label_fixture();
+        let task_repository = TaskRepositoryForMemory::new(labels);
+        let app = create_app(
+            task_repository.clone(),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+        let build_req = |json_body: &str| {
+            let mut req = build_req_with_json("/task", Method::POST, json_body.to_string());
+            req.headers_mut()
+                .insert(IDEMPOTENCY_KEY, "create-task-1".parse().unwrap());
+            req
+        };
+
+        let res = app
+            .clone()
+            .oneshot(build_req(
+                r#"{ "text": "idempotent task", "labels": [999] }"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let created = res_to_task(res).await;
+
+        let res = app
+            .clone()
+            .oneshot(build_req(
+                r#"{ "text": "idempotent task", "labels": [999] }"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!(res.headers()["idempotent-replayed"], "true");
+        let replayed = res_to_task(res).await;
+        assert_eq!(created, replayed);
+        assert_eq!(1, task_repository.all().await.unwrap().len());
+
+        // 同じキーで異なる body
+        let res = app
+            .oneshot(build_req(r#"{ "text": "another task", "labels": [999] }"#))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_create_label_for_each_idempotency_key() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let app = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            label_repository.clone(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+        for key in ["create-label-1", "create-label-2", "create-label-2"] {
+            let mut req = build_req_with_json(
+                "/label",
+                Method::POST,
+                format!(r#"{{ "name": "label for {}" }}"#, key),
+            );
+            req.headers_mut()
+                .insert(IDEMPOTENCY_KEY, key.parse().unwrap());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        assert_eq!(2, label_repository.all().await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn should_limit_rate_with_given_config() {
+        let config = AppConfig {
+            rate_limits: "POST /label=1/60".parse().unwrap(),
+            ..AppConfig::default()
+        };
+        let app = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            config,
+        );
+        for (name, status) in [
+            ("first", StatusCode::CREATED),
+            ("second", StatusCode::TOO_MANY_REQUESTS),
+        ] {
+            let req = build_req_with_json(
+                "/label",
+                Method::POST,
+                format!(r#"{{ "name": "{}" }}"#, name),
+            );
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(status, res.status());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_spawn_next_occurrence_on_completion() {
+        let app = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "weekly", "labels": [], "recurrence": "FREQ=YEARLY" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "daily", "labels": [], "recurrence": "FREQ=DAILY", "due_date": "+262142-12-31" }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "weekly", "labels": [], "recurrence": "FREQ=WEEKLY", "due_date": "2024-01-01" }"#
+                .to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let req = build_req_with_json(
+            "/task/1",
+            Method::PATCH,
+            r#"{ "completed": true }"#.to_string(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+
+        let req = build_req_with_empty("/task/1/occurrences", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let tasks: Vec<TaskEntity> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks[0].completed);
+        assert_eq!(tasks[1].due_date, Some("2024-01-08".parse().unwrap()));
+        assert_eq!(tasks[1].series_id, Some(1));
+    }
+
+    #[tokio::test]
+    async fn should_manage_projects() {
+        let store = MemoryStore::new();
+        let app = create_app(
+            TaskRepositoryForMemory::with_store(store.clone()),
+            LabelRepositoryForMemory::with_store(store.clone()),
+            ProjectRepositoryForMemory::with_store(store),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let req = build_req_with_json("/project", Method::POST, r#"{ "name": "" }"#.to_string());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        for name in ["home", "work"] {
+            let req = build_req_with_json(
+                "/project",
+                Method::POST,
+                format!(r#"{{ "name": "{}" }}"#, name),
+            );
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        let req = build_req_with_json(
+            "/project/2",
+            Method::PATCH,
+            r#"{ "name": "office" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "report", "labels": [], "project_id": 1 }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert_eq!(res_to_task(res).await.project_id, Some(1));
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "inbox", "labels": [] }"#.to_string(),
+        );
+        app.clone().oneshot(req).await.unwrap();
+
+        let project_tasks = |id: i32| {
+            let app = app.clone();
+            async move {
+                let req = build_req_with_empty(&format!("/project/{}/tasks", id), Method::GET);
+                let res = app.oneshot(req).await.unwrap();
+                assert_eq!(StatusCode::OK, res.status());
+                let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+                serde_json::from_slice::<Vec<TaskEntity>>(&bytes)
+                    .unwrap()
+                    .iter()
+                    .map(|task| task.text.clone())
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(project_tasks(1).await, vec!["report"]);
+        assert!(project_tasks(2).await.is_empty());
+
+        // タスクが残っている間は削除できない
+        let req = build_req_with_empty("/project/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+
+        let req = build_req_with_json(
+            "/task/1",
+            Method::PATCH,
+            r#"{ "project_id": 2 }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        assert!(project_tasks(1).await.is_empty());
+        assert_eq!(project_tasks(2).await, vec!["report"]);
+
+        let req = build_req_with_empty("/project/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty("/project/1/tasks", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/project", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let projects: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(projects, serde_json::json!([{ "id": 2, "name": "office" }]));
+    }
+
+    #[tokio::test]
+    async fn should_serve_openapi_document() {
+        let req = build_req_with_empty("/openapi.json", Method::GET);
+        let res = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let doc: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+
+        assert!(doc["paths"]["/task/{id}"]["patch"].is_object());
+        assert!(doc["paths"]["/label"]["post"].is_object());
+        let text = &doc["components"]["schemas"]["CreateTask"]["properties"]["text"];
+        assert_eq!(text["minLength"], 1);
+        assert_eq!(text["maxLength"], 100);
+    }
+
+    #[tokio::test]
+    async fn should_serve_swagger_ui() {
+        let req = build_req_with_empty("/swagger-ui/", Method::GET);
+        let res = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(res.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+
+    #[tokio::test]
+    async fn should_serve_graphql() {
+        let label = Label::new(1, "graphql".to_string());
+        let task_repository = TaskRepositoryForMemory::new(vec![label.clone()]);
+        task_repository
+            .create(CreateTask::new("graphql task".to_string(), vec![label.id]))
+            .await
+            .expect("failed create task");
+        let app = create_app(
+            task_repository,
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let req = build_req_with_json(
+            "/graphql",
+            Method::POST,
+            r#"{ "query": "{ tasks { text labels { name } } }" }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({ "data": { "tasks": [
+                { "text": "graphql task", "labels": [{ "name": "graphql" }] },
+            ] } })
+        );
+
+        let req = build_req_with_empty("/graphql", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        assert!(res.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .starts_with("text/html"));
+    }
+
+    #[tokio::test]
+    async fn should_manage_webhooks() {
+        let receiver = Receiver::spawn(0);
+        let store = MemoryStore::new();
+        let webhooks = Webhooks::new(
+            WebhookRepositoryForMemory::with_store(store.clone()),
+            WebhookConfig::new(1, std::time::Duration::ZERO, 10),
+        );
+        let app = create_app(
+            Webhooked::new(
+                TaskRepositoryForMemory::with_store(store.clone()),
+                webhooks.clone(),
+            ),
+            Webhooked::new(
+                LabelRepositoryForMemory::with_store(store),
+                webhooks.clone(),
+            ),
+            projects(),
+            webhooks,
+            AppConfig::default(),
+        );
+
+        let req = build_req_with_json(
+            "/webhook",
+            Method::POST,
+            format!(
+                r#"{{ "url": "{}", "secret": "0123456789abcdef", "events": ["task.created"] }}"#,
+                receiver.url
+            ),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let webhook: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        // secret は返さない
+        assert_eq!(
+            webhook,
+            serde_json::json!({ "id": 1, "url": receiver.url, "events": ["task.created"] })
+        );
+
+        let req = build_req_with_json(
+            "/webhook",
+            Method::POST,
+            r#"{ "url": "not a url", "secret": "short", "events": [] }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_req_with_json(
+            "/task",
+            Method::POST,
+            r#"{ "text": "hooked", "labels": [] }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let received = receiver.wait_for(1).await;
+        assert_eq!(received[0].json()["data"]["text"], "hooked");
+
+        // 送信結果は記録されてから返る
+        let mut deliveries = serde_json::Value::Null;
+        for _ in 0..100 {
+            let req = build_req_with_empty("/webhook/deliveries", Method::GET);
+            let res = app.clone().oneshot(req).await.unwrap();
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            deliveries = serde_json::from_slice(&bytes).unwrap();
+            if deliveries.as_array().is_some_and(|d| !d.is_empty()) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(deliveries[0]["webhook_id"], 1);
+        assert_eq!(deliveries[0]["event"], "task.created");
+        assert_eq!(deliveries[0]["status"], 204);
+
+        let req = build_req_with_empty("/webhook/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+        let req = build_req_with_empty("/webhook/1", Method::DELETE);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_req_with_empty("/webhook", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(&bytes[..], b"[]");
+    }
+
+    #[tokio::test]
+    async fn should_expose_metrics() {
+        let app = create_app(
+            Metered::new(TaskRepositoryForMemory::new(Vec::new())),
+            Metered::new(LabelRepositoryForMemory::new()),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+        let req = build_req_with_empty("/task", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let req = build_req_with_empty("/metrics", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(body.contains(r#"http_requests_total{method="GET",route="/task",status="200"}"#));
+        assert!(body.contains(r#"http_request_duration_seconds_count{method="GET",route="/task"}"#));
+        assert!(body.contains(
+            r#"repository_operation_duration_seconds_count{operation="all",outcome="ok",repository="task"}"#
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_be_alive() {
+        let req = build_req_with_empty("/healthz", Method::GET);
+        let res = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+    }
+
+    struct StaticCheck(&'static str, bool);
+
+    #[async_trait]
+    impl ReadinessCheck for StaticCheck {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        async fn check(&self) -> anyhow::Result<()> {
+            if self.1 {
+                Ok(())
+            } else {
+                anyhow::bail!("{} is down", self.0)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn should_report_readiness_checks() {
+        let app = |readiness: Readiness| {
+            create_app(
+                TaskRepositoryForMemory::new(Vec::new()),
+                LabelRepositoryForMemory::new(),
+                projects(),
+                webhooks(),
+                AppConfig::default(),
+            )
+            .layer(Extension(readiness))
+        };
+
+        let readiness = Readiness::new().check(StaticCheck("database", true));
+        let req = build_req_with_empty("/readyz", Method::GET);
+        let res = app(readiness).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+
+        let readiness = Readiness::new()
+            .check(StaticCheck("database", true))
+            .check(StaticCheck("migrations", false));
+        let req = build_req_with_empty("/readyz", Method::GET);
+        let res = app(readiness).oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(report["status"], "error");
+        assert_eq!(report["checks"][0]["status"], "ok");
+        assert!(report["checks"][0]["latency_ms"].is_number());
+        assert_eq!(report["checks"][1]["name"], "migrations");
+        assert_eq!(report["checks"][1]["error"], "migrations is down");
+    }
+
+    #[tokio::test]
+    async fn should_propagate_request_id() {
+        let app = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let mut req = build_req_with_empty("/task", Method::GET);
+        req.headers_mut()
+            .insert("x-request-id", "client-request-id".parse().unwrap());
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.headers()["x-request-id"], "client-request-id");
+
+        let req = build_req_with_empty("/task", Method::GET);
+        let res = app.oneshot(req).await.unwrap();
+        let request_id = res.headers()["x-request-id"].to_str().unwrap();
+        assert_eq!(request_id.len(), 36);
+    }
+
+    #[tokio::test]
+    async fn should_created_label() {
+        let (_labels, _) = label_fixture();
+        let expected = Label {
+            position: 1,
+            ..Label::new(1, "should_created_label".to_string())
+        };
+
+        let req = build_req_with_json(
+            "/label",
+            Method::POST,
+            r#"{ "name": "should_created_label" }"#.to_string(),
+        );
+        let res = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let label = res_to_label(res).await;
+        assert_eq!(expected, label);
+    }
+
+    #[tokio::test]
+    async fn should_all_label_readed() {
+        let expected = Label {
+            position: 1,
+            ..Label::new(1, "should_all_label_readed".to_string())
+        };
+        let label_repository = LabelRepositoryForMemory::new();
+        let label = label_repository
+            .create(CreateLabel::new("should_all_label_readed".to_string()))
+            .await
+            .expect("failed create label");
+
+        let req = build_req_with_empty("/label", Method::GET);
+        let res = create_app(
+            TaskRepositoryForMemory::new(vec![label]),
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let body = String::from_utf8(bytes.to_vec()).unwrap();
+        let labels: Vec<Label> = serde_json::from_str(&body)
+            .unwrap_or_else(|_| panic!("cannot convert Label list instance. body: {}", body));
+        assert_eq!(vec![expected], labels);
+    }
+
+    #[tokio::test]
+    async fn should_update_label() {
+        let app = create_app(
+            TaskRepositoryForMemory::new(Vec::new()),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+        let req = build_req_with_json(
+            "/label",
+            Method::POST,
+            r##"{ "name": "work", "color": "#1e90ff", "description": "office" }"##.to_string(),
+        );
+        let label = res_to_label(app.clone().oneshot(req).await.unwrap()).await;
+        assert_eq!(label.color.as_deref(), Some("#1e90ff"));
+        assert_eq!(label.description.as_deref(), Some("office"));
+
+        for body in [
+            r#"{ "name": "bad", "color": "red" }"#,
+            r##"{ "name": "bad", "color": "#12345g" }"##,
+        ] {
+            let req = build_req_with_json("/label", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(StatusCode::BAD_REQUEST, res.status());
+        }
+
+        let req = build_req_with_json(
+            "/label/1",
+            Method::PATCH,
+            r#"{ "color": "", "position": 5 }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let label = res_to_label(res).await;
+        assert_eq!(label.color, None);
+        assert_eq!(label.description.as_deref(), Some("office"));
+        assert_eq!(label.position, 5);
+
+        let req = build_req_with_json(
+            "/label/1",
+            Method::PATCH,
+            r##"{ "color": "#zzzzzz" }"##.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::BAD_REQUEST, res.status());
+
+        let req = build_req_with_json("/label/2", Method::PATCH, r#"{ "name": "x" }"#.to_string());
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_filter_tasks_by_label_tree() {
+        let store = MemoryStore::new();
+        let label_repository = LabelRepositoryForMemory::with_store(store.clone());
+        let work = label_repository
+            .create(CreateLabel::new("work".to_string()))
+            .await
+            .unwrap();
+        let client = label_repository
+            .create(CreateLabel::new("client".to_string()).with_parent(Some(work.id)))
+            .await
+            .unwrap();
+        let task_repository = TaskRepositoryForMemory::with_store(store);
+        for (text, label) in [("plan", work.id), ("invoice", client.id)] {
+            task_repository
+                .create(CreateTask::new(text.to_string(), vec![label]))
+                .await
+                .unwrap();
+        }
+        let app = create_app(
+            task_repository,
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        async fn texts(res: Response) -> Vec<String> {
+            let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+            let tasks: Vec<TaskEntity> = serde_json::from_slice(&bytes).unwrap();
+            tasks.into_iter().map(|task| task.text).collect()
+        }
+        let req = build_req_with_empty("/task?label=1", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(texts(res).await, vec!["plan"]);
+        let req = build_req_with_empty("/task?label=1&descendants=true", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(texts(res).await, vec!["invoice", "plan"]);
+        // 存在しないラベルは descendants の指定に関わらず 404
+        let req = build_req_with_empty("/task?label=9", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+        let req = build_req_with_empty("/task?label=9&descendants=true", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+
+        let req = build_req_with_empty("/label?tree=true", Method::GET);
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let tree: Vec<LabelTree> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].label, work);
+        assert_eq!(tree[0].children[0].label, client);
+
+        // 循環になる付け替え
+        let req = build_req_with_json(
+            "/label/1",
+            Method::PATCH,
+            r#"{ "parent_id": 2 }"#.to_string(),
+        );
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_report_label_stats() {
+        let store = MemoryStore::new();
+        let label_repository = LabelRepositoryForMemory::with_store(store.clone());
+        let label = label_repository
+            .create(CreateLabel::new("stats".to_string()))
+            .await
+            .unwrap();
+        let task_repository = TaskRepositoryForMemory::with_store(store);
+        task_repository
+            .create(CreateTask::new("task".to_string(), vec![label.id]))
+            .await
+            .unwrap();
+
+        let req = build_req_with_empty("/label/stats", Method::GET);
+        let res = create_app(
+            task_repository,
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let stats: Vec<LabelStats> = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].label_id, label.id);
+        assert_eq!((stats[0].open_tasks, stats[0].completed_tasks), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn should_merge_labels() {
+        let store = MemoryStore::new();
+        let label_repository = LabelRepositoryForMemory::with_store(store.clone());
+        for name in ["bug", "Bug", "bugs"] {
+            label_repository
+                .create(CreateLabel::new(name.to_string()))
+                .await
+                .unwrap();
+        }
+        let task_repository = TaskRepositoryForMemory::with_store(store);
+        // 統合先と統合元の両方が付いたタスクと，統合元だけが付いたタスク
+        for labels in [vec![1, 2, 3], vec![3]] {
+            task_repository
+                .create(CreateTask::new("task".to_string(), labels))
+                .await
+                .unwrap();
+        }
+        let app = create_app(
+            task_repository,
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        for (body, status) in [
+            (r#"{ "sources": [] }"#, StatusCode::BAD_REQUEST),
+            (r#"{ "sources": [1] }"#, StatusCode::CONFLICT),
+            (r#"{ "sources": [9] }"#, StatusCode::NOT_FOUND),
+        ] {
+            let req = build_req_with_json("/label/1/merge", Method::POST, body.to_string());
+            let res = app.clone().oneshot(req).await.unwrap();
+            assert_eq!(status, res.status(), "{}", body);
+        }
+
+        let req = build_req_with_json(
+            "/label/1/merge",
+            Method::POST,
+            r#"{ "sources": [2, 3] }"#.to_string(),
+        );
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::OK, res.status());
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        let merged: MergedLabels = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            merged,
+            MergedLabels {
+                affected_tasks: 2,
+                removed_labels: vec![2, 3],
+            }
+        );
+
+        for id in [1, 2] {
+            let req = build_req_with_empty(&format!("/task/{}", id), Method::GET);
+            let task = res_to_task(app.clone().oneshot(req).await.unwrap()).await;
+            assert_eq!(
+                task.labels.iter().map(|label| label.id).collect::<Vec<_>>(),
+                vec![1]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn should_delete_label() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let label = label_repository
+            .create(CreateLabel::new("should_delete_label".to_string()))
+            .await
+            .expect("failed create label");
+        let req = build_req_with_empty("/label/1", Method::DELETE);
+        let res = create_app(
+            TaskRepositoryForMemory::new(vec![label]),
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NO_CONTENT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_not_delete_label_with_children() {
+        let label_repository = LabelRepositoryForMemory::new();
+        let parent = label_repository
+            .create(CreateLabel::new("parent".to_string()))
+            .await
+            .expect("failed create label");
+        label_repository
+            .create(CreateLabel::new("child".to_string()).with_parent(Some(parent.id)))
+            .await
+            .expect("failed create label");
+        let app = create_app(
+            TaskRepositoryForMemory::new(vec![]),
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        );
+
+        let req = build_req_with_empty(&format!("/label/{}", parent.id), Method::DELETE);
+        let res = app.oneshot(req).await.unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_not_delete_label_in_use() {
+        let store = MemoryStore::new();
+        let label_repository = LabelRepositoryForMemory::with_store(store.clone());
+        let label = label_repository
+            .create(CreateLabel::new("label".to_string()))
+            .await
+            .expect("failed create label");
+        let task_repository = TaskRepositoryForMemory::with_store(store);
+        task_repository
+            .create(CreateTask::new("task".to_string(), vec![label.id]))
+            .await
+            .expect("failed create task");
+        let req = build_req_with_empty(&format!("/label/{}", label.id), Method::DELETE);
+        let res = create_app(
+            task_repository,
+            label_repository,
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::CONFLICT, res.status());
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_when_deleting_missing_label() {
+        let req = build_req_with_empty("/label/99", Method::DELETE);
+        let res = create_app(
+            TaskRepositoryForMemory::new(vec![]),
+            LabelRepositoryForMemory::new(),
+            projects(),
+            webhooks(),
+            AppConfig::default(),
+        )
+        .oneshot(req)
+        .await
+        .unwrap();
+        assert_eq!(StatusCode::NOT_FOUND, res.status());
+    }
+}
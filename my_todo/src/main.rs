@@ -1,307 +1,289 @@
-mod handlers;
-mod repositories;
-
-use crate::handlers::{
-    label::{all_labels, create_label, delete_label},
-    task::{all_tasks, create_task, delete_task, find_task, update_task},
-};
-use crate::repositories::{
-    label::{LabelRepository, LabelRepositoryForDb},
-    task::{TaskRepository, TaskRepositoryForDb},
-};
-use axum::{
-    extract::Extension,
-    routing::{delete, get, post},
-    Router,
+use axum::{extract::Extension, Router};
+use my_todo::{
+    create_app,
+    grpc::create_grpc_server,
+    handlers::health::{DatabaseCheck, MigrationCheck, Readiness},
+    metrics::PoolCollector,
+    middlewares::{idempotency::IdempotencyStore, rate_limit::RateLimitConfig},
+    reminder::{
+        notifier::{LogNotifier, Notifier, SmtpNotifier, WebhookNotifier},
+        ReminderConfig, Scheduler,
+    },
+    repositories::{
+        cached::{CacheConfig, Cached},
+        delivery::{
+            DeliveryRepository, DeliveryRepositoryForDb, DeliveryRepositoryForFile,
+            DeliveryRepositoryForMemory, DeliveryRepositoryForSqlite,
+        },
+        file::FileStore,
+        label::LabelRepository,
+        label::{
+            LabelRepositoryForDb, LabelRepositoryForFile, LabelRepositoryForMemory,
+            LabelRepositoryForSqlite,
+        },
+        memory::MemoryStore,
+        metered::Metered,
+        migrate::prepare_schema,
+        project::{
+            ProjectRepository, ProjectRepositoryForDb, ProjectRepositoryForFile,
+            ProjectRepositoryForMemory, ProjectRepositoryForSqlite,
+        },
+        task::TaskRepository,
+        task::{
+            TaskRepositoryForDb, TaskRepositoryForFile, TaskRepositoryForMemory,
+            TaskRepositoryForSqlite,
+        },
+        webhook::{
+            WebhookRepository, WebhookRepositoryForDb, WebhookRepositoryForFile,
+            WebhookRepositoryForMemory, WebhookRepositoryForSqlite,
+        },
+        webhooked::Webhooked,
+        MIGRATOR, SQLITE_MIGRATOR,
+    },
+    webhook::{WebhookConfig, Webhooks},
+    AppConfig,
 };
+use std::env;
 use std::net::SocketAddr;
-use std::{env, sync::Arc};
+use std::path::PathBuf;
+use std::sync::Arc;
 
+use clap::{ArgAction, Parser, ValueEnum};
 use dotenv::dotenv;
-use hyper::header::CONTENT_TYPE;
-use sqlx::PgPool;
-use tower_http::cors::{Any, CorsLayer, Origin};
-
-#[tokio::main]
-async fn main() {
-    // logging
-    let log_level = env::var("RUST_LOG").unwrap_or("info".to_string());
-    env::set_var("RUST_LOG", log_level);
-    tracing_subscriber::fmt::init();
-    dotenv().ok();
-
-    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL");
-    tracing::debug!("start connect database...");
-    let pool = PgPool::connect(database_url)
-        .await
-        .expect(&format!("fail connect database, url is [{}]", database_url));
+use sqlx::{PgPool, SqlitePool};
+use tonic::transport::server::Router as GrpcRouter;
+use tracing_subscriber::EnvFilter;
 
-    let app = create_app(
-        TaskRepositoryForDb::new(pool.clone()),
-        LabelRepositoryForDb::new(pool.clone()),
-    );
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    tracing::debug!("listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+#[derive(Debug, Parser)]
+struct Args {
+    /// データの保存先
+    #[arg(long, value_enum, default_value_t = Storage::Database)]
+    storage: Storage,
+    /// `--storage=file` で使うデータファイル
+    #[arg(long, env = "DATA_FILE", default_value = "my_todo.jsonl")]
+    data_file: PathBuf,
+    /// 起動時に未適用のマイグレーションを適用する
+    #[arg(long, env = "AUTO_MIGRATE", default_value_t = true, action = ArgAction::Set)]
+    auto_migrate: bool,
+    /// gRPC サーバーのポート
+    #[arg(long, env = "GRPC_PORT", default_value_t = 50051)]
+    grpc_port: u16,
+    /// リマインダーの送信先
+    #[arg(long, value_enum, env = "REMINDER_NOTIFIER", default_value_t = NotifierKind::Log)]
+    notifier: NotifierKind,
 }
 
-fn create_app<Task: TaskRepository, Label: LabelRepository>(
-    task_repository: Task,
-    label_repository: Label,
-) -> Router {
-    Router::new()
-        .route("/", get(root))
-        .route("/task", post(create_task::<Task>).get(all_tasks::<Task>))
-        .route(
-            "/task/:id",
-            get(find_task::<Task>)
-                .delete(delete_task::<Task>)
-                .patch(update_task::<Task>),
-        )
-        .route(
-            "/label",
-            post(create_label::<Label>).get(all_labels::<Label>),
-        )
-        .route("/label/:id", delete(delete_label::<Label>))
-        .layer(Extension(Arc::new(task_repository)))
-        .layer(Extension(Arc::new(label_repository)))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Origin::exact("http://localhost:3001".parse().unwrap()))
-                .allow_methods(Any)
-                .allow_headers(vec![CONTENT_TYPE]),
-        )
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Storage {
+    /// DATABASE_URL の DB (postgres / sqlite)
+    Database,
+    /// プロセス内のメモリ，終了すると消える
+    Memory,
+    /// ローカルのデータファイル
+    File,
 }
 
-async fn root() -> &'static str {
-    "Hello, World!"
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum NotifierKind {
+    /// ログに出すだけ
+    Log,
+    /// SMTP_HOST / SMTP_PORT の MTA から REMINDER_MAIL_TO へ送る
+    Smtp,
+    /// REMINDER_WEBHOOK_URL へ POST する
+    Webhook,
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::repositories::{
-        label::{test_utils::LabelRepositoryForMemory, Label},
-        task::{test_utils::TaskRepositoryForMemory, CreateTask, TaskEntity},
-    };
-    use axum::{
-        body::Body,
-        http::{header, Method, Request, StatusCode},
-        response::Response,
-    };
-    use tower::ServiceExt;
-
-    fn build_req_with_json(path: &str, method: Method, json_body: String) -> Request<Body> {
-        Request::builder()
-            .uri(path)
-            .method(method)
-            .header(header::CONTENT_TYPE, mime::APPLICATION_JSON.as_ref())
-            .body(Body::from(json_body))
-            .unwrap()
-    }
-
-    fn build_req_with_empty(path: &str, method: Method) -> Request<Body> {
-        Request::builder()
-            .uri(path)
-            .method(method)
-            .body(Body::empty())
-            .unwrap()
-    }
-
-    async fn res_to_task(res: Response) -> TaskEntity {
-        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
-        let body = String::from_utf8(bytes.to_vec()).unwrap();
-        let task = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Task instance. body: {}", body));
-        task
-    }
-
-    async fn res_to_label(res: Response) -> Label {
-        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
-        let body = String::from_utf8(bytes.to_vec()).unwrap();
-        let label = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Label instance. body: {}", body));
-        label
+impl NotifierKind {
+    fn build(self) -> anyhow::Result<Arc<dyn Notifier>> {
+        Ok(match self {
+            NotifierKind::Log => Arc::new(LogNotifier),
+            NotifierKind::Smtp => Arc::new(SmtpNotifier::from_env()?),
+            NotifierKind::Webhook => Arc::new(WebhookNotifier::from_env()?),
+        })
     }
+}
 
-    fn label_fixture() -> (Vec<Label>, Vec<i32>) {
-        let id = 999;
-        (
-            vec![Label {
-                id,
-                name: String::from("test label"),
-            }],
-            vec![id],
-        )
-    }
-
-    #[tokio::test]
-    async fn should_created_task() {
-        let (labels, _) = label_fixture();
-        let expected = TaskEntity::new(1, "should_return_created_task".to_string(), labels.clone());
-
-        let req = build_req_with_json(
-            "/task",
-            Method::POST,
-            r#"{ "text": "should_return_created_task", "labels": [999] }"#.to_string(),
-        );
-        let res = create_app(
-            TaskRepositoryForMemory::new(labels),
-            LabelRepositoryForMemory::new(),
-        )
-        .oneshot(req)
-        .await
-        .unwrap();
-        let task = res_to_task(res).await;
-        assert_eq!(expected, task);
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    // logging
+    let log_level = env::var("RUST_LOG").unwrap_or("info".to_string());
+    env::set_var("RUST_LOG", log_level);
+    let subscriber = tracing_subscriber::fmt().with_env_filter(EnvFilter::from_default_env());
+    // LOG_FORMAT=json で構造化ログ (1 行 1 JSON) を出力する
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => subscriber.json().init(),
+        _ => subscriber.init(),
     }
 
-    #[tokio::test]
-    async fn should_find_task() {
-        let (labels, label_ids) = label_fixture();
-        let expected = TaskEntity::new(1, "should_find_task".to_string(), labels.clone());
-
-        let task_repository = TaskRepositoryForMemory::new(labels.clone());
-        task_repository
-            .create(CreateTask::new("should_find_task".to_string(), label_ids))
-            .await
-            .expect("failed create task");
-        let req = build_req_with_empty("/task/1", Method::GET);
-        let res = create_app(task_repository, LabelRepositoryForMemory::new())
-            .oneshot(req)
-            .await
-            .unwrap();
-        let task = res_to_task(res).await;
-        assert_eq!(expected, task);
+    let args = Args::parse();
+    let notifier = args
+        .notifier
+        .build()
+        .unwrap_or_else(|e| panic!("fail build notifier: {:#}", e));
+    let (app, grpc) = match args.storage {
+        Storage::Memory => {
+            tracing::info!("using in-memory storage, data is lost on shutdown");
+            let store = MemoryStore::new();
+            let (tasks, labels) = decorate(
+                TaskRepositoryForMemory::with_store(store.clone()),
+                LabelRepositoryForMemory::with_store(store.clone()),
+            );
+            create_apps(
+                tasks,
+                labels,
+                Metered::new(ProjectRepositoryForMemory::with_store(store.clone())),
+                DeliveryRepositoryForMemory::with_store(store.clone()),
+                WebhookRepositoryForMemory::with_store(store),
+                notifier,
+            )
+        }
+        Storage::File => {
+            let store = FileStore::open(&args.data_file).unwrap_or_else(|e| {
+                panic!(
+                    "fail open data file [{}]: {:#}",
+                    args.data_file.display(),
+                    e
+                )
+            });
+            let (tasks, labels) = decorate(
+                TaskRepositoryForFile::new(store.clone()),
+                LabelRepositoryForFile::new(store.clone()),
+            );
+            create_apps(
+                tasks,
+                labels,
+                Metered::new(ProjectRepositoryForFile::new(store.clone())),
+                DeliveryRepositoryForFile::new(store.clone()),
+                WebhookRepositoryForFile::new(store),
+                notifier,
+            )
+        }
+        Storage::Database => database_apps(args.auto_migrate, notifier).await,
+    };
+    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    let grpc_addr = SocketAddr::from(([127, 0, 0, 1], args.grpc_port));
+    tracing::debug!("listening on {}, gRPC on {}", addr, grpc_addr);
+    let http =
+        axum::Server::bind(&addr).serve(app.into_make_service_with_connect_info::<SocketAddr, _>());
+    // どちらかが終了したらプロセスごと終了する
+    tokio::select! {
+        res = http => res.unwrap(),
+        res = grpc.serve(grpc_addr) => res.unwrap(),
     }
+}
 
-    #[tokio::test]
-    async fn should_get_all_tasks() {
-        let (labels, label_ids) = label_fixture();
-        let expected = TaskEntity::new(1, "should_get_all_tasks".to_string(), labels.clone());
+/// REST と gRPC で repository (キャッシュ含む) を共有する
+/// リマインダーも同じ repository を使ってバックグラウンドで開始する
+/// どちらから変更しても webhook へイベントを送る
+fn create_apps<T, L, P, D, W>(
+    task_repository: T,
+    label_repository: L,
+    project_repository: P,
+    delivery_repository: D,
+    webhook_repository: W,
+    notifier: Arc<dyn Notifier>,
+) -> (Router, GrpcRouter)
+where
+    T: TaskRepository,
+    L: LabelRepository,
+    P: ProjectRepository,
+    D: DeliveryRepository,
+    W: WebhookRepository,
+{
+    let webhooks = Webhooks::new(webhook_repository, WebhookConfig::from_env());
+    let task_repository = Webhooked::new(task_repository, webhooks.clone());
+    let label_repository = Webhooked::new(label_repository, webhooks.clone());
+    let scheduler = Scheduler::new(
+        task_repository.clone(),
+        delivery_repository,
+        notifier,
+        ReminderConfig::from_env(),
+    );
+    tokio::spawn(scheduler.run());
+    let grpc = create_grpc_server(task_repository.clone(), label_repository.clone());
+    (
+        create_app(
+            task_repository,
+            label_repository,
+            project_repository,
+            webhooks,
+            app_config(),
+        ),
+        grpc,
+    )
+}
 
-        let task_repository = TaskRepositoryForMemory::new(labels);
-        task_repository
-            .create(CreateTask::new(
-                "should_get_all_tasks".to_string(),
-                label_ids,
-            ))
+async fn database_apps(auto_migrate: bool, notifier: Arc<dyn Notifier>) -> (Router, GrpcRouter) {
+    let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL");
+    tracing::debug!("start connect database...");
+    // DATABASE_URL のスキームでストレージを切り替える
+    if database_url.starts_with("sqlite:") {
+        let pool = SqlitePool::connect(database_url)
             .await
-            .expect("failed create task");
-        let req = build_req_with_empty("/task", Method::GET);
-        let res = create_app(task_repository, LabelRepositoryForMemory::new())
-            .oneshot(req)
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        prepare_schema(&pool, &SQLITE_MIGRATOR, auto_migrate)
             .await
-            .unwrap();
-        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
-        let body = String::from_utf8(bytes.to_vec()).unwrap();
-        let tasks: Vec<TaskEntity> = serde_json::from_str(&body)
-            .expect(&format!("cannot convert Task instance. body: {}", body));
-        assert_eq!(vec![expected], tasks);
-    }
+            .unwrap_or_else(|e| panic!("fail prepare database schema: {:#}", e));
+        PoolCollector::register(pool.clone()).expect("fail register pool metrics");
+        let readiness = Readiness::new()
+            .check(DatabaseCheck::new(pool.clone()))
+            .check(MigrationCheck::new(pool.clone(), &SQLITE_MIGRATOR));
 
-    #[tokio::test]
-    async fn should_update_task() {
-        let (labels, label_ids) = label_fixture();
-        let expected = TaskEntity::new(1, "should_update_task".to_string(), labels.clone());
-
-        let task_repository = TaskRepositoryForMemory::new(labels);
-        task_repository
-            .create(CreateTask::new("before_update_task".to_string(), label_ids))
-            .await
-            .expect("failed create task");
-        let req = build_req_with_json(
-            "/task/1",
-            Method::PATCH,
-            r#"{
-                "text": "should_update_task",
-                "completed": false
-            }"#
-            .to_string(),
+        let (tasks, labels) = decorate(
+            TaskRepositoryForSqlite::new(pool.clone()),
+            LabelRepositoryForSqlite::new(pool.clone()),
         );
-        let res = create_app(task_repository, LabelRepositoryForMemory::new())
-            .oneshot(req)
-            .await
-            .unwrap();
-        let task = res_to_task(res).await;
-        assert_eq!(expected, task);
-    }
-
-    #[tokio::test]
-    async fn should_delete_task() {
-        let (labels, label_ids) = label_fixture();
-        let task_repository = TaskRepositoryForMemory::new(labels);
-        task_repository
-            .create(CreateTask::new("should_delete_task".to_string(), label_ids))
+        let (app, grpc) = create_apps(
+            tasks,
+            labels,
+            Metered::new(ProjectRepositoryForSqlite::new(pool.clone())),
+            DeliveryRepositoryForSqlite::new(pool.clone()),
+            WebhookRepositoryForSqlite::new(pool.clone()),
+            notifier,
+        );
+        (app.layer(Extension(readiness)), grpc)
+    } else {
+        let pool = PgPool::connect(database_url)
             .await
-            .expect("failed create task");
-        let req = build_req_with_empty("/task/1", Method::DELETE);
-        let res = create_app(task_repository, LabelRepositoryForMemory::new())
-            .oneshot(req)
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        prepare_schema(&pool, &MIGRATOR, auto_migrate)
             .await
-            .unwrap();
-        assert_eq!(StatusCode::NO_CONTENT, res.status());
-    }
-
-    #[tokio::test]
-    async fn should_created_label() {
-        let (_labels, _) = label_fixture();
-        let expected = Label::new(1, "should_created_label".to_string());
+            .unwrap_or_else(|e| panic!("fail prepare database schema: {:#}", e));
+        PoolCollector::register(pool.clone()).expect("fail register pool metrics");
+        let readiness = Readiness::new()
+            .check(DatabaseCheck::new(pool.clone()))
+            .check(MigrationCheck::new(pool.clone(), &MIGRATOR));
 
-        let req = build_req_with_json(
-            "/label",
-            Method::POST,
-            r#"{ "name": "should_created_label" }"#.to_string(),
+        let (tasks, labels) = decorate(
+            TaskRepositoryForDb::new(pool.clone()),
+            LabelRepositoryForDb::new(pool.clone()),
         );
-        let res = create_app(
-            TaskRepositoryForMemory::new(Vec::new()),
-            LabelRepositoryForMemory::new(),
-        )
-        .oneshot(req)
-        .await
-        .unwrap();
-        let label = res_to_label(res).await;
-        assert_eq!(expected, label);
+        let (app, grpc) = create_apps(
+            tasks,
+            labels,
+            Metered::new(ProjectRepositoryForDb::new(pool.clone())),
+            DeliveryRepositoryForDb::new(pool.clone()),
+            WebhookRepositoryForDb::new(pool.clone()),
+            notifier,
+        );
+        (app.layer(Extension(readiness)), grpc)
     }
+}
 
-    #[tokio::test]
-    async fn should_all_label_readed() {
-        let expected = Label::new(1, "should_all_label_readed".to_string());
-        let label_repository = LabelRepositoryForMemory::new();
-        let label = label_repository
-            .create("should_all_label_readed".to_string())
-            .await
-            .expect("failed create label");
-
-        let req = build_req_with_empty("/label", Method::GET);
-        let res = create_app(TaskRepositoryForMemory::new(vec![label]), label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
-        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
-        let body = String::from_utf8(bytes.to_vec()).unwrap();
-        let labels: Vec<Label> = serde_json::from_str(&body).expect(&format!(
-            "cannot convert Label list instance. body: {}",
-            body
-        ));
-        assert_eq!(vec![expected], labels);
+/// `RATE_LIMITS` と `IDEMPOTENCY_TTL_SECONDS` から HTTP ミドルウェアの設定を読み込む
+fn app_config() -> AppConfig {
+    AppConfig {
+        rate_limits: RateLimitConfig::from_env().expect("invalid [RATE_LIMITS]"),
+        idempotency: IdempotencyStore::from_env(),
     }
+}
 
-    #[tokio::test]
-    async fn should_delete_label() {
-        let label_repository = LabelRepositoryForMemory::new();
-        let label = label_repository
-            .create("should_delete_label".to_string())
-            .await
-            .expect("failed create label");
-        let req = build_req_with_empty("/label/1", Method::DELETE);
-        let res = create_app(TaskRepositoryForMemory::new(vec![label]), label_repository)
-            .oneshot(req)
-            .await
-            .unwrap();
-        assert_eq!(StatusCode::NO_CONTENT, res.status());
-    }
+/// メトリクスは実際にストレージへ届いた操作だけを数える
+/// タスクはラベルを含むので，ラベルの変更でタスクのキャッシュも破棄する
+fn decorate<T, L>(tasks: T, labels: L) -> (Cached<Metered<T>>, Cached<Metered<L>>) {
+    let config = CacheConfig::from_env();
+    let tasks = Cached::new(Metered::new(tasks), config);
+    let labels = Cached::new(Metered::new(labels), config).share_generation(&tasks);
+    (tasks, labels)
 }
@@ -0,0 +1,112 @@
+use axum::{
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{Headers, IntoResponse},
+};
+use prometheus::{
+    core::{Collector, Desc},
+    proto::MetricFamily,
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    IntGaugeVec, Opts, TextEncoder,
+};
+use sqlx::{Database, Pool};
+use std::{future::Future, sync::LazyLock, time::Instant};
+
+pub static HTTP_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "Number of HTTP requests by route and status code",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUEST_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "HTTP request latencies in seconds",
+        &["method", "route"]
+    )
+    .unwrap()
+});
+
+pub static REPOSITORY_OPERATION_DURATION_SECONDS: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "repository_operation_duration_seconds",
+        "Repository operation latencies in seconds",
+        &["repository", "operation", "outcome"]
+    )
+    .unwrap()
+});
+
+pub static REPOSITORY_CACHE_REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "repository_cache_requests_total",
+        "Repository cache lookups by result",
+        &["repository", "operation", "result"]
+    )
+    .unwrap()
+});
+
+/// リポジトリの処理時間を計測する
+pub async fn observe_repository<T, F>(repository: &str, operation: &str, f: F) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    let start = Instant::now();
+    let res = f.await;
+    let outcome = if res.is_ok() { "ok" } else { "error" };
+    REPOSITORY_OPERATION_DURATION_SECONDS
+        .with_label_values(&[repository, operation, outcome])
+        .observe(start.elapsed().as_secs_f64());
+    res
+}
+
+/// スクレイプ時に DB プールの接続数を読み取る
+pub struct PoolCollector<DB: Database> {
+    pool: Pool<DB>,
+    connections: IntGaugeVec,
+}
+
+impl<DB: Database> PoolCollector<DB> {
+    pub fn new(pool: Pool<DB>) -> Self {
+        let connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .unwrap();
+        Self { pool, connections }
+    }
+
+    pub fn register(pool: Pool<DB>) -> prometheus::Result<()> {
+        prometheus::register(Box::new(Self::new(pool)))
+    }
+}
+
+impl<DB: Database> Collector for PoolCollector<DB> {
+    fn desc(&self) -> Vec<&Desc> {
+        self.connections.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let size = self.pool.size() as i64;
+        let idle = self.pool.num_idle() as i64;
+        self.connections
+            .with_label_values(&["active"])
+            .set(size - idle);
+        self.connections.with_label_values(&["idle"]).set(idle);
+        self.connections.collect()
+    }
+}
+
+pub async fn metrics() -> impl IntoResponse {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    encoder
+        .encode(&prometheus::gather(), &mut buffer)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok::<_, StatusCode>((
+        StatusCode::OK,
+        Headers([(CONTENT_TYPE, encoder.format_type().to_string())]),
+        buffer,
+    ))
+}
@@ -0,0 +1,4 @@
+pub mod idempotency;
+pub mod metrics;
+pub mod rate_limit;
+pub mod request_id;
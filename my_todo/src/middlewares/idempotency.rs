@@ -0,0 +1,473 @@
+use axum::{
+    body::{boxed, Body, Full},
+    http::{header::HeaderName, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    env,
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::rate_limit::client_key;
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const DEFAULT_TTL_SECONDS: u64 = 60 * 60 * 24;
+// 処理中のキーはこれを過ぎたら破棄し，再送を受け付ける
+const IN_FLIGHT_TTL_SECONDS: u64 = 60;
+// クライアントごとに保持するキーの上限，超えたら保存が古いレスポンスから破棄する
+const MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct StoredResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    fingerprint: u64,
+    expires_at: Instant,
+    /// 登録した順番，索引のキーに使う
+    seq: u64,
+    /// None は元のリクエストを処理中
+    response: Option<StoredResponse>,
+}
+
+/// 1 つのクライアントのキー
+#[derive(Debug, Default)]
+struct Scope {
+    entries: HashMap<String, Entry>,
+    /// レスポンスを保存したキーを保存した順に，seq → key
+    completed: BTreeMap<u64, String>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    scopes: HashMap<String, Scope>,
+    /// 全てのキーの期限順の索引，(expires_at, seq) → (client, key)
+    expiries: BTreeMap<(Instant, u64), (String, String)>,
+    seq: u64,
+}
+
+impl Entries {
+    fn get(&self, client: &str, key: &str) -> Option<&Entry> {
+        self.scopes.get(client)?.entries.get(key)
+    }
+
+    fn insert(
+        &mut self,
+        client: &str,
+        key: &str,
+        fingerprint: u64,
+        expires_at: Instant,
+        response: Option<StoredResponse>,
+    ) {
+        self.seq += 1;
+        let seq = self.seq;
+        let scope = self.scopes.entry(client.to_string()).or_default();
+        if response.is_some() {
+            scope.completed.insert(seq, key.to_string());
+        }
+        scope.entries.insert(
+            key.to_string(),
+            Entry {
+                fingerprint,
+                expires_at,
+                seq,
+                response,
+            },
+        );
+        self.expiries
+            .insert((expires_at, seq), (client.to_string(), key.to_string()));
+    }
+
+    fn remove(&mut self, client: &str, key: &str) {
+        let scope = match self.scopes.get_mut(client) {
+            Some(scope) => scope,
+            None => return,
+        };
+        if let Some(entry) = scope.entries.remove(key) {
+            scope.completed.remove(&entry.seq);
+            self.expiries.remove(&(entry.expires_at, entry.seq));
+        }
+        if scope.entries.is_empty() {
+            self.scopes.remove(client);
+        }
+    }
+
+    /// 期限切れのキーを期限の順に破棄する
+    fn purge(&mut self, now: Instant) {
+        while let Some(entry) = self.expiries.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let (client, key) = entry.remove();
+            self.remove(&client, &key);
+        }
+    }
+
+    /// 保存が最も古いレスポンスを破棄する，処理中のキーしか無ければ false
+    fn evict_oldest_completed(&mut self, client: &str) -> bool {
+        let oldest = self
+            .scopes
+            .get(client)
+            .and_then(|scope| scope.completed.values().next().cloned());
+        match oldest {
+            Some(key) => {
+                self.remove(client, &key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn len(&self, client: &str) -> usize {
+        self.scopes
+            .get(client)
+            .map_or(0, |scope| scope.entries.len())
+    }
+}
+
+/// `Idempotency-Key` ごとに最初のレスポンスを TTL の間保持する
+#[derive(Debug, Clone)]
+pub struct IdempotencyStore {
+    ttl: Duration,
+    in_flight_ttl: Duration,
+    capacity: usize,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            in_flight_ttl: ttl.min(Duration::from_secs(IN_FLIGHT_TTL_SECONDS)),
+            capacity: MAX_ENTRIES,
+            entries: Arc::default(),
+        }
+    }
+
+    /// クライアントごとに保持するキーの上限
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// `IDEMPOTENCY_TTL_SECONDS` から TTL を読み込む (未設定の場合は 24 時間)
+    pub fn from_env() -> Self {
+        let ttl = env::var("IDEMPOTENCY_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS);
+        Self::new(Duration::from_secs(ttl))
+    }
+
+    /// 保存済みのレスポンスがあれば返し，なければ処理中として登録する
+    /// 処理中のキーは破棄しないので，上限まで処理中なら 503 を返す
+    fn begin(
+        &self,
+        client: &str,
+        key: &str,
+        fingerprint: u64,
+    ) -> Result<Option<StoredResponse>, StatusCode> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.purge(now);
+
+        match entries.get(client, key) {
+            Some(entry) if entry.fingerprint != fingerprint => {
+                Err(StatusCode::UNPROCESSABLE_ENTITY)
+            }
+            Some(Entry { response: None, .. }) => Err(StatusCode::CONFLICT),
+            Some(Entry {
+                response: Some(response),
+                ..
+            }) => Ok(Some(response.clone())),
+            None => {
+                while entries.len(client) >= self.capacity {
+                    if !entries.evict_oldest_completed(client) {
+                        return Err(StatusCode::SERVICE_UNAVAILABLE);
+                    }
+                }
+                entries.insert(client, key, fingerprint, now + self.in_flight_ttl, None);
+                Ok(None)
+            }
+        }
+    }
+
+    fn complete(&self, client: &str, key: &str, fingerprint: u64, response: StoredResponse) {
+        let mut entries = self.entries.lock().unwrap();
+        // 処理中に期限が切れて破棄された場合は保存しない
+        if !matches!(entries.get(client, key), Some(Entry { response: None, .. })) {
+            return;
+        }
+        entries.remove(client, key);
+        let expires_at = Instant::now() + self.ttl;
+        entries.insert(client, key, fingerprint, expires_at, Some(response));
+    }
+
+    fn abort(&self, client: &str, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(Entry { response: None, .. }) = entries.get(client, key) {
+            entries.remove(client, key);
+        }
+    }
+}
+
+/// 処理中として登録したキーを保持し，`complete` されずに drop された場合は破棄する
+/// クライアントの切断で future が drop された場合やハンドラが panic した場合も再送を受け付けるため
+struct InFlightGuard {
+    store: IdempotencyStore,
+    client: String,
+    key: String,
+    fingerprint: u64,
+    completed: bool,
+}
+
+impl InFlightGuard {
+    fn complete(mut self, response: StoredResponse) {
+        self.store
+            .complete(&self.client, &self.key, self.fingerprint, response);
+        self.completed = true;
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.store.abort(&self.client, &self.key);
+        }
+    }
+}
+
+impl Default for IdempotencyStore {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_TTL_SECONDS))
+    }
+}
+
+/// POST リクエストに `Idempotency-Key` が付いていれば，同じキーでの再送に最初のレスポンスを返す
+/// キーはクライアントごとに区別し，他のクライアントのレスポンスは返さない
+pub async fn idempotency(
+    req: Request<Body>,
+    next: Next<Body>,
+    store: IdempotencyStore,
+) -> Response {
+    let key = match req.headers().get(IDEMPOTENCY_KEY) {
+        Some(key) if req.method() == Method::POST => key.clone(),
+        _ => return next.run(req).await,
+    };
+    let client = client_key(&req);
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() => format!("{} {} {}", req.method(), req.uri().path(), key),
+        _ => {
+            let message = "Idempotency-Key parse error".to_string();
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        }
+    };
+
+    // 同じキーで異なるリクエストが送られた場合を検出するため，body のハッシュを保持する
+    let (parts, body) = req.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let fingerprint = hasher.finish();
+
+    match store.begin(&client, &key, fingerprint) {
+        Ok(Some(stored)) => return replay(stored),
+        Ok(None) => {}
+        Err(status) => return status.into_response(),
+    }
+    let guard = InFlightGuard {
+        store,
+        client,
+        key,
+        fingerprint,
+        completed: false,
+    };
+
+    let res = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    // サーバーエラーは再送で回復し得るので保存しない
+    if res.status().is_server_error() {
+        return res;
+    }
+
+    let (parts, body) = res.into_parts();
+    let bytes = match hyper::body::to_bytes(body).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    guard.complete(StoredResponse {
+        status: parts.status,
+        headers: parts.headers.clone(),
+        body: bytes.to_vec(),
+    });
+    Response::from_parts(parts, boxed(Full::from(bytes)))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut res = Response::new(boxed(Full::from(stored.body)));
+    *res.status_mut() = stored.status;
+    *res.headers_mut() = stored.headers;
+    res.headers_mut()
+        .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{extract::ConnectInfo, middleware, routing::post, Router};
+    use std::{
+        net::SocketAddr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use tower::ServiceExt;
+
+    fn build_req(key: &str, ip: [u8; 4]) -> Request<Body> {
+        let mut req = Request::builder()
+            .uri("/task")
+            .method(Method::POST)
+            .header(IDEMPOTENCY_KEY, key)
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((ip, 3000))));
+        req
+    }
+
+    /// 呼ばれた回数を body に返すアプリ
+    fn app(store: IdempotencyStore) -> Router {
+        let count = Arc::new(AtomicUsize::new(0));
+        Router::new()
+            .route(
+                "/task",
+                post(move || {
+                    let count = count.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move { (StatusCode::CREATED, count.to_string()) }
+                }),
+            )
+            .layer(middleware::from_fn(move |req, next| {
+                idempotency(req, next, store.clone())
+            }))
+    }
+
+    async fn body_of(app: &Router, req: Request<Body>) -> String {
+        let res = app.clone().oneshot(req).await.unwrap();
+        let bytes = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_scope_keys_per_client() {
+        let app = app(IdempotencyStore::new(Duration::from_secs(60)));
+        assert_eq!(body_of(&app, build_req("key", [127, 0, 0, 1])).await, "1");
+        assert_eq!(body_of(&app, build_req("key", [127, 0, 0, 1])).await, "1");
+        // 同じキーでも別のクライアントのレスポンスは返さない
+        assert_eq!(body_of(&app, build_req("key", [127, 0, 0, 2])).await, "2");
+    }
+
+    #[tokio::test]
+    async fn should_evict_oldest_entry_over_capacity() {
+        let store = IdempotencyStore::new(Duration::from_secs(60)).with_capacity(2);
+        let app = app(store.clone());
+        for key in ["a", "b", "c"] {
+            body_of(&app, build_req(key, [127, 0, 0, 1])).await;
+        }
+        assert_eq!(store.entries.lock().unwrap().len("127.0.0.1"), 2);
+        assert_eq!(body_of(&app, build_req("c", [127, 0, 0, 1])).await, "3");
+        // 最も古い a は破棄されているので再び処理される
+        assert_eq!(body_of(&app, build_req("a", [127, 0, 0, 1])).await, "4");
+    }
+
+    #[tokio::test]
+    async fn should_limit_entries_per_client() {
+        let store = IdempotencyStore::new(Duration::from_secs(60)).with_capacity(1);
+        let app = app(store);
+        assert_eq!(body_of(&app, build_req("a", [127, 0, 0, 1])).await, "1");
+        for key in ["b", "c", "d"] {
+            body_of(&app, build_req(key, [127, 0, 0, 2])).await;
+        }
+        // 他のクライアントがキーを使い切っても破棄されない
+        assert_eq!(body_of(&app, build_req("a", [127, 0, 0, 1])).await, "1");
+    }
+
+    #[test]
+    fn should_not_evict_in_flight_entries() {
+        let store = IdempotencyStore::new(Duration::from_secs(60)).with_capacity(1);
+        let response = || StoredResponse {
+            status: StatusCode::CREATED,
+            headers: HeaderMap::new(),
+            body: vec![],
+        };
+        store.begin("client", "a", 0).unwrap();
+        assert_eq!(
+            store.begin("client", "b", 0).unwrap_err(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        // 処理中のキーへの再送は 409 のまま
+        assert_eq!(
+            store.begin("client", "a", 0).unwrap_err(),
+            StatusCode::CONFLICT
+        );
+
+        // 保存済みのレスポンスは破棄して空ける
+        store.complete("client", "a", 0, response());
+        assert!(store.begin("client", "b", 0).unwrap().is_none());
+        assert!(store.entries.lock().unwrap().get("client", "a").is_none());
+    }
+
+    #[tokio::test]
+    async fn should_release_key_when_in_flight_request_is_dropped() {
+        let store = IdempotencyStore::new(Duration::from_secs(60));
+        let count = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route(
+                "/task",
+                post(move || {
+                    let count = count.fetch_add(1, Ordering::SeqCst) + 1;
+                    async move {
+                        // 最初のリクエストは応答しないまま切断される
+                        if count == 1 {
+                            std::future::pending::<()>().await;
+                        }
+                        (StatusCode::CREATED, count.to_string())
+                    }
+                }),
+            )
+            .layer(middleware::from_fn({
+                let store = store.clone();
+                move |req, next| idempotency(req, next, store.clone())
+            }));
+
+        let dropped = tokio::time::timeout(
+            Duration::from_millis(50),
+            app.clone().oneshot(build_req("key", [127, 0, 0, 1])),
+        )
+        .await;
+        assert!(dropped.is_err());
+        assert!(store.entries.lock().unwrap().scopes.is_empty());
+        assert_eq!(body_of(&app, build_req("key", [127, 0, 0, 1])).await, "2");
+    }
+
+    #[test]
+    fn should_expire_in_flight_entries_before_completed_ones() {
+        let store = IdempotencyStore::new(Duration::from_secs(60 * 60));
+        store.begin("client", "key", 0).unwrap();
+        let entries = store.entries.lock().unwrap();
+        let expires_at = entries.get("client", "key").unwrap().expires_at;
+        assert!(expires_at <= Instant::now() + Duration::from_secs(IN_FLIGHT_TTL_SECONDS));
+    }
+}
@@ -0,0 +1,450 @@
+use axum::{
+    body::BoxBody,
+    extract::ConnectInfo,
+    http::{header::RETRY_AFTER, Method, Request, StatusCode},
+    response::{Headers, IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tower::{Layer, Service};
+
+use crate::clock::{Clock, SystemClock};
+
+const DEFAULT_RATE_LIMITS: &str = "POST /task=30/60,POST /label=30/60";
+// バケット数の上限，超えるときは満タンのものから，次に長く使われていないものから破棄する
+const MAX_BUCKETS: usize = 10_000;
+
+/// `per` の間に `capacity` 回までのリクエストを許可する
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    capacity: u32,
+    per: Duration,
+}
+
+impl Quota {
+    pub fn new(capacity: u32, per: Duration) -> Self {
+        Self { capacity, per }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.capacity as f64 / self.per.as_secs_f64()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct RouteLimit {
+    method: Method,
+    path: String,
+    quota: Quota,
+}
+
+impl RouteLimit {
+    // `:id` のようなパスパラメータは任意のセグメントに一致させる
+    fn matches(&self, method: &Method, path: &str) -> bool {
+        if self.method != method {
+            return false;
+        }
+        let mut pattern = self.path.split('/');
+        let mut path = path.split('/');
+        loop {
+            match (pattern.next(), path.next()) {
+                (None, None) => return true,
+                (Some(p), Some(s)) if p.starts_with(':') && !s.is_empty() => continue,
+                (Some(p), Some(s)) if p == s => continue,
+                _ => return false,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitConfig {
+    routes: Vec<RouteLimit>,
+}
+
+impl RateLimitConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, method: Method, path: &str, quota: Quota) -> Self {
+        self.routes.push(RouteLimit {
+            method,
+            path: path.to_string(),
+            quota,
+        });
+        self
+    }
+
+    /// `RATE_LIMITS` を読み込む
+    /// 書式は `METHOD PATH=CAPACITY/SECONDS` のカンマ区切り (例: `POST /task=30/60`)
+    pub fn from_env() -> anyhow::Result<Self> {
+        let rate_limits =
+            env::var("RATE_LIMITS").unwrap_or_else(|_| DEFAULT_RATE_LIMITS.to_string());
+        rate_limits.parse()
+    }
+}
+
+impl std::str::FromStr for RateLimitConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = RateLimitConfig::new();
+        for rule in s.split(',').map(str::trim).filter(|rule| !rule.is_empty()) {
+            let invalid = || anyhow::anyhow!("invalid rate limit rule: [{}]", rule);
+            let (route, quota) = rule.split_once('=').ok_or_else(invalid)?;
+            let (method, path) = route.trim().split_once(' ').ok_or_else(invalid)?;
+            let (capacity, seconds) = quota.trim().split_once('/').ok_or_else(invalid)?;
+            let method = method.parse::<Method>().map_err(|_| invalid())?;
+            let capacity = capacity.parse::<u32>().map_err(|_| invalid())?;
+            let seconds = seconds.parse::<u64>().map_err(|_| invalid())?;
+            if capacity == 0 || seconds == 0 {
+                return Err(invalid());
+            }
+            config = config.route(
+                method,
+                path.trim(),
+                Quota::new(capacity, Duration::from_secs(seconds)),
+            );
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(quota: &Quota, now: Instant) -> Self {
+        Self {
+            tokens: quota.capacity as f64,
+            updated_at: now,
+        }
+    }
+
+    fn tokens_at(&self, quota: &Quota, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        (self.tokens + elapsed * quota.refill_per_sec()).min(quota.capacity as f64)
+    }
+
+    fn refill(&mut self, quota: &Quota, now: Instant) {
+        self.tokens = self.tokens_at(quota, now);
+        self.updated_at = now;
+    }
+
+    /// 満タンなら破棄して作り直しても同じ，最後に使った時刻は変えない
+    fn is_full(&self, quota: &Quota, now: Instant) -> bool {
+        self.tokens_at(quota, now) >= quota.capacity as f64
+    }
+
+    /// トークンを 1 つ消費する，足りなければ次に消費できるまでの時間を返す
+    fn try_acquire(&mut self, quota: &Quota, now: Instant) -> Result<(), Duration> {
+        self.refill(quota, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / quota.refill_per_sec();
+            Err(Duration::from_secs_f64(wait))
+        }
+    }
+}
+
+struct Limiter {
+    config: RateLimitConfig,
+    clock: Box<dyn Clock>,
+    buckets: Mutex<HashMap<(usize, String), TokenBucket>>,
+    max_buckets: usize,
+}
+
+impl Limiter {
+    fn check(&self, method: &Method, path: &str, client: String) -> Result<(), Duration> {
+        let (index, route) = match self
+            .config
+            .routes
+            .iter()
+            .enumerate()
+            .find(|(_, route)| route.matches(method, path))
+        {
+            Some(route) => route,
+            None => return Ok(()),
+        };
+
+        let now = self.clock.now();
+        let key = (index, client);
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= self.max_buckets && !buckets.contains_key(&key) {
+            self.evict(&mut buckets, now);
+        }
+        buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::full(&route.quota, now))
+            .try_acquire(&route.quota, now)
+    }
+
+    /// 1 つ追加できるまでバケットを減らす
+    fn evict(&self, buckets: &mut HashMap<(usize, String), TokenBucket>, now: Instant) {
+        let routes = &self.config.routes;
+        buckets.retain(|(index, _), bucket| !bucket.is_full(&routes[*index].quota, now));
+        let excess = (buckets.len() + 1).saturating_sub(self.max_buckets);
+        if excess == 0 {
+            return;
+        }
+        // updated_at は最後にリクエストがあった時刻
+        let mut keys = buckets
+            .iter()
+            .map(|(key, bucket)| (bucket.updated_at, key.clone()))
+            .collect::<Vec<_>>();
+        keys.select_nth_unstable_by_key(excess - 1, |(updated_at, _)| *updated_at);
+        for (_, key) in keys.into_iter().take(excess) {
+            buckets.remove(&key);
+        }
+    }
+}
+
+/// クライアント (IP アドレス) ごと，ルートごとにトークンバケットで流量を制限する
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<Limiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+
+    pub fn with_clock<C: Clock>(config: RateLimitConfig, clock: C) -> Self {
+        Self {
+            limiter: Arc::new(Limiter {
+                config,
+                clock: Box::new(clock),
+                buckets: Mutex::default(),
+                max_buckets: MAX_BUCKETS,
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+}
+
+impl<S, B> Service<Request<B>> for RateLimit<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let client = client_key(&req);
+        match self.limiter.check(req.method(), req.uri().path(), client) {
+            Ok(()) => Box::pin(self.inner.call(req)),
+            Err(wait) => {
+                // 浮動小数点の誤差で切り上がらないよう，ミリ秒単位で切り上げる
+                let retry_after = wait.as_millis().div_ceil(1000).max(1);
+                let res = (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    Headers([(RETRY_AFTER, retry_after.to_string())]),
+                    "Too Many Requests",
+                )
+                    .into_response();
+                Box::pin(async move { Ok(res) })
+            }
+        }
+    }
+}
+
+/// クライアントの識別子，接続元の IP アドレス
+pub(crate) fn client_key<B>(req: &Request<B>) -> String {
+    req.extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::clock::test_utils::MockClock;
+    use axum::{body::Body, routing::post, Router};
+    use tower::ServiceExt;
+
+    fn build_req(path: &str, ip: [u8; 4]) -> Request<Body> {
+        let mut req = Request::builder()
+            .uri(path)
+            .method(Method::POST)
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((ip, 3000))));
+        req
+    }
+
+    fn app(clock: MockClock) -> Router {
+        let config = RateLimitConfig::new().route(
+            Method::POST,
+            "/task",
+            Quota::new(2, Duration::from_secs(10)),
+        );
+        Router::new()
+            .route("/task", post(|| async { StatusCode::CREATED }))
+            .route("/label", post(|| async { StatusCode::CREATED }))
+            .layer(RateLimitLayer::with_clock(config, clock))
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: RateLimitConfig = "POST /task=30/60, DELETE /task/:id=5/1".parse().unwrap();
+        assert_eq!(config.routes.len(), 2);
+        assert_eq!(
+            config.routes[0].quota,
+            Quota::new(30, Duration::from_secs(60))
+        );
+        assert!(config.routes[1].matches(&Method::DELETE, "/task/1"));
+        assert!(!config.routes[1].matches(&Method::DELETE, "/task"));
+        assert!(!config.routes[1].matches(&Method::PATCH, "/task/1"));
+
+        assert!("POST /task=0/60".parse::<RateLimitConfig>().is_err());
+        assert!("POST /task".parse::<RateLimitConfig>().is_err());
+    }
+
+    #[tokio::test]
+    async fn should_limit_after_bucket_is_empty() {
+        let clock = MockClock::new();
+        let app = app(clock.clone());
+
+        for _ in 0..2 {
+            let res = app
+                .clone()
+                .oneshot(build_req("/task", [127, 0, 0, 1]))
+                .await
+                .unwrap();
+            assert_eq!(StatusCode::CREATED, res.status());
+        }
+        let res = app
+            .clone()
+            .oneshot(build_req("/task", [127, 0, 0, 1]))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert_eq!(res.headers()[RETRY_AFTER], "5");
+
+        // 制限のないルート，別のクライアントには影響しない
+        let res = app
+            .clone()
+            .oneshot(build_req("/label", [127, 0, 0, 1]))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+        let res = app
+            .clone()
+            .oneshot(build_req("/task", [127, 0, 0, 2]))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+
+        // 5 秒で 1 トークン回復する
+        clock.advance(Duration::from_secs(4));
+        let res = app
+            .clone()
+            .oneshot(build_req("/task", [127, 0, 0, 1]))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, res.status());
+        assert_eq!(res.headers()[RETRY_AFTER], "1");
+
+        clock.advance(Duration::from_secs(1));
+        let res = app
+            .oneshot(build_req("/task", [127, 0, 0, 1]))
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::CREATED, res.status());
+    }
+
+    #[test]
+    fn should_evict_least_recently_used_buckets() {
+        let clock = MockClock::new();
+        let limiter = Limiter {
+            config: RateLimitConfig::new().route(
+                Method::POST,
+                "/task",
+                Quota::new(1, Duration::from_secs(60)),
+            ),
+            clock: Box::new(clock.clone()),
+            buckets: Mutex::default(),
+            max_buckets: 3,
+        };
+        let check = |client: &str| limiter.check(&Method::POST, "/task", client.to_string());
+        let clients = || {
+            let mut clients = limiter
+                .buckets
+                .lock()
+                .unwrap()
+                .keys()
+                .map(|(_, client)| client.clone())
+                .collect::<Vec<_>>();
+            clients.sort();
+            clients
+        };
+
+        for client in ["a", "b", "c"] {
+            assert!(check(client).is_ok());
+            clock.advance(Duration::from_secs(1));
+        }
+        // 満タンのものが無いので，最後に使ったのが最も古い a を破棄する
+        assert!(check("d").is_ok());
+        assert_eq!(clients(), vec!["b", "c", "d"]);
+        // 上限に達していても既存のバケットは破棄しない
+        assert!(check("c").is_err());
+        assert_eq!(clients(), vec!["b", "c", "d"]);
+
+        // b だけ満タンに戻ったので，古さに関係なく b を破棄する
+        clock.advance(Duration::from_secs(58));
+        assert!(check("e").is_ok());
+        assert_eq!(clients(), vec!["c", "d", "e"]);
+    }
+
+    #[test]
+    fn bucket_does_not_exceed_capacity() {
+        let clock = MockClock::new();
+        let quota = Quota::new(2, Duration::from_secs(10));
+        let mut bucket = TokenBucket::full(&quota, clock.now());
+
+        clock.advance(Duration::from_secs(60));
+        assert!(bucket.try_acquire(&quota, clock.now()).is_ok());
+        assert!(bucket.try_acquire(&quota, clock.now()).is_ok());
+        let wait = bucket.try_acquire(&quota, clock.now()).unwrap_err();
+        assert_eq!(wait.as_secs_f64().round(), 5.0);
+    }
+}
@@ -0,0 +1,30 @@
+use axum::http::{HeaderValue, Request};
+use tower_http::request_id::{MakeRequestId, RequestId};
+use tracing::Span;
+use uuid::Uuid;
+
+/// `X-Request-Id` が付いていないリクエストに UUID v4 を割り当てる
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MakeRequestUuid;
+
+impl MakeRequestId for MakeRequestUuid {
+    fn make_request_id<B>(&mut self, _request: &Request<B>) -> Option<RequestId> {
+        let request_id = Uuid::new_v4().to_string();
+        HeaderValue::from_str(&request_id).ok().map(RequestId::new)
+    }
+}
+
+/// ハンドラ，リポジトリのログを request_id で紐付けるための span
+pub fn make_span<B>(req: &Request<B>) -> Span {
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        uri = %req.uri(),
+    )
+}
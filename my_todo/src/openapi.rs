@@ -0,0 +1,183 @@
+use crate::handlers::{
+    self,
+    label::{LabelList, MergedLabels},
+};
+use crate::repositories::{
+    label::{CreateLabel, Label, LabelStats, LabelTree, MergeLabels, UpdateLabel},
+    project::{CreateProject, Project, UpdateProject},
+    task::{CreateTask, MoveTask, TaskEntity, UpdateTask},
+    webhook::{CreateWebhook, EventType, Webhook},
+};
+use crate::webhook::Attempt;
+use axum::{
+    extract::Path,
+    http::{header::CONTENT_TYPE, StatusCode},
+    response::{Headers, IntoResponse, Redirect, Response},
+    Json,
+};
+use std::sync::Arc;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::Config;
+
+pub const OPENAPI_PATH: &str = "/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        handlers::task::create_task,
+        handlers::task::all_tasks,
+        handlers::task::find_task,
+        handlers::task::update_task,
+        handlers::task::task_occurrences,
+        handlers::task::move_task,
+        handlers::task::delete_task,
+        handlers::label::create_label,
+        handlers::label::all_labels,
+        handlers::label::label_stats,
+        handlers::label::update_label,
+        handlers::label::merge_labels,
+        handlers::label::delete_label,
+        handlers::project::create_project,
+        handlers::project::all_projects,
+        handlers::project::find_project,
+        handlers::project::update_project,
+        handlers::project::delete_project,
+        handlers::project::project_tasks,
+        handlers::webhook::create_webhook,
+        handlers::webhook::all_webhooks,
+        handlers::webhook::delete_webhook,
+        handlers::webhook::webhook_deliveries,
+    ),
+    components(schemas(
+        TaskEntity,
+        CreateTask,
+        UpdateTask,
+        MoveTask,
+        Label,
+        LabelTree,
+        LabelList,
+        LabelStats,
+        CreateLabel,
+        UpdateLabel,
+        MergeLabels,
+        MergedLabels,
+        Project,
+        CreateProject,
+        UpdateProject,
+        Webhook,
+        CreateWebhook,
+        EventType,
+        Attempt
+    )),
+    tags(
+        (name = "task", description = "Task CRUD"),
+        (name = "label", description = "Label CRUD"),
+        (name = "project", description = "Projects grouping tasks"),
+        (name = "webhook", description = "Outgoing webhook subscriptions"),
+    )
+)]
+pub struct ApiDoc;
+
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+pub async fn swagger_ui_index() -> Redirect {
+    Redirect::permanent("/swagger-ui/".parse().unwrap())
+}
+
+// Swagger UI の静的ファイルはバイナリに埋め込まれている
+pub async fn swagger_ui(Path(tail): Path<String>) -> Response {
+    let config = Arc::new(Config::from(OPENAPI_PATH));
+    match utoipa_swagger_ui::serve(tail.trim_start_matches('/'), config) {
+        Ok(Some(file)) => (
+            StatusCode::OK,
+            Headers([(CONTENT_TYPE, file.content_type)]),
+            file.bytes.to_vec(),
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::Value;
+    use validator::Validate;
+
+    /// 文書に書かれた長さの境界で，実際の検証結果が変わることを確かめる
+    fn assert_length<T: Validate>(
+        doc: &Value,
+        schema: &str,
+        property: &str,
+        build: impl Fn(String) -> T,
+    ) {
+        let property = &doc["components"]["schemas"][schema]["properties"][property];
+        let valid = |len: u64| build("a".repeat(len as usize)).validate().is_ok();
+        let max = property["maxLength"]
+            .as_u64()
+            .unwrap_or_else(|| panic!("{} has no maxLength", schema));
+        assert!(valid(max), "{}", schema);
+        assert!(!valid(max + 1), "{}", schema);
+        let min = property["minLength"].as_u64().unwrap_or(0);
+        assert!(valid(min), "{}", schema);
+        if min > 0 {
+            assert!(!valid(min - 1), "{}", schema);
+        }
+    }
+
+    #[test]
+    fn label_list_documents_the_tree() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let response = &doc["paths"]["/label"]["get"]["responses"]["200"]["content"]
+            ["application/json"]["schema"]["$ref"];
+        assert_eq!(response, "#/components/schemas/LabelList");
+        let schemas = doc["components"]["schemas"]["LabelList"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|schema| schema["items"]["$ref"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            schemas,
+            vec![
+                "#/components/schemas/Label",
+                "#/components/schemas/LabelTree"
+            ]
+        );
+    }
+
+    #[test]
+    fn lengths_match_validation() {
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        assert_length(&doc, "CreateTask", "text", |text| {
+            CreateTask::new(text, vec![])
+        });
+        assert_length(&doc, "UpdateTask", "text", |text| {
+            UpdateTask::new(Some(text), None, None)
+        });
+        assert_length(&doc, "CreateLabel", "name", CreateLabel::new);
+        assert_length(&doc, "CreateLabel", "description", |description| {
+            CreateLabel::new("label".to_string()).with_details(None, Some(description))
+        });
+        assert_length(&doc, "UpdateLabel", "name", |name| {
+            UpdateLabel::new(Some(name), None)
+        });
+        assert_length(&doc, "UpdateLabel", "description", |description| {
+            UpdateLabel::default().with_details(None, Some(description))
+        });
+        assert_length(&doc, "CreateProject", "name", CreateProject::new);
+        assert_length(&doc, "UpdateProject", "name", |name| {
+            UpdateProject::new(Some(name))
+        });
+        assert_length(&doc, "CreateWebhook", "secret", |secret| {
+            CreateWebhook::new(
+                "http://localhost:8080/hook".to_string(),
+                secret,
+                vec![EventType::TaskCreated],
+            )
+        });
+    }
+}
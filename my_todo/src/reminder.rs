@@ -0,0 +1,290 @@
+pub mod notifier;
+
+use std::{env, sync::Arc, time::Duration};
+
+use chrono::{Days, Local, NaiveDate};
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::repositories::{
+    delivery::{DeliveryRepository, ReminderKind},
+    task::{TaskEntity, TaskRepository},
+    RepositoryError,
+};
+use notifier::Notifier;
+
+const DEFAULT_INTERVAL_SECONDS: u64 = 60;
+const DEFAULT_LEAD_DAYS: u64 = 1;
+
+#[derive(Debug, Clone)]
+pub struct ReminderConfig {
+    interval: Duration,
+    lead_days: u64,
+}
+
+impl ReminderConfig {
+    pub fn new(interval: Duration, lead_days: u64) -> Self {
+        Self {
+            interval,
+            lead_days,
+        }
+    }
+
+    /// `REMINDER_INTERVAL_SECONDS=0` で無効にする
+    pub fn from_env() -> Self {
+        let interval = env::var("REMINDER_INTERVAL_SECONDS")
+            .ok()
+            .and_then(|interval| interval.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL_SECONDS);
+        let lead_days = env::var("REMINDER_LEAD_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .unwrap_or(DEFAULT_LEAD_DAYS);
+        Self::new(Duration::from_secs(interval), lead_days)
+    }
+}
+
+/// Notifier に渡す内容，webhook ではそのまま JSON で送る
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Reminder {
+    pub kind: ReminderKind,
+    pub task: TaskEntity,
+}
+
+impl Reminder {
+    pub fn due_date(&self) -> NaiveDate {
+        // run_once は期日のあるタスクしか渡さない
+        self.task.due_date.unwrap()
+    }
+
+    pub fn subject(&self) -> String {
+        match self.kind {
+            ReminderKind::Upcoming => {
+                format!("[my_todo] due {}: {}", self.due_date(), self.task.text)
+            }
+            ReminderKind::Overdue => {
+                format!(
+                    "[my_todo] overdue since {}: {}",
+                    self.due_date(),
+                    self.task.text
+                )
+            }
+        }
+    }
+}
+
+/// 定期的にタスクを走査してリマインダーを送る
+pub struct Scheduler<T, D> {
+    tasks: T,
+    deliveries: D,
+    notifier: Arc<dyn Notifier>,
+    config: ReminderConfig,
+}
+
+impl<T: TaskRepository, D: DeliveryRepository> Scheduler<T, D> {
+    pub fn new(
+        tasks: T,
+        deliveries: D,
+        notifier: Arc<dyn Notifier>,
+        config: ReminderConfig,
+    ) -> Self {
+        Self {
+            tasks,
+            deliveries,
+            notifier,
+            config,
+        }
+    }
+
+    /// 期日を過ぎたものと `lead_days` 日以内に期日が来るもののうち，未送信のものを送って数を返す
+    /// 送信に失敗したものは記録せず次回やり直す
+    pub async fn run_once(&self, today: NaiveDate) -> anyhow::Result<usize> {
+        let horizon = today
+            .checked_add_days(Days::new(self.config.lead_days))
+            .unwrap_or(NaiveDate::MAX);
+        let mut sent = 0;
+        for delivery in self.deliveries.pending(today, horizon).await? {
+            let task = match self.tasks.find(delivery.task_id).await {
+                Ok(task) => task,
+                // 走査の後に削除されたもの
+                Err(e) if matches!(e.downcast_ref(), Some(RepositoryError::NotFound(_))) => {
+                    continue
+                }
+                Err(e) => return Err(e),
+            };
+            let reminder = Reminder {
+                kind: delivery.kind,
+                task,
+            };
+            // 走査の後に完了したか期日が変わったものは次回に回す
+            if reminder.task.completed || reminder.task.due_date != Some(delivery.due_date) {
+                continue;
+            }
+            if let Err(e) = self.notifier.notify(&reminder).await {
+                tracing::warn!(
+                    "fail send {} reminder of task {}: {:#}",
+                    delivery.kind.as_str(),
+                    delivery.task_id,
+                    e
+                );
+                continue;
+            }
+            self.deliveries.record(&delivery).await?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// プロセスが終了するまで走査を続ける
+    pub async fn run(self) {
+        if self.config.interval.is_zero() {
+            tracing::info!("reminders are disabled");
+            return;
+        }
+        let mut interval = tokio::time::interval(self.config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            match self.run_once(Local::now().date_naive()).await {
+                Ok(0) => {}
+                Ok(sent) => tracing::info!("sent {} reminders", sent),
+                Err(e) => tracing::error!("fail scan reminders: {:#}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        delivery::DeliveryRepositoryForMemory,
+        memory::MemoryStore,
+        task::{CreateTask, TaskRepositoryForMemory, UpdateTask},
+    };
+    use axum::async_trait;
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    };
+
+    /// 送ったリマインダーを記録する，`failing` の間は送信に失敗する
+    #[derive(Default)]
+    struct RecordingNotifier {
+        sent: Mutex<Vec<(ReminderKind, i32)>>,
+        failing: AtomicBool,
+    }
+
+    #[async_trait]
+    impl Notifier for RecordingNotifier {
+        async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                anyhow::bail!("unavailable");
+            }
+            self.sent
+                .lock()
+                .unwrap()
+                .push((reminder.kind, reminder.task.id));
+            Ok(())
+        }
+    }
+
+    impl RecordingNotifier {
+        fn take(&self) -> Vec<(ReminderKind, i32)> {
+            std::mem::take(&mut self.sent.lock().unwrap())
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    async fn create(tasks: &TaskRepositoryForMemory, text: &str, due_date: &str) -> TaskEntity {
+        tasks
+            .create(
+                CreateTask::new(text.to_string(), vec![])
+                    .with_recurrence(None, Some(date(due_date))),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_classify_due_tasks() {
+        let store = MemoryStore::new();
+        let tasks = TaskRepositoryForMemory::with_store(store.clone());
+        let overdue = create(&tasks, "overdue", "2024-01-09").await;
+        let today = create(&tasks, "today", "2024-01-10").await;
+        let tomorrow = create(&tasks, "tomorrow", "2024-01-11").await;
+        create(&tasks, "later", "2024-01-12").await;
+        let done = create(&tasks, "done", "2024-01-01").await;
+        tasks
+            .update(done.id, UpdateTask::new(None, Some(true), None), None)
+            .await
+            .unwrap();
+        tasks
+            .create(CreateTask::new("no due date".to_string(), vec![]))
+            .await
+            .unwrap();
+
+        let notifier = Arc::new(RecordingNotifier::default());
+        let scheduler = Scheduler::new(
+            tasks,
+            DeliveryRepositoryForMemory::with_store(store),
+            notifier.clone(),
+            ReminderConfig::new(Duration::from_secs(60), 1),
+        );
+        assert_eq!(scheduler.run_once(date("2024-01-10")).await.unwrap(), 3);
+        let mut kinds = notifier.take();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            vec![
+                (ReminderKind::Upcoming, today.id),
+                (ReminderKind::Upcoming, tomorrow.id),
+                (ReminderKind::Overdue, overdue.id),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_send_twice_across_restarts() {
+        let store = MemoryStore::new();
+        let tasks = TaskRepositoryForMemory::with_store(store.clone());
+        let task = create(&tasks, "task", "2024-01-10").await;
+        let notifier = Arc::new(RecordingNotifier::default());
+        let scheduler = || {
+            Scheduler::new(
+                tasks.clone(),
+                DeliveryRepositoryForMemory::with_store(store.clone()),
+                notifier.clone(),
+                ReminderConfig::new(Duration::from_secs(60), 1),
+            )
+        };
+
+        assert_eq!(scheduler().run_once(date("2024-01-09")).await.unwrap(), 1);
+        assert_eq!(notifier.take(), vec![(ReminderKind::Upcoming, task.id)]);
+        // 作り直しても記録が残っていれば送らない
+        assert_eq!(scheduler().run_once(date("2024-01-10")).await.unwrap(), 0);
+
+        // 失敗した分は次回送る
+        notifier.failing.store(true, Ordering::SeqCst);
+        assert_eq!(scheduler().run_once(date("2024-01-11")).await.unwrap(), 0);
+        notifier.failing.store(false, Ordering::SeqCst);
+        assert_eq!(scheduler().run_once(date("2024-01-11")).await.unwrap(), 1);
+        assert_eq!(notifier.take(), vec![(ReminderKind::Overdue, task.id)]);
+        assert_eq!(scheduler().run_once(date("2024-01-12")).await.unwrap(), 0);
+
+        // 期日を変えれば改めて送る
+        tasks
+            .update(
+                task.id,
+                UpdateTask::new(None, None, None).with_recurrence(None, Some(date("2024-01-13"))),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(scheduler().run_once(date("2024-01-12")).await.unwrap(), 1);
+        assert_eq!(notifier.take(), vec![(ReminderKind::Upcoming, task.id)]);
+    }
+}
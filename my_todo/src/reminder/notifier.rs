@@ -0,0 +1,256 @@
+use std::{env, time::Duration};
+
+use anyhow::Context;
+use axum::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use super::Reminder;
+
+const DEFAULT_SMTP_HOST: &str = "localhost";
+const DEFAULT_SMTP_PORT: u16 = 25;
+const DEFAULT_MAIL_FROM: &str = "my_todo@localhost";
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// リマインダーの送信先
+#[async_trait]
+pub trait Notifier: Send + Sync + 'static {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()>;
+}
+
+/// ログに出すだけ，送信先を用意していない環境向け
+#[derive(Debug, Clone, Default)]
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        tracing::info!(
+            task_id = reminder.task.id,
+            kind = reminder.kind.as_str(),
+            "{}",
+            reminder.subject()
+        );
+        Ok(())
+    }
+}
+
+/// ローカルの MTA へ送る，TLS と認証は使わない
+#[derive(Clone)]
+pub struct SmtpNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Mailbox,
+}
+
+impl SmtpNotifier {
+    pub fn new(host: &str, port: u16, from: &str, to: &str) -> anyhow::Result<Self> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+            .port(port)
+            .build();
+        Ok(Self {
+            transport,
+            from: from
+                .parse()
+                .with_context(|| format!("invalid sender [{}]", from))?,
+            to: to
+                .parse()
+                .with_context(|| format!("invalid recipient [{}]", to))?,
+        })
+    }
+
+    /// 宛先の `REMINDER_MAIL_TO` は必須
+    pub fn from_env() -> anyhow::Result<Self> {
+        let host = env::var("SMTP_HOST").unwrap_or(DEFAULT_SMTP_HOST.to_string());
+        let port = env::var("SMTP_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(DEFAULT_SMTP_PORT);
+        let from = env::var("REMINDER_MAIL_FROM").unwrap_or(DEFAULT_MAIL_FROM.to_string());
+        let to = env::var("REMINDER_MAIL_TO").context("undefined [REMINDER_MAIL_TO]")?;
+        Self::new(&host, port, &from, &to)
+    }
+}
+
+#[async_trait]
+impl Notifier for SmtpNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        let body = format!(
+            "{}\n\ntask: {}\ndue date: {}\n",
+            reminder.task.text,
+            reminder.task.id,
+            reminder.due_date()
+        );
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(reminder.subject())
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Reminder を JSON で POST する，2xx 以外は失敗として扱う
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()?;
+        Ok(Self { client, url })
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        let url = env::var("REMINDER_WEBHOOK_URL").context("undefined [REMINDER_WEBHOOK_URL]")?;
+        Self::new(url)
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, reminder: &Reminder) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .json(reminder)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{delivery::ReminderKind, task::TaskEntity};
+    use axum::{extract::Extension, http::StatusCode, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use std::{net::SocketAddr, sync::Arc};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::Mutex,
+    };
+
+    fn reminder() -> Reminder {
+        let mut task = TaskEntity::new(1, "write report".to_string(), vec![]);
+        task.due_date = Some("2024-01-10".parse().unwrap());
+        Reminder {
+            kind: ReminderKind::Upcoming,
+            task,
+        }
+    }
+
+    /// 受け取った body を記録し，`status` を返す受信側
+    fn spawn_receiver(status: StatusCode) -> (SocketAddr, Arc<Mutex<Vec<Value>>>) {
+        let received = Arc::new(Mutex::new(vec![]));
+        let app = Router::new()
+            .route(
+                "/hook",
+                post(
+                    move |Extension(received): Extension<Arc<Mutex<Vec<Value>>>>,
+                          Json(body): Json<Value>| async move {
+                        received.lock().await.push(body);
+                        status
+                    },
+                ),
+            )
+            .layer(Extension(received.clone()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        (addr, received)
+    }
+
+    #[tokio::test]
+    async fn should_post_reminder_to_webhook() {
+        let (addr, received) = spawn_receiver(StatusCode::NO_CONTENT);
+        let notifier = WebhookNotifier::new(format!("http://{}/hook", addr)).unwrap();
+        notifier.notify(&reminder()).await.unwrap();
+
+        let received = received.lock().await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["kind"], json!("upcoming"));
+        assert_eq!(received[0]["task"]["text"], json!("write report"));
+        assert_eq!(received[0]["task"]["due_date"], json!("2024-01-10"));
+    }
+
+    #[tokio::test]
+    async fn should_fail_on_webhook_error_status() {
+        let (addr, _) = spawn_receiver(StatusCode::INTERNAL_SERVER_ERROR);
+        let notifier = WebhookNotifier::new(format!("http://{}/hook", addr)).unwrap();
+        assert!(notifier.notify(&reminder()).await.is_err());
+    }
+
+    /// 1 通だけ受け取って DATA の中身を返す最小限の SMTP サーバー
+    async fn spawn_smtp_server() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (read, mut write) = stream.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            let mut data = String::new();
+            while let Some(line) = lines.next_line().await.unwrap() {
+                let command = line.to_ascii_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                    b"250 localhost\r\n"
+                } else if command.starts_with("DATA") {
+                    write
+                        .write_all(b"354 end with <CRLF>.<CRLF>\r\n")
+                        .await
+                        .unwrap();
+                    while let Some(line) = lines.next_line().await.unwrap() {
+                        if line == "." {
+                            break;
+                        }
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    b"250 queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    write.write_all(b"221 bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                write.write_all(reply).await.unwrap();
+            }
+            data
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn should_send_reminder_by_smtp() {
+        let (port, server) = spawn_smtp_server().await;
+        let notifier =
+            SmtpNotifier::new("127.0.0.1", port, "my_todo@localhost", "owner@localhost").unwrap();
+        notifier.notify(&reminder()).await.unwrap();
+        // 接続を閉じさせてサーバー側を終わらせる
+        drop(notifier);
+
+        let data = server.await.unwrap();
+        assert!(data.contains("To: owner@localhost"), "{}", data);
+        assert!(
+            data.contains("Subject: [my_todo] due 2024-01-10: write report"),
+            "{}",
+            data
+        );
+        assert!(data.contains("due date: 2024-01-10"), "{}", data);
+    }
+}
@@ -1,14 +1,57 @@
+pub mod cached;
+pub mod delivery;
+pub mod file;
 pub mod label;
+pub mod limits;
+pub mod maintenance;
+pub mod memory;
+pub mod metered;
+pub mod migrate;
+pub mod position;
+pub mod project;
+pub mod recurrence;
 pub mod task;
+pub mod webhook;
+pub mod webhooked;
 
+use sqlx::migrate::Migrator;
 use thiserror::Error;
 
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
 #[derive(Debug, Error)]
-enum RepositoryError {
+pub enum RepositoryError {
     #[error("Unexpected Error: [{0}]")]
     Unexpected(String),
     #[error("NotFound, id is {0}")]
     NotFound(i32),
     #[error("Duplicate data, id is {0}")]
     Duplicate(i32),
+    #[error("Version mismatch, id is {0}")]
+    VersionMismatch(i32),
+    /// 他のデータと矛盾するため操作できない
+    #[error("Conflict, id is {0}")]
+    Conflict(i32),
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::SQLITE_MIGRATOR;
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    /// マイグレーション済みのインメモリ SQLite
+    /// 接続ごとに別の DB になるため接続数は 1 に制限する
+    pub async fn sqlite_memory_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("fail connect sqlite");
+        SQLITE_MIGRATOR
+            .run(&pool)
+            .await
+            .expect("fail run sqlite migrations");
+        pool
+    }
 }
@@ -0,0 +1,442 @@
+use axum::async_trait;
+use std::{
+    collections::HashMap,
+    env,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{
+    label::{CreateLabel, Label, LabelRepository, LabelStats, UpdateLabel},
+    task::{CreateTask, MoveTask, TaskEntity, TaskRecord, TaskRepository, UpdateTask},
+};
+use crate::{
+    clock::{Clock, SystemClock},
+    metrics::REPOSITORY_CACHE_REQUESTS_TOTAL,
+};
+
+const DEFAULT_CAPACITY: usize = 1_000;
+const DEFAULT_TTL_SECONDS: u64 = 5;
+
+/// 容量か TTL が 0 ならキャッシュしない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    capacity: usize,
+    ttl: Duration,
+}
+
+impl CacheConfig {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self { capacity, ttl }
+    }
+
+    pub fn from_env() -> Self {
+        let capacity = env::var("CACHE_CAPACITY")
+            .ok()
+            .and_then(|capacity| capacity.parse().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        let ttl = env::var("CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|ttl| ttl.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS);
+        Self::new(capacity, Duration::from_secs(ttl))
+    }
+
+    fn enabled(&self) -> bool {
+        self.capacity > 0 && !self.ttl.is_zero()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    All,
+    Find(i32),
+    Occurrences(i32),
+}
+
+impl Key {
+    fn operation(&self) -> &'static str {
+        match self {
+            Key::All => "all",
+            Key::Find(_) => "find",
+            Key::Occurrences(_) => "occurrences",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Task(TaskEntity),
+    Tasks(Vec<TaskEntity>),
+    Labels(Vec<Label>),
+}
+
+#[derive(Debug)]
+struct Entry {
+    value: Value,
+    inserted_at: Instant,
+    generation: u64,
+}
+
+/// 外部からは `repository_cache_requests_total` で確認する
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// `all` と `find` の結果を保持し，書き込み時に破棄するラッパー
+#[derive(Clone)]
+pub struct Cached<T> {
+    inner: T,
+    config: CacheConfig,
+    entries: Arc<Mutex<HashMap<Key, Entry>>>,
+    // 書き込みのたびに進め，それより前に保存した結果は使わない
+    // `share_generation` で共有したキャッシュへの書き込みでも進む
+    generation: Arc<AtomicU64>,
+    clock: Arc<dyn Clock>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl<T> Cached<T> {
+    pub fn new(inner: T, config: CacheConfig) -> Self {
+        Self::with_clock(inner, config, SystemClock)
+    }
+
+    pub fn with_clock<C: Clock>(inner: T, config: CacheConfig, clock: C) -> Self {
+        Self {
+            inner,
+            config,
+            entries: Arc::default(),
+            generation: Arc::default(),
+            clock: Arc::new(clock),
+            hits: Arc::default(),
+            misses: Arc::default(),
+        }
+    }
+
+    /// `other` と世代を共有し，どちらへの書き込みでも両方のキャッシュを破棄する
+    /// タスクはラベルを含むので，ラベルの変更をタスクのキャッシュにも反映するために使う
+    pub fn share_generation<U>(mut self, other: &Cached<U>) -> Self {
+        self.generation = other.generation.clone();
+        self
+    }
+
+    #[cfg(test)]
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn get(&self, repository: &str, key: Key) -> Result<Value, u64> {
+        let now = self.clock.now();
+        let mut entries = self.entries.lock().unwrap();
+        let generation = self.generation.load(Ordering::SeqCst);
+        let value = match entries.get(&key) {
+            Some(entry) if self.is_fresh(entry, now, generation) => Some(entry.value.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        let (counter, result) = match value {
+            Some(_) => (&self.hits, "hit"),
+            None => (&self.misses, "miss"),
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        REPOSITORY_CACHE_REQUESTS_TOTAL
+            .with_label_values(&[repository, key.operation(), result])
+            .inc();
+        value.ok_or(generation)
+    }
+
+    fn is_fresh(&self, entry: &Entry, now: Instant, generation: u64) -> bool {
+        entry.generation == generation && now.duration_since(entry.inserted_at) < self.config.ttl
+    }
+
+    fn insert(&self, key: Key, value: Value, generation: u64) {
+        if !self.config.enabled() {
+            return;
+        }
+        let now = self.clock.now();
+        let mut entries = self.entries.lock().unwrap();
+        if self.generation.load(Ordering::SeqCst) != generation {
+            return;
+        }
+        if entries.len() >= self.config.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| self.is_fresh(entry, now, generation));
+        }
+        // 期限切れを除いても空きがなければ最も古いものを捨てる
+        if entries.len() >= self.config.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.inserted_at)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value,
+                inserted_at: now,
+                generation,
+            },
+        );
+    }
+
+    fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.clear();
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    async fn write<R, F>(&self, f: F) -> anyhow::Result<R>
+    where
+        F: Future<Output = anyhow::Result<R>>,
+    {
+        let res = f.await;
+        self.invalidate();
+        res
+    }
+}
+
+#[async_trait]
+impl<T: TaskRepository> TaskRepository for Cached<T> {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        self.write(self.inner.create(payload)).await
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        let generation = match self.get("task", Key::Find(id)) {
+            Ok(Value::Task(task)) => return Ok(task),
+            Ok(_) => unreachable!(),
+            Err(generation) => generation,
+        };
+        let task = self.inner.find(id).await?;
+        self.insert(Key::Find(id), Value::Task(task.clone()), generation);
+        Ok(task)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        let generation = match self.get("task", Key::All) {
+            Ok(Value::Tasks(tasks)) => return Ok(tasks),
+            Ok(_) => unreachable!(),
+            Err(generation) => generation,
+        };
+        let tasks = self.inner.all().await?;
+        self.insert(Key::All, Value::Tasks(tasks.clone()), generation);
+        Ok(tasks)
+    }
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        self.write(self.inner.update_with_next(id, payload, version))
+            .await
+    }
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+        let generation = match self.get("task", Key::Occurrences(id)) {
+            Ok(Value::Tasks(tasks)) => return Ok(tasks),
+            Ok(_) => unreachable!(),
+            Err(generation) => generation,
+        };
+        let tasks = self.inner.occurrences(id).await?;
+        self.insert(
+            Key::Occurrences(id),
+            Value::Tasks(tasks.clone()),
+            generation,
+        );
+        Ok(tasks)
+    }
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        self.write(self.inner.move_task(id, payload)).await
+    }
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        self.write(self.inner.delete(id, version)).await
+    }
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        self.inner.records().await
+    }
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        self.write(self.inner.restore(record)).await
+    }
+}
+
+#[async_trait]
+impl<T: LabelRepository> LabelRepository for Cached<T> {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        self.write(self.inner.create(payload)).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let generation = match self.get("label", Key::All) {
+            Ok(Value::Labels(labels)) => return Ok(labels),
+            Ok(_) => unreachable!(),
+            Err(generation) => generation,
+        };
+        let labels = self.inner.all().await?;
+        self.insert(Key::All, Value::Labels(labels.clone()), generation);
+        Ok(labels)
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        self.write(self.inner.update(id, payload)).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.write(self.inner.delete(id)).await
+    }
+    // タスクの変更でも変わるのでキャッシュしない
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.stats().await
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        self.write(self.inner.merge(target, sources)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        clock::test_utils::MockClock,
+        repositories::{
+            label::LabelRepositoryForMemory, memory::MemoryStore, task::TaskRepositoryForMemory,
+        },
+    };
+
+    fn cached_tasks(config: CacheConfig, clock: MockClock) -> Cached<TaskRepositoryForMemory> {
+        Cached::with_clock(TaskRepositoryForMemory::new(vec![]), config, clock)
+    }
+
+    #[tokio::test]
+    async fn should_hit_until_write() {
+        let config = CacheConfig::new(10, Duration::from_secs(60));
+        let repository = cached_tasks(config, MockClock::new());
+        let task = repository
+            .create(CreateTask::new("task".to_string(), vec![]))
+            .await
+            .unwrap();
+
+        assert_eq!(repository.all().await.unwrap(), vec![task.clone()]);
+        assert_eq!(repository.all().await.unwrap(), vec![task.clone()]);
+        assert_eq!(repository.find(task.id).await.unwrap(), task);
+        assert_eq!(repository.find(task.id).await.unwrap(), task);
+        assert_eq!(repository.stats(), CacheStats { hits: 2, misses: 2 });
+
+        repository.delete(task.id, None).await.unwrap();
+        assert!(repository.all().await.unwrap().is_empty());
+        assert!(repository.find(task.id).await.is_err());
+        assert_eq!(repository.stats(), CacheStats { hits: 2, misses: 4 });
+    }
+
+    #[tokio::test]
+    async fn should_expire_after_ttl() {
+        let clock = MockClock::new();
+        let config = CacheConfig::new(10, Duration::from_secs(5));
+        let repository = cached_tasks(config, clock.clone());
+
+        repository.all().await.unwrap();
+        clock.advance(Duration::from_secs(4));
+        repository.all().await.unwrap();
+        clock.advance(Duration::from_secs(1));
+        repository.all().await.unwrap();
+        assert_eq!(repository.stats(), CacheStats { hits: 1, misses: 2 });
+    }
+
+    #[tokio::test]
+    async fn should_evict_oldest_entry_when_full() {
+        let clock = MockClock::new();
+        let config = CacheConfig::new(2, Duration::from_secs(60));
+        let inner = TaskRepositoryForMemory::new(vec![]);
+        for text in ["first", "second"] {
+            inner
+                .create(CreateTask::new(text.to_string(), vec![]))
+                .await
+                .unwrap();
+        }
+        let repository = Cached::with_clock(inner, config, clock.clone());
+
+        repository.find(1).await.unwrap();
+        clock.advance(Duration::from_secs(1));
+        repository.find(2).await.unwrap();
+        clock.advance(Duration::from_secs(1));
+        repository.all().await.unwrap();
+        // find(1) が追い出されている
+        repository.find(2).await.unwrap();
+        repository.find(1).await.unwrap();
+        assert_eq!(repository.stats(), CacheStats { hits: 1, misses: 4 });
+    }
+
+    #[tokio::test]
+    async fn should_not_cache_when_disabled() {
+        let config = CacheConfig::new(0, Duration::from_secs(60));
+        let repository =
+            Cached::with_clock(LabelRepositoryForMemory::new(), config, MockClock::new());
+        repository.all().await.unwrap();
+        repository.all().await.unwrap();
+        assert_eq!(repository.stats(), CacheStats { hits: 0, misses: 2 });
+    }
+
+    #[tokio::test]
+    async fn should_invalidate_labels_on_create() {
+        let config = CacheConfig::new(10, Duration::from_secs(60));
+        let repository =
+            Cached::with_clock(LabelRepositoryForMemory::new(), config, MockClock::new());
+        assert!(repository.all().await.unwrap().is_empty());
+        let label = repository
+            .create(CreateLabel::new("label".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(repository.all().await.unwrap(), vec![label]);
+    }
+
+    #[tokio::test]
+    async fn should_invalidate_tasks_on_label_update() {
+        let config = CacheConfig::new(10, Duration::from_secs(60));
+        let store = MemoryStore::new();
+        let tasks = Cached::with_clock(
+            TaskRepositoryForMemory::with_store(store.clone()),
+            config,
+            MockClock::new(),
+        );
+        let labels = Cached::with_clock(
+            LabelRepositoryForMemory::with_store(store),
+            config,
+            MockClock::new(),
+        )
+        .share_generation(&tasks);
+        let label = labels
+            .create(CreateLabel::new("label".to_string()))
+            .await
+            .unwrap();
+        let task = tasks
+            .create(CreateTask::new("task".to_string(), vec![label.id]))
+            .await
+            .unwrap();
+        tasks.all().await.unwrap();
+        tasks.find(task.id).await.unwrap();
+        assert_eq!(tasks.find(task.id).await.unwrap().labels, vec![label]);
+
+        // ラベルの名前を変えるとタスクのキャッシュも破棄される
+        let label = labels
+            .update(
+                task.labels[0].id,
+                UpdateLabel::new(Some("renamed".to_string()), None),
+            )
+            .await
+            .unwrap();
+        assert_eq!(tasks.all().await.unwrap()[0].labels, vec![label.clone()]);
+        assert_eq!(tasks.find(task.id).await.unwrap().labels, vec![label]);
+        assert_eq!(tasks.stats(), CacheStats { hits: 1, misses: 4 });
+    }
+}
@@ -0,0 +1,525 @@
+use axum::async_trait;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, SqlitePool};
+
+use super::{
+    file::{FileStore, Record},
+    memory::MemoryStore,
+};
+
+/// 送信済みのリマインダーを記録し，再起動後に同じものを送らないようにする
+#[async_trait]
+pub trait DeliveryRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn is_delivered(&self, delivery: &Delivery) -> anyhow::Result<bool>;
+    /// 記録済みの場合は何もしない
+    async fn record(&self, delivery: &Delivery) -> anyhow::Result<()>;
+    /// 未完了で期日が `horizon` までのタスクのうち，まだ送っていないもの，task_id の昇順
+    /// 期日が `today` より前なら Overdue，それ以外は Upcoming
+    async fn pending(&self, today: NaiveDate, horizon: NaiveDate) -> anyhow::Result<Vec<Delivery>>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReminderKind {
+    /// 期日が近い
+    Upcoming,
+    /// 期日を過ぎた
+    Overdue,
+}
+
+impl ReminderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReminderKind::Upcoming => "upcoming",
+            ReminderKind::Overdue => "overdue",
+        }
+    }
+
+    fn of(due_date: NaiveDate, today: NaiveDate) -> Self {
+        if due_date < today {
+            ReminderKind::Overdue
+        } else {
+            ReminderKind::Upcoming
+        }
+    }
+}
+
+impl std::str::FromStr for ReminderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "upcoming" => Ok(ReminderKind::Upcoming),
+            "overdue" => Ok(ReminderKind::Overdue),
+            _ => anyhow::bail!("unknown reminder kind [{}]", s),
+        }
+    }
+}
+
+/// 期日が変われば別のリマインダーとして扱う
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Delivery {
+    pub task_id: i32,
+    pub kind: ReminderKind,
+    pub due_date: NaiveDate,
+}
+
+fn deliveries(rows: Vec<(i32, String, NaiveDate)>) -> anyhow::Result<Vec<Delivery>> {
+    rows.into_iter()
+        .map(|(task_id, kind, due_date)| {
+            Ok(Delivery {
+                task_id,
+                kind: kind.parse()?,
+                due_date,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct DeliveryRepositoryForDb {
+    pool: PgPool,
+}
+
+impl DeliveryRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DeliveryRepository for DeliveryRepositoryForDb {
+    async fn is_delivered(&self, delivery: &Delivery) -> anyhow::Result<bool> {
+        let delivered = sqlx::query(
+            r#"
+                select 1 from reminder_deliveries
+                where task_id = $1 and kind = $2 and due_date = $3
+            "#,
+        )
+        .bind(delivery.task_id)
+        .bind(delivery.kind.as_str())
+        .bind(delivery.due_date)
+        .fetch_optional(&self.pool)
+        .await?
+        .is_some();
+
+        Ok(delivered)
+    }
+    async fn record(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert into reminder_deliveries (task_id, kind, due_date)
+                values ($1, $2, $3)
+                on conflict do nothing
+            "#,
+        )
+        .bind(delivery.task_id)
+        .bind(delivery.kind.as_str())
+        .bind(delivery.due_date)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn pending(&self, today: NaiveDate, horizon: NaiveDate) -> anyhow::Result<Vec<Delivery>> {
+        let rows = sqlx::query_as::<_, (i32, String, NaiveDate)>(
+            r#"
+                select due.task_id, due.kind, due.due_date
+                from (
+                    select
+                        id as task_id,
+                        case when due_date < $1 then 'overdue' else 'upcoming' end as kind,
+                        due_date
+                    from tasks
+                    where not completed and due_date <= $2
+                ) as due
+                where not exists (
+                    select 1 from reminder_deliveries as rd
+                    where rd.task_id = due.task_id
+                        and rd.kind = due.kind
+                        and rd.due_date = due.due_date
+                )
+                order by due.task_id asc
+            "#,
+        )
+        .bind(today)
+        .bind(horizon)
+        .fetch_all(&self.pool)
+        .await?;
+        deliveries(rows)
+    }
+}
+
+#[derive(Clone)]
+pub struct DeliveryRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl DeliveryRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl DeliveryRepository for DeliveryRepositoryForSqlite {
+    async fn is_delivered(&self, delivery: &Delivery) -> anyhow::Result<bool> {
+        let delivered = sqlx::query(
+            r#"
+                select 1 from reminder_deliveries
+                where task_id = ?1 and kind = ?2 and due_date = ?3
+            "#,
+        )
+        .bind(delivery.task_id)
+        .bind(delivery.kind.as_str())
+        .bind(delivery.due_date)
+        .fetch_optional(&self.pool)
+        .await?
+        .is_some();
+
+        Ok(delivered)
+    }
+    async fn record(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                insert or ignore into reminder_deliveries (task_id, kind, due_date)
+                values (?1, ?2, ?3)
+            "#,
+        )
+        .bind(delivery.task_id)
+        .bind(delivery.kind.as_str())
+        .bind(delivery.due_date)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+    async fn pending(&self, today: NaiveDate, horizon: NaiveDate) -> anyhow::Result<Vec<Delivery>> {
+        let rows = sqlx::query_as::<_, (i32, String, NaiveDate)>(
+            r#"
+                select due.task_id, due.kind, due.due_date
+                from (
+                    select
+                        id as task_id,
+                        case when due_date < ?1 then 'overdue' else 'upcoming' end as kind,
+                        due_date
+                    from tasks
+                    where not completed and due_date <= ?2
+                ) as due
+                where not exists (
+                    select 1 from reminder_deliveries as rd
+                    where rd.task_id = due.task_id
+                        and rd.kind = due.kind
+                        and rd.due_date = due.due_date
+                )
+                order by due.task_id asc
+            "#,
+        )
+        .bind(today)
+        .bind(horizon)
+        .fetch_all(&self.pool)
+        .await?;
+        deliveries(rows)
+    }
+}
+
+/// `--storage=memory` 用，プロセス終了で記録も消える
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl DeliveryRepositoryForMemory {
+    pub fn with_store(store: MemoryStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl DeliveryRepository for DeliveryRepositoryForMemory {
+    async fn is_delivered(&self, delivery: &Delivery) -> anyhow::Result<bool> {
+        Ok(self.store.read().deliveries.contains(delivery))
+    }
+    async fn record(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        self.store.write().deliveries.insert(*delivery);
+        Ok(())
+    }
+    async fn pending(&self, today: NaiveDate, horizon: NaiveDate) -> anyhow::Result<Vec<Delivery>> {
+        let tables = self.store.read();
+        let pending = tables
+            .tasks
+            .iter()
+            .filter(|(_, task)| !task.completed)
+            .filter_map(|(id, task)| {
+                let due_date = task.due_date.filter(|due_date| *due_date <= horizon)?;
+                Some(Delivery {
+                    task_id: *id,
+                    kind: ReminderKind::of(due_date, today),
+                    due_date,
+                })
+            })
+            .filter(|delivery| !tables.deliveries.contains(delivery))
+            .collect();
+        Ok(pending)
+    }
+}
+
+/// 記録をログファイルに追記する
+#[derive(Debug, Clone)]
+pub struct DeliveryRepositoryForFile {
+    inner: DeliveryRepositoryForMemory,
+    store: FileStore,
+}
+
+impl DeliveryRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            inner: DeliveryRepositoryForMemory::with_store(store.memory()),
+            store,
+        }
+    }
+}
+
+#[async_trait]
+impl DeliveryRepository for DeliveryRepositoryForFile {
+    async fn is_delivered(&self, delivery: &Delivery) -> anyhow::Result<bool> {
+        self.inner.is_delivered(delivery).await
+    }
+    async fn record(&self, delivery: &Delivery) -> anyhow::Result<()> {
+        let mut journal = self.store.journal().await;
+        if self.inner.is_delivered(delivery).await? {
+            return Ok(());
+        }
+        journal.append(&Record::Delivered(*delivery))?;
+        self.inner.record(delivery).await
+    }
+    async fn pending(&self, today: NaiveDate, horizon: NaiveDate) -> anyhow::Result<Vec<Delivery>> {
+        self.inner.pending(today, horizon).await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::task::{CreateTask, TaskRepository, TaskRepositoryForDb};
+    use dotenv::dotenv;
+    use std::env;
+
+    async fn pool() -> PgPool {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url))
+    }
+
+    #[tokio::test]
+    async fn record_scenario() {
+        let pool = pool().await;
+        // 他のテストと重ならない task_id を使う，記録はタスクと一緒に消える
+        let tasks = TaskRepositoryForDb::new(pool.clone());
+        let task = tasks
+            .create(CreateTask::new(
+                "[delivery record_scenario]".to_string(),
+                vec![],
+            ))
+            .await
+            .unwrap();
+
+        test_utils::record_scenario(&DeliveryRepositoryForDb::new(pool), task.id).await;
+        tasks.delete(task.id, None).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pending_scenario() {
+        let pool = pool().await;
+        test_utils::pending_scenario(
+            &TaskRepositoryForDb::new(pool.clone()),
+            &DeliveryRepositoryForDb::new(pool),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::{
+        task::{CreateTask, TaskRepository, TaskRepositoryForSqlite},
+        test_utils::sqlite_memory_pool,
+    };
+
+    #[tokio::test]
+    async fn record_scenario() {
+        let pool = sqlite_memory_pool().await;
+        let task = TaskRepositoryForSqlite::new(pool.clone())
+            .create(CreateTask::new("task".to_string(), vec![]))
+            .await
+            .unwrap();
+        let repository = DeliveryRepositoryForSqlite::new(pool);
+        test_utils::record_scenario(&repository, task.id).await;
+    }
+
+    #[tokio::test]
+    async fn pending_scenario() {
+        let pool = sqlite_memory_pool().await;
+        test_utils::pending_scenario(
+            &TaskRepositoryForSqlite::new(pool.clone()),
+            &DeliveryRepositoryForSqlite::new(pool),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod memory_test {
+    use super::*;
+
+    use crate::repositories::task::TaskRepositoryForMemory;
+
+    #[tokio::test]
+    async fn record_scenario() {
+        test_utils::record_scenario(&DeliveryRepositoryForMemory::default(), 1).await;
+    }
+
+    #[tokio::test]
+    async fn pending_scenario() {
+        let store = MemoryStore::new();
+        test_utils::pending_scenario(
+            &TaskRepositoryForMemory::with_store(store.clone()),
+            &DeliveryRepositoryForMemory::with_store(store),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use super::*;
+    use crate::repositories::{
+        file::test_utils::temp_data_file,
+        task::{CreateTask, TaskRepository, TaskRepositoryForFile},
+    };
+
+    #[tokio::test]
+    async fn record_scenario() {
+        let path = temp_data_file();
+        let (task, deleted) = {
+            let store = FileStore::open(&path).unwrap();
+            let tasks = TaskRepositoryForFile::new(store.clone());
+            let task = tasks
+                .create(CreateTask::new("task".to_string(), vec![]))
+                .await
+                .unwrap();
+            let deleted = tasks
+                .create(CreateTask::new("deleted".to_string(), vec![]))
+                .await
+                .unwrap();
+            let repository = DeliveryRepositoryForFile::new(store);
+            test_utils::record_scenario(&repository, task.id).await;
+            test_utils::record_scenario(&repository, deleted.id).await;
+            tasks.delete(deleted.id, None).await.unwrap();
+            (task, deleted)
+        };
+
+        // 開き直しても記録は残る，削除済みのタスクの分は書き直しで捨てる
+        let store = FileStore::open(&path).unwrap();
+        let repository = DeliveryRepositoryForFile::new(store.clone());
+        let delivery = test_utils::delivery(task.id, ReminderKind::Upcoming, "2024-01-01");
+        assert!(repository.is_delivered(&delivery).await.unwrap());
+        let delivery = test_utils::delivery(deleted.id, ReminderKind::Upcoming, "2024-01-01");
+        assert!(!repository.is_delivered(&delivery).await.unwrap());
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::task::{CreateTask, TaskRepository, UpdateTask};
+
+    pub fn delivery(task_id: i32, kind: ReminderKind, due_date: &str) -> Delivery {
+        Delivery {
+            task_id,
+            kind,
+            due_date: due_date.parse().unwrap(),
+        }
+    }
+
+    /// 各実装で共通のシナリオ
+    pub async fn record_scenario<T: DeliveryRepository>(repository: &T, task_id: i32) {
+        let upcoming = delivery(task_id, ReminderKind::Upcoming, "2024-01-01");
+        assert!(!repository.is_delivered(&upcoming).await.unwrap());
+
+        repository.record(&upcoming).await.unwrap();
+        // 2 回目の記録はエラーにしない
+        repository.record(&upcoming).await.unwrap();
+        assert!(repository.is_delivered(&upcoming).await.unwrap());
+
+        // 種類や期日が違えば別物
+        let overdue = delivery(task_id, ReminderKind::Overdue, "2024-01-01");
+        let moved = delivery(task_id, ReminderKind::Upcoming, "2024-01-02");
+        assert!(!repository.is_delivered(&overdue).await.unwrap());
+        assert!(!repository.is_delivered(&moved).await.unwrap());
+    }
+
+    /// 期日を過ぎたものと近いもののうち，送っていないものだけを返す
+    /// 記録はタスクと一緒に消える
+    pub async fn pending_scenario<T, D>(tasks: &T, repository: &D)
+    where
+        T: TaskRepository,
+        D: DeliveryRepository,
+    {
+        let create = |text: &str, due_date: Option<&str>| {
+            tasks.create(
+                CreateTask::new(format!("[pending_scenario] {}", text), vec![])
+                    .with_recurrence(None, due_date.map(|date| date.parse().unwrap())),
+            )
+        };
+        let overdue = create("overdue", Some("2024-01-09")).await.unwrap();
+        let today = create("today", Some("2024-01-10")).await.unwrap();
+        let tomorrow = create("tomorrow", Some("2024-01-11")).await.unwrap();
+        let later = create("later", Some("2024-01-12")).await.unwrap();
+        let done = create("done", Some("2024-01-01")).await.unwrap();
+        tasks
+            .update(done.id, UpdateTask::new(None, Some(true), None), None)
+            .await
+            .unwrap();
+        let undated = create("no due date", None).await.unwrap();
+        let ids = [
+            overdue.id,
+            today.id,
+            tomorrow.id,
+            later.id,
+            done.id,
+            undated.id,
+        ];
+
+        // 他のテストのタスクは除いて比べる
+        let pending = || async {
+            repository
+                .pending("2024-01-10".parse().unwrap(), "2024-01-11".parse().unwrap())
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|delivery| ids.contains(&delivery.task_id))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            delivery(overdue.id, ReminderKind::Overdue, "2024-01-09"),
+            delivery(today.id, ReminderKind::Upcoming, "2024-01-10"),
+            delivery(tomorrow.id, ReminderKind::Upcoming, "2024-01-11"),
+        ];
+        assert_eq!(pending().await, expected);
+
+        repository.record(&expected[0]).await.unwrap();
+        assert_eq!(pending().await, expected[1..]);
+
+        tasks.delete(overdue.id, None).await.unwrap();
+        assert!(!repository.is_delivered(&expected[0]).await.unwrap());
+        for id in &ids[1..] {
+            tasks.delete(*id, None).await.unwrap();
+        }
+    }
+}
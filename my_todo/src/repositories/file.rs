@@ -0,0 +1,762 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions, TryLockError},
+    future::Future,
+    io::Write,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, MutexGuard};
+
+use super::{
+    delivery::{Delivery, ReminderKind},
+    label::Label,
+    memory::{MemoryStore, Tables, TaskRow},
+    position,
+    project::Project,
+    webhook::{EventType, Webhook},
+};
+
+/// ログの 1 行，改行まで書けたものだけを有効とする
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(super) enum Record {
+    Sequence {
+        task: i32,
+        label: i32,
+        #[serde(default)]
+        webhook: i32,
+        #[serde(default)]
+        project: i32,
+    },
+    PutLabel {
+        id: i32,
+        name: String,
+        #[serde(default)]
+        color: Option<String>,
+        #[serde(default)]
+        description: Option<String>,
+        #[serde(default)]
+        position: i32,
+        #[serde(default)]
+        parent_id: Option<i32>,
+    },
+    DeleteLabel {
+        id: i32,
+    },
+    PutTask {
+        id: i32,
+        text: String,
+        completed: bool,
+        version: i32,
+        labels: Vec<i32>,
+        // 追加前のログには無いため省略可
+        #[serde(default)]
+        recurrence: Option<String>,
+        #[serde(default)]
+        due_date: Option<NaiveDate>,
+        #[serde(default)]
+        series_id: Option<i32>,
+        #[serde(default)]
+        position: String,
+        #[serde(default)]
+        project_id: Option<i32>,
+        /// (label_id, 付けた日時)，日時の無いラベルは含めない
+        #[serde(default)]
+        attached_at: Vec<(i32, DateTime<Utc>)>,
+    },
+    DeleteTask {
+        id: i32,
+    },
+    Delivered(Delivery),
+    PutWebhook {
+        id: i32,
+        url: String,
+        secret: String,
+        events: Vec<EventType>,
+    },
+    DeleteWebhook {
+        id: i32,
+    },
+    PutProject {
+        id: i32,
+        name: String,
+    },
+    DeleteProject {
+        id: i32,
+    },
+}
+
+impl Record {
+    /// `id` のタスクは存在していること
+    pub fn put_task(tables: &Tables, id: i32) -> Self {
+        let row = &tables.tasks[&id];
+        Record::PutTask {
+            id,
+            text: row.text.clone(),
+            completed: row.completed,
+            version: row.version,
+            labels: tables.labels_of(id).iter().map(|label| label.id).collect(),
+            attached_at: tables
+                .task_labels
+                .range((id, i32::MIN)..=(id, i32::MAX))
+                .filter_map(|((_, label_id), at)| Some((*label_id, (*at)?)))
+                .collect(),
+            recurrence: row.recurrence.clone(),
+            due_date: row.due_date,
+            series_id: row.series_id,
+            position: row.position.clone(),
+            project_id: row.project_id,
+        }
+    }
+
+    pub fn put_label(label: &Label) -> Self {
+        Record::PutLabel {
+            id: label.id,
+            name: label.name.clone(),
+            color: label.color.clone(),
+            description: label.description.clone(),
+            position: label.position,
+            parent_id: label.parent_id,
+        }
+    }
+
+    pub fn put_project(project: &Project) -> Self {
+        Record::PutProject {
+            id: project.id,
+            name: project.name.clone(),
+        }
+    }
+
+    pub fn put_webhook(webhook: &Webhook) -> Self {
+        Record::PutWebhook {
+            id: webhook.id,
+            url: webhook.url.clone(),
+            secret: webhook.secret.clone(),
+            events: webhook.events.clone(),
+        }
+    }
+
+    fn apply(self, tables: &mut Tables) {
+        match self {
+            Record::Sequence {
+                task,
+                label,
+                webhook,
+                project,
+            } => {
+                tables.task_seq = tables.task_seq.max(task);
+                tables.project_seq = tables.project_seq.max(project);
+                tables.label_seq = tables.label_seq.max(label);
+                tables.webhook_seq = tables.webhook_seq.max(webhook);
+            }
+            Record::PutLabel {
+                id,
+                name,
+                color,
+                description,
+                position,
+                parent_id,
+            } => tables.insert_label(Label {
+                id,
+                name,
+                color,
+                description,
+                position,
+                parent_id,
+            }),
+            Record::DeleteLabel { id } => {
+                tables.labels.remove(&id);
+            }
+            Record::PutTask {
+                id,
+                text,
+                completed,
+                version,
+                labels,
+                recurrence,
+                due_date,
+                series_id,
+                position,
+                project_id,
+                attached_at,
+            } => {
+                tables.task_seq = tables.task_seq.max(id);
+                // キーが無い頃のログはこれまでと同じく id の降順に並べる
+                let position = match position.is_empty() {
+                    true => position::legacy(id),
+                    false => position,
+                };
+                tables.tasks.insert(
+                    id,
+                    TaskRow {
+                        text,
+                        completed,
+                        version,
+                        recurrence,
+                        due_date,
+                        series_id,
+                        position,
+                        project_id,
+                    },
+                );
+                let attached_at: BTreeMap<i32, DateTime<Utc>> = attached_at.into_iter().collect();
+                tables.task_labels.retain(|(task_id, _), _| *task_id != id);
+                tables.task_labels.extend(
+                    labels
+                        .into_iter()
+                        .map(|label_id| ((id, label_id), attached_at.get(&label_id).copied())),
+                );
+            }
+            Record::DeleteTask { id } => {
+                tables.tasks.remove(&id);
+                tables.task_labels.retain(|(task_id, _), _| *task_id != id);
+                tables.deliveries.retain(|delivery| delivery.task_id != id);
+            }
+            Record::Delivered(delivery) => {
+                tables.deliveries.insert(delivery);
+            }
+            Record::PutWebhook {
+                id,
+                url,
+                secret,
+                events,
+            } => {
+                tables.webhook_seq = tables.webhook_seq.max(id);
+                tables.webhooks.insert(
+                    id,
+                    Webhook {
+                        id,
+                        url,
+                        secret,
+                        events,
+                    },
+                );
+            }
+            Record::DeleteWebhook { id } => {
+                tables.webhooks.remove(&id);
+            }
+            Record::PutProject { id, name } => {
+                tables.project_seq = tables.project_seq.max(id);
+                tables.projects.insert(id, Project { id, name });
+            }
+            Record::DeleteProject { id } => {
+                tables.projects.remove(&id);
+            }
+        }
+    }
+}
+
+/// 現在の状態を再現する最小のレコード列
+fn snapshot(tables: &Tables) -> Vec<Record> {
+    let mut records = vec![Record::Sequence {
+        task: tables.task_seq,
+        label: tables.label_seq,
+        webhook: tables.webhook_seq,
+        project: tables.project_seq,
+    }];
+    records.extend(tables.labels.values().map(Record::put_label));
+    records.extend(tables.projects.values().map(Record::put_project));
+    records.extend(tables.tasks.keys().map(|id| Record::put_task(tables, *id)));
+    // 削除済みのタスクの id は再利用しないので，その記録は捨ててよい
+    records.extend(
+        tables
+            .deliveries
+            .iter()
+            .filter(|delivery| tables.tasks.contains_key(&delivery.task_id))
+            .map(|delivery| Record::Delivered(*delivery)),
+    );
+    records.extend(tables.webhooks.values().map(Record::put_webhook));
+    records
+}
+
+/// 追記専用のログファイル
+#[derive(Debug)]
+pub(super) struct Journal {
+    file: File,
+    len: u64,
+    // drop されるまでロックを保持する
+    _lock: File,
+}
+
+impl Journal {
+    pub fn append(&mut self, record: &Record) -> anyhow::Result<()> {
+        self.append_all(std::slice::from_ref(record))
+    }
+
+    /// まとめて書き込み，失敗した場合は途中まで書いた行を切り詰めて元に戻す
+    pub fn append_all(&mut self, records: &[Record]) -> anyhow::Result<()> {
+        let mut lines = vec![];
+        for record in records {
+            serde_json::to_writer(&mut lines, record)?;
+            lines.push(b'\n');
+        }
+        let res = self
+            .file
+            .write_all(&lines)
+            .and_then(|_| self.file.sync_data());
+        if let Err(e) = res {
+            self.file.set_len(self.len)?;
+            return Err(e.into());
+        }
+        self.len += lines.len() as u64;
+        Ok(())
+    }
+}
+
+/// JSON Lines のログに永続化するストア
+/// 起動時にログを読み込み，スナップショットへ書き直してから追記を始める
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    memory: MemoryStore,
+    journal: Arc<Mutex<Journal>>,
+}
+
+impl FileStore {
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let lock = lock(path)?;
+
+        let memory = MemoryStore::new();
+        for record in read_log(path)? {
+            record.apply(&mut memory.write());
+        }
+
+        compact(path, &snapshot(&memory.read()))?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        let len = file.metadata()?.len();
+        let journal = Journal {
+            file,
+            len,
+            _lock: lock,
+        };
+
+        Ok(Self {
+            memory,
+            journal: Arc::new(Mutex::new(journal)),
+        })
+    }
+
+    pub(super) fn memory(&self) -> MemoryStore {
+        self.memory.clone()
+    }
+
+    /// 書き込みの間保持し，メモリ上の変更とログの順序を揃える
+    pub(super) async fn journal(&self) -> MutexGuard<'_, Journal> {
+        self.journal.lock().await
+    }
+
+    /// メモリ上で変更し，返されたレコードをログに追記する
+    /// `touched` が返す行だけを変更前に控えておき，変更か追記に失敗した場合はそれを書き戻してログに無い変更を残さない
+    pub(super) async fn commit<T, P, F, Fut>(&self, touched: P, change: F) -> anyhow::Result<T>
+    where
+        P: FnOnce(&Tables) -> Touched,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<(T, Vec<Record>)>>,
+    {
+        let mut journal = self.journal().await;
+        let backup = {
+            let tables = self.memory.read();
+            Backup::new(&tables, touched(&tables))
+        };
+        let res = match change().await {
+            Ok((value, records)) => journal.append_all(&records).map(|_| value),
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            backup.restore(&mut self.memory.write());
+        }
+        res
+    }
+}
+
+/// 変更で書き換わる既存の行の id，新しく作る行は連番から分かるので含めなくてよい
+#[derive(Debug, Default)]
+pub(super) struct Touched {
+    pub tasks: Vec<i32>,
+    pub labels: Vec<i32>,
+    pub projects: Vec<i32>,
+    pub webhooks: Vec<i32>,
+}
+
+impl Touched {
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    pub fn tasks(ids: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            tasks: ids.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn labels(ids: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            labels: ids.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn projects(ids: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            projects: ids.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn webhooks(ids: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            webhooks: ids.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+}
+
+/// タスクの行と，それに紐づくラベルと送信記録
+#[derive(Debug)]
+struct TaskBackup {
+    id: i32,
+    row: Option<TaskRow>,
+    /// (label_id, 付けた日時)
+    labels: Vec<(i32, Option<DateTime<Utc>>)>,
+    deliveries: Vec<Delivery>,
+}
+
+/// 変更前の連番と `Touched` の行，書き戻すのにかかる時間は控えた行の数だけで決まる
+#[derive(Debug)]
+struct Backup {
+    task_seq: i32,
+    label_seq: i32,
+    webhook_seq: i32,
+    project_seq: i32,
+    tasks: Vec<TaskBackup>,
+    labels: Vec<(i32, Option<Label>)>,
+    projects: Vec<(i32, Option<Project>)>,
+    webhooks: Vec<(i32, Option<Webhook>)>,
+}
+
+impl Backup {
+    fn new(tables: &Tables, touched: Touched) -> Self {
+        let tasks = touched
+            .tasks
+            .into_iter()
+            .map(|id| TaskBackup {
+                id,
+                row: tables.tasks.get(&id).cloned(),
+                labels: tables
+                    .task_labels
+                    .range((id, i32::MIN)..=(id, i32::MAX))
+                    .map(|((_, label_id), attached_at)| (*label_id, *attached_at))
+                    .collect(),
+                deliveries: tables
+                    .deliveries
+                    .range(deliveries_of(id))
+                    .copied()
+                    .collect(),
+            })
+            .collect();
+        Self {
+            task_seq: tables.task_seq,
+            label_seq: tables.label_seq,
+            webhook_seq: tables.webhook_seq,
+            project_seq: tables.project_seq,
+            tasks,
+            labels: rows(&tables.labels, touched.labels),
+            projects: rows(&tables.projects, touched.projects),
+            webhooks: rows(&tables.webhooks, touched.webhooks),
+        }
+    }
+
+    fn restore(self, tables: &mut Tables) {
+        // 連番より後ろは今回作った行
+        tables.tasks.split_off(&(self.task_seq + 1));
+        tables.task_labels.split_off(&(self.task_seq + 1, i32::MIN));
+        tables.labels.split_off(&(self.label_seq + 1));
+        tables.webhooks.split_off(&(self.webhook_seq + 1));
+        tables.projects.split_off(&(self.project_seq + 1));
+        tables.task_seq = self.task_seq;
+        tables.label_seq = self.label_seq;
+        tables.webhook_seq = self.webhook_seq;
+        tables.project_seq = self.project_seq;
+
+        for task in self.tasks {
+            match task.row {
+                Some(row) => tables.tasks.insert(task.id, row),
+                None => tables.tasks.remove(&task.id),
+            };
+            let current = tables
+                .task_labels
+                .range((task.id, i32::MIN)..=(task.id, i32::MAX))
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+            for key in current {
+                tables.task_labels.remove(&key);
+            }
+            tables.task_labels.extend(
+                task.labels
+                    .into_iter()
+                    .map(|(label_id, attached_at)| ((task.id, label_id), attached_at)),
+            );
+            tables.deliveries.extend(task.deliveries);
+        }
+        restore_rows(&mut tables.labels, self.labels);
+        restore_rows(&mut tables.projects, self.projects);
+        restore_rows(&mut tables.webhooks, self.webhooks);
+    }
+}
+
+fn rows<V: Clone>(table: &BTreeMap<i32, V>, ids: Vec<i32>) -> Vec<(i32, Option<V>)> {
+    ids.into_iter()
+        .map(|id| (id, table.get(&id).cloned()))
+        .collect()
+}
+
+fn restore_rows<V>(table: &mut BTreeMap<i32, V>, rows: Vec<(i32, Option<V>)>) {
+    for (id, row) in rows {
+        match row {
+            Some(row) => table.insert(id, row),
+            None => table.remove(&id),
+        };
+    }
+}
+
+/// `Delivery` は task_id から順に並ぶので，1 つのタスクの記録は連続する
+fn deliveries_of(task_id: i32) -> RangeInclusive<Delivery> {
+    Delivery {
+        task_id,
+        kind: ReminderKind::Upcoming,
+        due_date: NaiveDate::MIN,
+    }..=Delivery {
+        task_id,
+        kind: ReminderKind::Overdue,
+        due_date: NaiveDate::MAX,
+    }
+}
+
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(extension);
+    PathBuf::from(name)
+}
+
+/// 別プロセスと同じファイルを共有しないよう排他ロックを取る
+/// OS のロックなのでプロセスが落ちれば解放される
+fn lock(path: &Path) -> anyhow::Result<File> {
+    let lock_path = sibling(path, ".lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("fail open lock file [{}]", lock_path.display()))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => Err(anyhow::anyhow!(
+            "[{}] is used by another process",
+            path.display()
+        )),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// 改行で終わっていない末尾は書き込み途中で落ちたものとして捨てる
+fn read_log(path: &Path) -> anyhow::Result<Vec<Record>> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut lines = content.split(|b| *b == b'\n').collect::<Vec<_>>();
+    // 改行で終わっていれば最後は空になる
+    let tail = lines.pop().unwrap_or_default();
+    if !tail.is_empty() {
+        tracing::warn!(
+            "discard truncated tail of [{}] ({} bytes)",
+            path.display(),
+            tail.len()
+        );
+    }
+
+    let mut records = vec![];
+    for (i, line) in lines.into_iter().enumerate() {
+        let record = serde_json::from_slice(line)
+            .with_context(|| format!("corrupted record at {}:{}", path.display(), i + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// 一時ファイルに書いてから rename するので，途中で落ちても元のファイルは壊れない
+fn compact(path: &Path, records: &[Record]) -> anyhow::Result<()> {
+    let tmp_path = sibling(path, ".tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    for record in records {
+        serde_json::to_writer(&mut tmp, record)?;
+        tmp.write_all(b"\n")?;
+    }
+    tmp.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use std::path::PathBuf;
+
+    /// テストごとに別のデータファイルのパスを返す
+    pub fn temp_data_file() -> PathBuf {
+        std::env::temp_dir().join(format!("my_todo-{}.jsonl", uuid::Uuid::new_v4()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepository, LabelRepositoryForFile},
+        task::{CreateTask, TaskRepository, TaskRepositoryForFile, UpdateTask},
+    };
+    use test_utils::temp_data_file;
+
+    #[tokio::test]
+    async fn should_restore_after_reopen() {
+        let path = temp_data_file();
+        let (label, task) = {
+            let store = FileStore::open(&path).unwrap();
+            let label = LabelRepositoryForFile::new(store.clone())
+                .create(CreateLabel::new("label".to_string()))
+                .await
+                .unwrap();
+            let tasks = TaskRepositoryForFile::new(store);
+            let task = tasks
+                .create(CreateTask::new("task".to_string(), vec![label.id]))
+                .await
+                .unwrap();
+            let deleted = tasks
+                .create(CreateTask::new("deleted".to_string(), vec![]))
+                .await
+                .unwrap();
+            tasks.delete(deleted.id, None).await.unwrap();
+            (label, task)
+        };
+
+        let store = FileStore::open(&path).unwrap();
+        let labels = LabelRepositoryForFile::new(store.clone());
+        let tasks = TaskRepositoryForFile::new(store);
+        assert_eq!(labels.all().await.unwrap(), vec![label]);
+        assert_eq!(tasks.all().await.unwrap(), vec![task]);
+        // 削除済みの id は再利用しない
+        let created = tasks
+            .create(CreateTask::new("new".to_string(), vec![]))
+            .await
+            .unwrap();
+        assert_eq!(created.id, 3);
+    }
+
+    #[tokio::test]
+    async fn should_roll_back_when_append_fails() {
+        let path = temp_data_file();
+        let task = {
+            let store = FileStore::open(&path).unwrap();
+            let labels = LabelRepositoryForFile::new(store.clone());
+            let tasks = TaskRepositoryForFile::new(store.clone());
+            let label = labels
+                .create(CreateLabel::new("label".to_string()))
+                .await
+                .unwrap();
+            let target = labels
+                .create(CreateLabel::new("target".to_string()))
+                .await
+                .unwrap();
+            let task = tasks
+                .create(CreateTask::new("task".to_string(), vec![label.id]))
+                .await
+                .unwrap();
+
+            // 読み込み専用で開き直し，以降の追記を失敗させる
+            store.journal().await.file = File::open(&path).unwrap();
+            assert!(tasks.delete(task.id, None).await.is_err());
+            assert!(tasks
+                .update(
+                    task.id,
+                    UpdateTask::new(Some("changed".to_string()), Some(true), Some(vec![])),
+                    None,
+                )
+                .await
+                .is_err());
+            assert!(labels.merge(target.id, &[label.id]).await.is_err());
+            assert!(labels.delete(target.id).await.is_err());
+            assert!(tasks
+                .create(CreateTask::new("new".to_string(), vec![label.id]))
+                .await
+                .is_err());
+            assert_eq!(tasks.all().await.unwrap(), vec![task.clone()]);
+            assert_eq!(labels.all().await.unwrap(), vec![label, target]);
+            // 作りかけの行も連番も残さない
+            let tables = store.memory().read().clone();
+            assert_eq!(tables.task_labels.len(), 1);
+            assert_eq!(tables.task_seq, task.id);
+            task
+        };
+
+        let store = FileStore::open(&path).unwrap();
+        let tasks = TaskRepositoryForFile::new(store);
+        assert_eq!(tasks.all().await.unwrap(), vec![task]);
+    }
+
+    #[test]
+    fn should_not_open_locked_file() {
+        let path = temp_data_file();
+        let store = FileStore::open(&path).unwrap();
+        assert!(FileStore::open(&path).is_err());
+        drop(store);
+        assert!(FileStore::open(&path).is_ok());
+    }
+
+    #[test]
+    fn should_discard_truncated_tail() {
+        let path = temp_data_file();
+        fs::write(
+            &path,
+            concat!(
+                r#"{"op":"put_label","id":1,"name":"label"}"#,
+                "\n",
+                r#"{"op":"put_label","id":2,"na"#,
+            ),
+        )
+        .unwrap();
+
+        let store = FileStore::open(&path).unwrap();
+        let labels = store
+            .memory()
+            .read()
+            .labels
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec![Label::new(1, "label".to_string())]);
+        drop(store);
+
+        // 書き直されたログは改行で終わる
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with('\n'));
+        assert_eq!(read_log(&path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn should_fail_on_corrupted_record() {
+        let path = temp_data_file();
+        fs::write(&path, "{\"op\":\"unknown\"}\n").unwrap();
+        assert!(FileStore::open(&path).is_err());
+    }
+}
@@ -1,28 +1,286 @@
+use async_graphql::SimpleObject;
 use axum::async_trait;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgPool, SqlitePool};
+use std::collections::HashMap;
+use utoipa::{openapi::ObjectBuilder, ToSchema};
+use validator::{Validate, ValidationError};
 
-use super::RepositoryError;
+use super::{
+    file::{FileStore, Record, Touched},
+    limits::{
+        description_schema, optional_text_schema, text_schema, DESCRIPTION_MAX_LEN, TEXT_MAX_LEN,
+        TEXT_MIN_LEN,
+    },
+    memory::{MemoryStore, Tables},
+    task::{memory_entity, TaskEntity, TaskRepositoryForDb, TaskRepositoryForSqlite},
+    RepositoryError,
+};
 
 #[async_trait]
 pub trait LabelRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, name: String) -> anyhow::Result<Label>;
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label>;
+    /// position の昇順，同じ場合は id の昇順
     async fn all(&self) -> anyhow::Result<Vec<Label>>;
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label>;
     async fn delete(&self, id: i32) -> anyhow::Result<()>;
+    /// ラベルごとの使用状況，all と同じ順
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>>;
+    /// `sources` を `target` に付け替えて削除し，`sources` が付いていたタスクを付け替えた後の状態で返す
+    /// 既に `target` が付いていて `sources` が外れただけのタスクも含める，id の昇順
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>>;
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct Label {
     pub id: i32,
     pub name: String,
+    /// `#rrggbb` 形式
+    pub color: Option<String>,
+    pub description: Option<String>,
+    /// 一覧での並び順
+    pub position: i32,
+    /// 親ラベル，None はトップレベル
+    pub parent_id: Option<i32>,
 }
 
-/*
-pub struct UpdateLabel {
-    id: i32,
+impl Label {
+    pub fn new(id: i32, name: String) -> Self {
+        Self {
+            id,
+            name,
+            color: None,
+            description: None,
+            position: 0,
+            parent_id: None,
+        }
+    }
+}
+
+/// `GET /label/stats` の 1 行
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct LabelStats {
+    pub label_id: i32,
+    pub name: String,
+    pub open_tasks: i64,
+    pub completed_tasks: i64,
+    /// 一度も付けていないか，日時を記録する前に付けたものだけなら None
+    pub last_attached_at: Option<DateTime<Utc>>,
+}
+
+/// `GET /label?tree=true` で返す階層
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, ToSchema)]
+pub struct LabelTree {
+    #[serde(flatten)]
+    pub label: Label,
+    pub children: Vec<LabelTree>,
+}
+
+/// 並び順を保ったまま階層に組み立てる，`labels` は `all` の結果
+pub fn tree(labels: Vec<Label>) -> Vec<LabelTree> {
+    let mut children: HashMap<Option<i32>, Vec<Label>> = HashMap::new();
+    for label in labels {
+        children.entry(label.parent_id).or_default().push(label);
+    }
+    fn build(
+        parent: Option<i32>,
+        children: &mut HashMap<Option<i32>, Vec<Label>>,
+    ) -> Vec<LabelTree> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|label| LabelTree {
+                children: build(Some(label.id), children),
+                label,
+            })
+            .collect()
+    }
+    build(None, &mut children)
+}
+
+/// `id` とその子孫のラベルの id
+pub fn descendants(labels: &[Label], id: i32) -> Vec<i32> {
+    let mut ids = vec![id];
+    let mut i = 0;
+    while let Some(parent) = ids.get(i).copied() {
+        ids.extend(
+            labels
+                .iter()
+                .filter(|label| label.parent_id == Some(parent))
+                .map(|label| label.id),
+        );
+        i += 1;
+    }
+    ids
+}
+
+/// `parents` は全ラベルの (id, parent_id)，`id` は新規作成なら None
+/// `parent` から親を辿って `id` に戻ってくる場合は循環になる
+fn check_parent(
+    parents: &[(i32, Option<i32>)],
+    id: Option<i32>,
+    parent: i32,
+) -> Result<(), RepositoryError> {
+    let parents: HashMap<i32, Option<i32>> = parents.iter().copied().collect();
+    let mut current = Some(parent);
+    while let Some(ancestor) = current {
+        if Some(ancestor) == id {
+            return Err(RepositoryError::Conflict(ancestor));
+        }
+        current = *parents
+            .get(&ancestor)
+            .ok_or(RepositoryError::NotFound(ancestor))?;
+    }
+    Ok(())
+}
+
+/// `sources` を `target` にまとめられるか，`parents` は全ラベルの (id, parent_id)
+/// `target` が `sources` の子孫の場合は，子ラベルを `target` の下に移すと循環するのでまとめられない
+fn check_merge(
+    parents: &[(i32, Option<i32>)],
+    target: i32,
+    sources: &[i32],
+) -> Result<(), RepositoryError> {
+    let parents: HashMap<i32, Option<i32>> = parents.iter().copied().collect();
+    for id in std::iter::once(&target).chain(sources) {
+        if !parents.contains_key(id) {
+            return Err(RepositoryError::NotFound(*id));
+        }
+    }
+    let mut current = Some(target);
+    while let Some(ancestor) = current {
+        if sources.contains(&ancestor) {
+            return Err(RepositoryError::Conflict(ancestor));
+        }
+        current = parents.get(&ancestor).copied().flatten();
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct MergeLabels {
+    /// target に付け替えて削除するラベル
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    pub sources: Vec<i32>,
+}
+
+/// 空文字で説明を消せることをスキーマの説明に残す
+fn cleared_description_schema() -> ObjectBuilder {
+    description_schema().description(Some("空文字で説明を消す"))
+}
+
+/// 0 で親から外す，None は変更しない
+fn stored_parent(payload: Option<i32>, old: Option<i32>) -> Option<i32> {
+    match payload {
+        Some(0) => None,
+        Some(id) => Some(id),
+        None => old,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct CreateLabel {
+    #[validate(length(min = "TEXT_MIN_LEN", message = "Can not be empty"))]
+    #[validate(length(max = "TEXT_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = text_schema)]
     name: String,
+    /// `#rrggbb` 形式
+    #[validate(custom = "validate_color")]
+    #[schema(pattern = "^#[0-9a-fA-F]{6}$")]
+    color: Option<String>,
+    #[validate(length(max = "DESCRIPTION_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = description_schema)]
+    description: Option<String>,
+    /// 省略すると末尾に置く
+    position: Option<i32>,
+    parent_id: Option<i32>,
+}
+
+impl CreateLabel {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            color: None,
+            description: None,
+            position: None,
+            parent_id: None,
+        }
+    }
+
+    pub fn with_parent(mut self, parent_id: Option<i32>) -> Self {
+        self.parent_id = parent_id;
+        self
+    }
+
+    pub fn with_details(mut self, color: Option<String>, description: Option<String>) -> Self {
+        self.color = color;
+        self.description = description;
+        self
+    }
+
+    pub fn with_position(mut self, position: Option<i32>) -> Self {
+        self.position = position;
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default, Validate, ToSchema)]
+pub struct UpdateLabel {
+    #[validate(length(min = "TEXT_MIN_LEN", message = "Can not be empty"))]
+    #[validate(length(max = "TEXT_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = optional_text_schema)]
+    name: Option<String>,
+    /// 空文字で色を消す
+    #[validate(custom = "validate_color")]
+    color: Option<String>,
+    #[validate(length(max = "DESCRIPTION_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = cleared_description_schema)]
+    description: Option<String>,
+    position: Option<i32>,
+    /// 0 でトップレベルに戻す
+    parent_id: Option<i32>,
+}
+
+impl UpdateLabel {
+    pub fn new(name: Option<String>, position: Option<i32>) -> Self {
+        Self {
+            name,
+            position,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_details(mut self, color: Option<String>, description: Option<String>) -> Self {
+        self.color = color;
+        self.description = description;
+        self
+    }
+
+    pub fn with_parent(mut self, parent_id: Option<i32>) -> Self {
+        self.parent_id = parent_id;
+        self
+    }
+}
+
+/// `#rrggbb` 形式か空文字
+fn validate_color(color: &str) -> Result<(), ValidationError> {
+    let valid = color.is_empty()
+        || (color.len() == 7
+            && color.starts_with('#')
+            && color[1..].chars().all(|c| c.is_ascii_hexdigit()));
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("color must be #rrggbb")),
+    }
+}
+
+/// 空文字は未設定として保存する
+fn stored_text(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.is_empty())
 }
-*/
 
 #[derive(Clone)]
 pub struct LabelRepositoryForDb {
@@ -37,27 +295,45 @@ impl LabelRepositoryForDb {
 
 #[async_trait]
 impl LabelRepository for LabelRepositoryForDb {
-    async fn create(&self, name: String) -> anyhow::Result<Label> {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
         let optional_label = sqlx::query_as::<_, Label>(
             r#"
                 select * from labels where name = $1
             "#,
         )
-        .bind(name.clone())
+        .bind(payload.name.clone())
         .fetch_optional(&self.pool)
         .await?;
 
         if let Some(label) = optional_label {
             return Err(RepositoryError::Duplicate(label.id).into());
         }
+        if let Some(parent) = payload.parent_id {
+            let parents = sqlx::query_as::<_, (i32, Option<i32>)>(
+                r#"
+                    select id, parent_id from labels
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            check_parent(&parents, None, parent)?;
+        }
         let label = sqlx::query_as::<_, Label>(
             r#"
-                insert into labels (name)
-                values ($1)
+                insert into labels (name, color, description, position, parent_id)
+                values (
+                    $1, $2, $3,
+                    coalesce($4, (select coalesce(max(position), 0) + 1 from labels)),
+                    $5
+                )
                 returning *
             "#,
         )
-        .bind(name.clone())
+        .bind(payload.name)
+        .bind(stored_text(payload.color))
+        .bind(stored_text(payload.description))
+        .bind(payload.position)
+        .bind(payload.parent_id)
         .fetch_one(&self.pool)
         .await?;
 
@@ -67,7 +343,7 @@ impl LabelRepository for LabelRepositoryForDb {
         let labels = sqlx::query_as::<_, Label>(
             r#"
                 select * from labels
-                order by labels.id asc;
+                order by labels.position asc, labels.id asc;
             "#,
         )
         .fetch_all(&self.pool)
@@ -75,154 +351,1614 @@ impl LabelRepository for LabelRepositoryForDb {
 
         Ok(labels)
     }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(name) = &payload.name {
+            let duplicate = sqlx::query_scalar::<_, i32>(
+                r#"
+                    select id from labels where name = $1 and id <> $2
+                "#,
+            )
+            .bind(name)
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?;
+            if let Some(duplicate) = duplicate {
+                return Err(RepositoryError::Duplicate(duplicate).into());
+            }
+        }
+        if let Some(parent) = payload.parent_id.filter(|parent| *parent != 0) {
+            // 同時に付け替えて循環を作られないよう，全ラベルをロックしてから確かめる
+            let parents = sqlx::query_as::<_, (i32, Option<i32>)>(
+                r#"
+                    select id, parent_id from labels for update
+                "#,
+            )
+            .fetch_all(&mut tx)
+            .await?;
+            check_parent(&parents, Some(id), parent)?;
+        }
+        // None は変更しない，空文字や 0 は null にする
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                update labels
+                set
+                    name = coalesce($1, name),
+                    color = case when $2::text is null then color else nullif($2, '') end,
+                    description = case when $3::text is null then description else nullif($3, '') end,
+                    position = coalesce($4, position),
+                    parent_id = case when $5::int4 is null then parent_id else nullif($5, 0) end
+                where id = $6
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(payload.color)
+        .bind(payload.description)
+        .bind(payload.position)
+        .bind(payload.parent_id)
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        tx.commit().await?;
+
+        Ok(label)
+    }
     async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        sqlx::query(
+        let mut tx = self.pool.begin().await?;
+        // 子ラベルが残っている間は削除できない，先に付け替えること
+        let has_children = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (select 1 from labels where parent_id = $1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if has_children {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        // 外部キーは遅延評価でコミット時まで失敗しないため，使用中かを先に確かめる
+        let in_use = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (select 1 from task_labels where label_id = $1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if in_use {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        let deleted = sqlx::query(
             r#"
                 delete from labels where id = $1
             "#,
         )
         .bind(id)
-        .execute(&self.pool)
+        .execute(&mut tx)
         .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?
+        .rows_affected();
+        if deleted == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        tx.commit().await?;
 
         Ok(())
     }
-}
-
-#[cfg(test)]
-#[cfg(feature = "database-test")]
-mod test {
-    use super::*;
-    use dotenv::dotenv;
-    use std::env;
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let stats = sqlx::query_as::<_, LabelStats>(
+            r#"
+                select
+                    labels.id as label_id,
+                    labels.name,
+                    count(distinct tasks.id) filter (where not tasks.completed) as open_tasks,
+                    count(distinct tasks.id) filter (where tasks.completed) as completed_tasks,
+                    max(tl.attached_at) as last_attached_at
+                from
+                    labels
+                    left outer join task_labels as tl
+                        on tl.label_id = labels.id
+                    left outer join tasks
+                        on tasks.id = tl.task_id
+                group by labels.id
+                order by labels.position asc, labels.id asc;
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-    #[tokio::test]
-    async fn crud_scenario() {
-        dotenv().ok();
-        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
-        let pool = PgPool::connect(database_url)
-            .await
-            .expect(&format!("fail connect database, url is [{}]", database_url));
+        Ok(stats)
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        let mut tx = self.pool.begin().await?;
+        let parents = sqlx::query_as::<_, (i32, Option<i32>)>(
+            r#"
+                select id, parent_id from labels for update
+            "#,
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        check_merge(&parents, target, sources)?;
 
-        let repository = LabelRepositoryForDb::new(pool);
-        let label_text = "test_label";
+        let task_ids = sqlx::query_scalar::<_, i32>(
+            r#"
+                select distinct task_id from task_labels where label_id = any($1) order by task_id
+            "#,
+        )
+        .bind(sources)
+        .fetch_all(&mut tx)
+        .await?;
+        // 既に target が付いているタスクには重複して付けない，付けた日時は最後のものを引き継ぐ
+        sqlx::query(
+            r#"
+                insert into task_labels (task_id, label_id, attached_at)
+                select task_id, $1, max(attached_at)
+                from task_labels
+                where label_id = any($2)
+                    and task_id not in (
+                        select task_id from task_labels where label_id = $1
+                    )
+                group by task_id
+            "#,
+        )
+        .bind(target)
+        .bind(sources)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query("delete from task_labels where label_id = any($1)")
+            .bind(sources)
+            .execute(&mut tx)
+            .await?;
+        // 子ラベルは target の下に移す
+        sqlx::query("update labels set parent_id = $1 where parent_id = any($2)")
+            .bind(target)
+            .bind(sources)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("delete from labels where id = any($1)")
+            .bind(sources)
+            .execute(&mut tx)
+            .await?;
 
-        // create
-        let label = repository
-            .create(label_text.to_string())
-            .await
-            .expect("[create] returned Err");
-        assert_eq!(label.name, label_text);
+        let mut tasks = vec![];
+        for id in task_ids {
+            tasks.push(TaskRepositoryForDb::select(&mut tx, id).await?);
+        }
+        tx.commit().await?;
+        Ok(tasks)
+    }
+}
 
-        // all
-        let labels = repository.all().await.expect("[all] returned Err");
-        let label = labels.last().unwrap();
-        assert_eq!(label.name, label_text);
+#[derive(Clone)]
+pub struct LabelRepositoryForSqlite {
+    pool: SqlitePool,
+}
 
-        // delete
-        repository
-            .delete(label.id)
-            .await
-            .expect("[delete] returned Err");
+impl LabelRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
     }
 }
 
-#[cfg(test)]
-pub mod test_utils {
-    use std::{
-        collections::HashMap,
-        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    };
-
-    use super::*;
+#[async_trait]
+impl LabelRepository for LabelRepositoryForSqlite {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let optional_label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where name = ?1
+            "#,
+        )
+        .bind(payload.name.clone())
+        .fetch_optional(&self.pool)
+        .await?;
 
-    impl Label {
-        pub fn new(id: i32, name: String) -> Self {
-            Self { id, name }
+        if let Some(label) = optional_label {
+            return Err(RepositoryError::Duplicate(label.id).into());
         }
-    }
+        if let Some(parent) = payload.parent_id {
+            let parents = sqlx::query_as::<_, (i32, Option<i32>)>(
+                r#"
+                    select id, parent_id from labels
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await?;
+            check_parent(&parents, None, parent)?;
+        }
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                insert into labels (name, color, description, position, parent_id)
+                values (
+                    ?1, ?2, ?3,
+                    coalesce(?4, (select coalesce(max(position), 0) + 1 from labels)),
+                    ?5
+                )
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(stored_text(payload.color))
+        .bind(stored_text(payload.description))
+        .bind(payload.position)
+        .bind(payload.parent_id)
+        .fetch_one(&self.pool)
+        .await?;
 
-    type LabelData = HashMap<i32, Label>;
+        Ok(label)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let labels = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels
+                order by labels.position asc, labels.id asc;
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-    #[derive(Clone)]
-    pub struct LabelRepositoryForMemory {
-        store: Arc<RwLock<LabelData>>,
+        Ok(labels)
     }
-    impl LabelRepositoryForMemory {
-        pub fn new() -> Self {
-            LabelRepositoryForMemory {
-                store: Arc::default(),
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut tx = self.pool.begin().await?;
+        if let Some(name) = &payload.name {
+            let duplicate = sqlx::query_scalar::<_, i32>(
+                r#"
+                    select id from labels where name = ?1 and id <> ?2
+                "#,
+            )
+            .bind(name)
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?;
+            if let Some(duplicate) = duplicate {
+                return Err(RepositoryError::Duplicate(duplicate).into());
             }
         }
-
-        fn write_store_ref(&self) -> RwLockWriteGuard<LabelData> {
-            self.store.write().unwrap()
-        }
-        fn read_store_ref(&self) -> RwLockReadGuard<LabelData> {
-            self.store.read().unwrap()
+        if let Some(parent) = payload.parent_id.filter(|parent| *parent != 0) {
+            let parents = sqlx::query_as::<_, (i32, Option<i32>)>(
+                r#"
+                    select id, parent_id from labels
+                "#,
+            )
+            .fetch_all(&mut tx)
+            .await?;
+            check_parent(&parents, Some(id), parent)?;
         }
-    }
+        let label = sqlx::query_as::<_, Label>(
+            r#"
+                update labels
+                set
+                    name = coalesce(?1, name),
+                    color = case when ?2 is null then color else nullif(?2, '') end,
+                    description = case when ?3 is null then description else nullif(?3, '') end,
+                    position = coalesce(?4, position),
+                    parent_id = case when ?5 is null then parent_id else nullif(?5, 0) end
+                where id = ?6
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(payload.color)
+        .bind(payload.description)
+        .bind(payload.position)
+        .bind(payload.parent_id)
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        tx.commit().await?;
 
-    #[async_trait]
-    impl LabelRepository for LabelRepositoryForMemory {
-        async fn create(&self, name: String) -> anyhow::Result<Label> {
-            let mut store = self.write_store_ref();
-            let id = (store.len() + 1) as i32;
-            let label = Label::new(id, name);
-            store.insert(id, label.clone());
-            Ok(label)
+        Ok(label)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        // 子ラベルが残っている間は削除できない，先に付け替えること
+        let has_children = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (select 1 from labels where parent_id = ?1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if has_children {
+            return Err(RepositoryError::Conflict(id).into());
         }
-        async fn all(&self) -> anyhow::Result<Vec<Label>> {
-            let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().map(|label| label.clone())))
+        // 外部キーは遅延評価でコミット時まで失敗しないため，使用中かを先に確かめる
+        let in_use = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (select 1 from task_labels where label_id = ?1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if in_use {
+            return Err(RepositoryError::Conflict(id).into());
         }
-        async fn delete(&self, id: i32) -> anyhow::Result<()> {
-            let mut store = self.write_store_ref();
-            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
-            Ok(())
+        let deleted = sqlx::query(
+            r#"
+                delete from labels where id = ?1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?
+        .rows_affected();
+        if deleted == 0 {
+            return Err(RepositoryError::NotFound(id).into());
         }
-    }
+        tx.commit().await?;
 
-    #[tokio::test]
-    async fn label_crud_scenario() {
-        let name = "label name".to_string();
-        let id = 1;
-        let expected = Label::new(id, name.clone());
-        let repository = LabelRepositoryForMemory::new();
+        Ok(())
+    }
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let stats = sqlx::query_as::<_, LabelStats>(
+            r#"
+                select
+                    labels.id as label_id,
+                    labels.name,
+                    count(distinct tasks.id) filter (where not tasks.completed) as open_tasks,
+                    count(distinct tasks.id) filter (where tasks.completed) as completed_tasks,
+                    max(tl.attached_at) as last_attached_at
+                from
+                    labels
+                    left outer join task_labels as tl
+                        on tl.label_id = labels.id
+                    left outer join tasks
+                        on tasks.id = tl.task_id
+                group by labels.id
+                order by labels.position asc, labels.id asc;
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
-        // create
-        let label = repository.create(name).await.expect("failed create label");
-        assert_eq!(expected, label);
+        Ok(stats)
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        let mut tx = self.pool.begin().await?;
+        let parents = sqlx::query_as::<_, (i32, Option<i32>)>(
+            r#"
+                select id, parent_id from labels
+            "#,
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        check_merge(&parents, target, sources)?;
 
-        // find
-        /*
-        let label = repository.find(label.id).await.unwrap();
-        assert_eq!(expected, label);
-        */
+        // ラベルの id は JSON 配列として渡す
+        let sources = serde_json::to_string(sources)?;
+        let task_ids = sqlx::query_scalar::<_, i32>(
+            r#"
+                select distinct task_id from task_labels
+                where label_id in (select value from json_each(?1))
+                order by task_id
+            "#,
+        )
+        .bind(&sources)
+        .fetch_all(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+                insert into task_labels (task_id, label_id, attached_at)
+                select task_id, ?1, max(attached_at)
+                from task_labels
+                where label_id in (select value from json_each(?2))
+                    and task_id not in (
+                        select task_id from task_labels where label_id = ?1
+                    )
+                group by task_id
+            "#,
+        )
+        .bind(target)
+        .bind(&sources)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+                delete from task_labels where label_id in (select value from json_each(?1))
+            "#,
+        )
+        .bind(&sources)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+                update labels set parent_id = ?1
+                where parent_id in (select value from json_each(?2))
+            "#,
+        )
+        .bind(target)
+        .bind(&sources)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+                delete from labels where id in (select value from json_each(?1))
+            "#,
+        )
+        .bind(&sources)
+        .execute(&mut tx)
+        .await?;
+
+        let mut tasks = vec![];
+        for id in task_ids {
+            tasks.push(TaskRepositoryForSqlite::select(&mut tx, id).await?);
+        }
+        tx.commit().await?;
+        Ok(tasks)
+    }
+}
+
+/// `--storage=memory` 用，プロセス終了でデータは消える
+#[derive(Debug, Clone, Default)]
+pub struct LabelRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl LabelRepositoryForMemory {
+    pub fn with_store(store: MemoryStore) -> Self {
+        Self { store }
+    }
+
+    /// ラベルが変わったタスクと，親を付け替えたラベルの id を返す
+    fn merge_with(&self, target: i32, sources: &[i32]) -> anyhow::Result<(Vec<i32>, Vec<i32>)> {
+        let mut tables = self.store.write();
+        check_merge(&tables.label_parents(), target, sources)?;
+
+        let moved: Vec<_> = tables
+            .task_labels
+            .iter()
+            .filter(|((_, label_id), _)| sources.contains(label_id))
+            .map(|(key, attached_at)| (*key, *attached_at))
+            .collect();
+        // 既に target が付いていたタスクは付けた日時を変えない
+        let had_target: Vec<i32> = tables
+            .task_labels
+            .keys()
+            .filter(|(_, label_id)| *label_id == target)
+            .map(|(task_id, _)| *task_id)
+            .collect();
+        let mut tasks: Vec<i32> = vec![];
+        for ((task_id, label_id), attached_at) in moved {
+            tables.task_labels.remove(&(task_id, label_id));
+            if !had_target.contains(&task_id) {
+                let current = tables
+                    .task_labels
+                    .entry((task_id, target))
+                    .or_insert(attached_at);
+                *current = (*current).max(attached_at);
+            }
+            if !tasks.contains(&task_id) {
+                tasks.push(task_id);
+            }
+        }
+
+        let children: Vec<i32> = tables
+            .labels
+            .values()
+            .filter(|label| {
+                label
+                    .parent_id
+                    .is_some_and(|parent| sources.contains(&parent))
+            })
+            .filter(|label| !sources.contains(&label.id))
+            .map(|label| label.id)
+            .collect();
+        for id in &children {
+            if let Some(label) = tables.labels.get_mut(id) {
+                label.parent_id = Some(target);
+            }
+        }
+        for id in sources {
+            tables.labels.remove(id);
+        }
+        Ok((tasks, children))
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForMemory {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let mut tables = self.store.write();
+        if let Some(label) = tables
+            .labels
+            .values()
+            .find(|label| label.name == payload.name)
+        {
+            return Err(RepositoryError::Duplicate(label.id).into());
+        }
+        if let Some(parent) = payload.parent_id {
+            check_parent(&tables.label_parents(), None, parent)?;
+        }
+        let position = payload.position.unwrap_or_else(|| {
+            tables
+                .labels
+                .values()
+                .map(|label| label.position)
+                .max()
+                .unwrap_or(0)
+                + 1
+        });
+        let id = tables.next_label_id();
+        let label = Label {
+            id,
+            name: payload.name,
+            color: stored_text(payload.color),
+            description: stored_text(payload.description),
+            position,
+            parent_id: payload.parent_id,
+        };
+        tables.labels.insert(id, label.clone());
+        Ok(label)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        let tables = self.store.read();
+        let mut labels: Vec<Label> = tables.labels.values().cloned().collect();
+        labels.sort_by_key(|label| (label.position, label.id));
+        Ok(labels)
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let mut tables = self.store.write();
+        if let Some(name) = &payload.name {
+            if let Some(label) = tables
+                .labels
+                .values()
+                .find(|label| label.name == *name && label.id != id)
+            {
+                return Err(RepositoryError::Duplicate(label.id).into());
+            }
+        }
+        if let Some(parent) = payload.parent_id.filter(|parent| *parent != 0) {
+            check_parent(&tables.label_parents(), Some(id), parent)?;
+        }
+        let label = tables
+            .labels
+            .get_mut(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        if let Some(name) = payload.name {
+            label.name = name;
+        }
+        if let Some(color) = payload.color {
+            label.color = stored_text(Some(color));
+        }
+        if let Some(description) = payload.description {
+            label.description = stored_text(Some(description));
+        }
+        if let Some(position) = payload.position {
+            label.position = position;
+        }
+        label.parent_id = stored_parent(payload.parent_id, label.parent_id);
+        Ok(label.clone())
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        // DB 実装と同じくタスクから参照中なら削除できない
+        if tables
+            .task_labels
+            .keys()
+            .any(|(_, label_id)| *label_id == id)
+        {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        if tables
+            .labels
+            .values()
+            .any(|label| label.parent_id == Some(id))
+        {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        tables
+            .labels
+            .remove(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(())
+    }
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        let tables = self.store.read();
+        let mut labels: Vec<&Label> = tables.labels.values().collect();
+        labels.sort_by_key(|label| (label.position, label.id));
+        let stats = labels
+            .into_iter()
+            .map(|label| {
+                let mut stats = LabelStats {
+                    label_id: label.id,
+                    name: label.name.clone(),
+                    open_tasks: 0,
+                    completed_tasks: 0,
+                    last_attached_at: None,
+                };
+                for ((task_id, _), attached_at) in tables
+                    .task_labels
+                    .iter()
+                    .filter(|((_, label_id), _)| *label_id == label.id)
+                {
+                    match tables.tasks.get(task_id) {
+                        Some(task) if task.completed => stats.completed_tasks += 1,
+                        Some(_) => stats.open_tasks += 1,
+                        None => {}
+                    }
+                    stats.last_attached_at = stats.last_attached_at.max(*attached_at);
+                }
+                stats
+            })
+            .collect();
+        Ok(stats)
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        let (tasks, _) = self.merge_with(target, sources)?;
+        let tables = self.store.read();
+        Ok(tasks
+            .iter()
+            .filter_map(|id| memory_entity(&tables, *id))
+            .collect())
+    }
+}
+
+/// 変更をログファイルに追記する，読み込みはメモリ上のデータから返す
+#[derive(Debug, Clone)]
+pub struct LabelRepositoryForFile {
+    inner: LabelRepositoryForMemory,
+    store: FileStore,
+}
+
+impl LabelRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            inner: LabelRepositoryForMemory::with_store(store.memory()),
+            store,
+        }
+    }
+}
+
+#[async_trait]
+impl LabelRepository for LabelRepositoryForFile {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        self.store
+            .commit(
+                |_| Touched::none(),
+                || async {
+                    let label = self.inner.create(payload).await?;
+                    let record = Record::put_label(&label);
+                    Ok((label, vec![record]))
+                },
+            )
+            .await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        self.inner.all().await
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        self.store
+            .commit(
+                |_| Touched::labels([id]),
+                || async {
+                    let label = self.inner.update(id, payload).await?;
+                    let record = Record::put_label(&label);
+                    Ok((label, vec![record]))
+                },
+            )
+            .await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.store
+            .commit(
+                |_| Touched::labels([id]),
+                || async {
+                    self.inner.delete(id).await?;
+                    Ok(((), vec![Record::DeleteLabel { id }]))
+                },
+            )
+            .await
+    }
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.stats().await
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        // 統合元の付いたタスクと，統合元とその子のラベルが書き換わる
+        let touched = |tables: &Tables| Touched {
+            tasks: tables
+                .task_labels
+                .keys()
+                .filter(|(_, label_id)| sources.contains(label_id))
+                .map(|(task_id, _)| *task_id)
+                .collect(),
+            labels: tables
+                .labels
+                .values()
+                .filter(|label| {
+                    sources.contains(&label.id)
+                        || label.parent_id.is_some_and(|id| sources.contains(&id))
+                })
+                .map(|label| label.id)
+                .collect(),
+            ..Touched::none()
+        };
+        self.store
+            .commit(touched, || async {
+                let (tasks, children) = self.inner.merge_with(target, sources)?;
+                let tables = self.inner.store.read();
+                let records = tasks
+                    .iter()
+                    .map(|id| Record::put_task(&tables, *id))
+                    .chain(
+                        children
+                            .iter()
+                            .map(|id| Record::put_label(&tables.labels[id])),
+                    )
+                    .chain(sources.iter().map(|id| Record::DeleteLabel { id: *id }))
+                    .collect();
+                let tasks = tasks
+                    .iter()
+                    .filter_map(|id| memory_entity(&tables, *id))
+                    .collect();
+                Ok((tasks, records))
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        let repository = LabelRepositoryForDb::new(pool);
+        test_utils::crud_scenario(&repository).await;
+    }
+
+    #[tokio::test]
+    async fn details_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::details_scenario(&LabelRepositoryForDb::new(pool)).await;
+    }
+
+    #[tokio::test]
+    async fn hierarchy_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::hierarchy_scenario(&LabelRepositoryForDb::new(pool)).await;
+    }
+
+    #[tokio::test]
+    async fn stats_scenario() {
+        use crate::repositories::task::TaskRepositoryForDb;
+
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::stats_scenario(
+            &LabelRepositoryForDb::new(pool.clone()),
+            &TaskRepositoryForDb::new(pool),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn merge_scenario() {
+        use crate::repositories::task::TaskRepositoryForDb;
+
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::merge_scenario(
+            &LabelRepositoryForDb::new(pool.clone()),
+            &TaskRepositoryForDb::new(pool),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn delete_scenario() {
+        use crate::repositories::task::TaskRepositoryForDb;
+
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::delete_scenario(
+            &LabelRepositoryForDb::new(pool.clone()),
+            &TaskRepositoryForDb::new(pool),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn merge_duplicates_scenario() {
+        use crate::repositories::{
+            maintenance::duplicate_labels,
+            task::{CreateTask, TaskRepository, TaskRepositoryForDb},
+        };
+
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let labels = LabelRepositoryForDb::new(pool.clone());
+        let tasks = TaskRepositoryForDb::new(pool);
+
+        let suffix = uuid::Uuid::new_v4();
+        let target = labels
+            .create(CreateLabel::new(format!("merge {}", suffix)))
+            .await
+            .unwrap();
+        let source = labels
+            .create(CreateLabel::new(format!(" MERGE {}", suffix)))
+            .await
+            .unwrap();
+        let duplicates = duplicate_labels(&labels).await.unwrap();
+        assert!(duplicates.contains(&vec![target.clone(), source.clone()]));
+
+        let both = tasks
+            .create(CreateTask::new(
+                "[merge_scenario] both".to_string(),
+                vec![target.id, source.id],
+            ))
+            .await
+            .unwrap();
+        let only_source = tasks
+            .create(CreateTask::new(
+                "[merge_scenario] source".to_string(),
+                vec![source.id],
+            ))
+            .await
+            .unwrap();
+
+        let merged = labels.merge(target.id, &[source.id]).await.unwrap();
+        assert_eq!(merged.len(), 2);
+        for task in [&both, &only_source] {
+            let task = tasks.find(task.id).await.unwrap();
+            assert_eq!(task.labels, vec![target.clone()]);
+            tasks.delete(task.id, None).await.unwrap();
+        }
+        assert!(!labels.all().await.unwrap().contains(&source));
+        assert!(labels.merge(target.id, &[source.id]).await.is_err());
+        labels.delete(target.id).await.unwrap();
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::{task::TaskRepositoryForSqlite, test_utils::sqlite_memory_pool};
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let repository = LabelRepositoryForSqlite::new(sqlite_memory_pool().await);
+        test_utils::crud_scenario(&repository).await;
+    }
+
+    #[tokio::test]
+    async fn details_scenario() {
+        let repository = LabelRepositoryForSqlite::new(sqlite_memory_pool().await);
+        test_utils::details_scenario(&repository).await;
+    }
+
+    #[tokio::test]
+    async fn hierarchy_scenario() {
+        let repository = LabelRepositoryForSqlite::new(sqlite_memory_pool().await);
+        test_utils::hierarchy_scenario(&repository).await;
+    }
+
+    #[tokio::test]
+    async fn stats_scenario() {
+        let pool = sqlite_memory_pool().await;
+        test_utils::stats_scenario(
+            &LabelRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn merge_scenario() {
+        let pool = sqlite_memory_pool().await;
+        test_utils::merge_scenario(
+            &LabelRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn delete_scenario() {
+        let pool = sqlite_memory_pool().await;
+        test_utils::delete_scenario(
+            &LabelRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod memory_test {
+    use super::*;
+    use crate::repositories::task::{CreateTask, TaskRepository, TaskRepositoryForMemory};
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        test_utils::crud_scenario(&LabelRepositoryForMemory::new()).await;
+    }
+
+    #[tokio::test]
+    async fn details_scenario() {
+        test_utils::details_scenario(&LabelRepositoryForMemory::new()).await;
+    }
+
+    #[tokio::test]
+    async fn hierarchy_scenario() {
+        test_utils::hierarchy_scenario(&LabelRepositoryForMemory::new()).await;
+    }
+
+    #[tokio::test]
+    async fn stats_scenario() {
+        let store = MemoryStore::new();
+        test_utils::stats_scenario(
+            &LabelRepositoryForMemory::with_store(store.clone()),
+            &TaskRepositoryForMemory::with_store(store),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn merge_scenario() {
+        let store = MemoryStore::new();
+        test_utils::merge_scenario(
+            &LabelRepositoryForMemory::with_store(store.clone()),
+            &TaskRepositoryForMemory::with_store(store),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn delete_scenario() {
+        let store = MemoryStore::new();
+        test_utils::delete_scenario(
+            &LabelRepositoryForMemory::with_store(store.clone()),
+            &TaskRepositoryForMemory::with_store(store),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn create_duplicate_name_fails() {
+        let repository = LabelRepositoryForMemory::new();
+        let label = repository
+            .create(CreateLabel::new("label".to_string()))
+            .await
+            .unwrap();
+        let res = repository
+            .create(CreateLabel::new("label".to_string()))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == label.id
+        ));
+    }
+
+    #[tokio::test]
+    async fn delete_label_in_use_fails() {
+        let store = MemoryStore::new();
+        let repository = LabelRepositoryForMemory::with_store(store.clone());
+        let label = repository
+            .create(CreateLabel::new("label".to_string()))
+            .await
+            .unwrap();
+        TaskRepositoryForMemory::with_store(store)
+            .create(CreateTask::new("text".to_string(), vec![label.id]))
+            .await
+            .unwrap();
+
+        let res = repository.delete(label.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(id)) if *id == label.id
+        ));
+        assert_eq!(repository.all().await.unwrap(), vec![label]);
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use super::*;
+    use crate::repositories::{
+        file::test_utils::temp_data_file,
+        task::{CreateTask, TaskRepository, TaskRepositoryForFile},
+    };
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let store = FileStore::open(temp_data_file()).unwrap();
+        test_utils::crud_scenario(&LabelRepositoryForFile::new(store)).await;
+    }
+
+    #[tokio::test]
+    async fn details_scenario() {
+        let path = temp_data_file();
+        let label = {
+            let repository = LabelRepositoryForFile::new(FileStore::open(&path).unwrap());
+            test_utils::details_scenario(&repository).await;
+
+            let label = repository
+                .create(
+                    CreateLabel::new("label".to_string())
+                        .with_details(Some("#00ff00".to_string()), Some("desc".to_string())),
+                )
+                .await
+                .unwrap();
+            repository
+                .update(label.id, UpdateLabel::new(None, Some(-1)))
+                .await
+                .unwrap()
+        };
+
+        // 色や並び順も再読み込み後に残る
+        let reopened = LabelRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert_eq!(reopened.all().await.unwrap(), vec![label]);
+    }
+
+    #[tokio::test]
+    async fn hierarchy_scenario() {
+        let path = temp_data_file();
+        let child = {
+            let repository = LabelRepositoryForFile::new(FileStore::open(&path).unwrap());
+            test_utils::hierarchy_scenario(&repository).await;
+
+            let parent = repository
+                .create(CreateLabel::new("parent".to_string()))
+                .await
+                .unwrap();
+            repository
+                .create(CreateLabel::new("child".to_string()).with_parent(Some(parent.id)))
+                .await
+                .unwrap()
+        };
+
+        let reopened = LabelRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert!(reopened.all().await.unwrap().contains(&child));
+    }
+
+    #[tokio::test]
+    async fn stats_scenario() {
+        let path = temp_data_file();
+        let stats = {
+            let store = FileStore::open(&path).unwrap();
+            let labels = LabelRepositoryForFile::new(store.clone());
+            let tasks = TaskRepositoryForFile::new(store);
+            test_utils::stats_scenario(&labels, &tasks).await;
+
+            let label = labels
+                .create(CreateLabel::new("label".to_string()))
+                .await
+                .unwrap();
+            tasks
+                .create(CreateTask::new("task".to_string(), vec![label.id]))
+                .await
+                .unwrap();
+            labels.stats().await.unwrap()
+        };
+
+        // 付けた日時もログから復元される
+        let reopened = LabelRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert!(stats[0].last_attached_at.is_some());
+        assert_eq!(reopened.stats().await.unwrap(), stats);
+    }
+
+    #[tokio::test]
+    async fn delete_scenario() {
+        let store = FileStore::open(temp_data_file()).unwrap();
+        test_utils::delete_scenario(
+            &LabelRepositoryForFile::new(store.clone()),
+            &TaskRepositoryForFile::new(store),
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn merge_scenario() {
+        let path = temp_data_file();
+        let (target, child, task) = {
+            let store = FileStore::open(&path).unwrap();
+            let labels = LabelRepositoryForFile::new(store.clone());
+            let tasks = TaskRepositoryForFile::new(store);
+            test_utils::merge_scenario(&labels, &tasks).await;
+
+            let target = labels
+                .create(CreateLabel::new("target".to_string()))
+                .await
+                .unwrap();
+            let source = labels
+                .create(CreateLabel::new("source".to_string()))
+                .await
+                .unwrap();
+            let child = labels
+                .create(CreateLabel::new("child".to_string()).with_parent(Some(source.id)))
+                .await
+                .unwrap();
+            let task = tasks
+                .create(CreateTask::new("task".to_string(), vec![source.id]))
+                .await
+                .unwrap();
+            assert_eq!(
+                labels.merge(target.id, &[source.id]).await.unwrap(),
+                vec![tasks.find(task.id).await.unwrap()]
+            );
+            (target, child, task)
+        };
+
+        // 付け替えたタスクと子ラベル，削除したラベルがログから復元される
+        let store = FileStore::open(&path).unwrap();
+        let labels = LabelRepositoryForFile::new(store.clone())
+            .all()
+            .await
+            .unwrap();
+        assert_eq!(
+            labels,
+            vec![
+                target.clone(),
+                Label {
+                    parent_id: Some(target.id),
+                    ..child
+                }
+            ]
+        );
+        let task = TaskRepositoryForFile::new(store)
+            .find(task.id)
+            .await
+            .unwrap();
+        assert_eq!(task.labels, vec![target]);
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::task::{CreateTask, TaskRepository, UpdateTask};
+
+    impl LabelRepositoryForMemory {
+        pub fn new() -> Self {
+            Self::default()
+        }
+    }
+
+    /// 各 DB 実装で共通の CRUD シナリオ
+    pub async fn crud_scenario<T: LabelRepository>(repository: &T) {
+        let label_text = "test_label";
+
+        // create
+        let label = repository
+            .create(CreateLabel::new(label_text.to_string()))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(label.name, label_text);
+
+        // all
+        let labels = repository.all().await.expect("[all] returned Err");
+        let label = labels.last().unwrap();
+        assert_eq!(label.name, label_text);
+
+        // delete
+        repository
+            .delete(label.id)
+            .await
+            .expect("[delete] returned Err");
+    }
+
+    /// 色，説明，並び順の更新
+    pub async fn details_scenario<T: LabelRepository>(repository: &T) {
+        let suffix = uuid::Uuid::new_v4();
+        let first = repository
+            .create(
+                CreateLabel::new(format!("first {}", suffix))
+                    .with_details(Some("#FF8800".to_string()), Some("desc".to_string())),
+            )
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(first.color.as_deref(), Some("#FF8800"));
+        assert_eq!(first.description.as_deref(), Some("desc"));
+        let second = repository
+            .create(
+                CreateLabel::new(format!("second {}", suffix))
+                    .with_details(Some("".to_string()), None),
+            )
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(second.color, None);
+        assert!(second.position > first.position);
+
+        // 並び替え
+        let second = repository
+            .update(second.id, UpdateLabel::new(None, Some(first.position - 1)))
+            .await
+            .expect("[update] returned Err");
+        let order: Vec<i32> = repository
+            .all()
+            .await
+            .expect("[all] returned Err")
+            .iter()
+            .map(|label| label.id)
+            .filter(|id| [first.id, second.id].contains(id))
+            .collect();
+        assert_eq!(order, vec![second.id, first.id]);
+
+        // 空文字で消す，None は変更しない
+        let first = repository
+            .update(
+                first.id,
+                UpdateLabel::new(None, None).with_details(Some("".to_string()), None),
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(first.color, None);
+        assert_eq!(first.description.as_deref(), Some("desc"));
+
+        let res = repository
+            .update(first.id, UpdateLabel::new(Some(second.name.clone()), None))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Duplicate(id)) if *id == second.id
+        ));
+        let res = repository
+            .update(
+                i32::MAX,
+                UpdateLabel::new(Some("missing".to_string()), None),
+            )
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+
+        repository
+            .delete(first.id)
+            .await
+            .expect("[delete] returned Err");
+        repository
+            .delete(second.id)
+            .await
+            .expect("[delete] returned Err");
+    }
+
+    /// 親子関係の作成，循環の検出，階層の組み立て
+    pub async fn hierarchy_scenario<T: LabelRepository>(repository: &T) {
+        let suffix = uuid::Uuid::new_v4();
+        let create = |name: &str, parent_id: Option<i32>| {
+            repository
+                .create(CreateLabel::new(format!("{} {}", name, suffix)).with_parent(parent_id))
+        };
+        let work = create("work", None).await.expect("[create] returned Err");
+        let client = create("work/client", Some(work.id))
+            .await
+            .expect("[create] returned Err");
+        let billing = create("work/client/billing", Some(client.id))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(billing.parent_id, Some(client.id));
+
+        // 存在しない親
+        let res = create("orphan", Some(i32::MAX)).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == i32::MAX
+        ));
+
+        // 自分自身や子孫を親にはできない
+        for parent in [work.id, billing.id] {
+            let res = repository
+                .update(work.id, UpdateLabel::default().with_parent(Some(parent)))
+                .await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+        }
+
+        let labels = repository.all().await.expect("[all] returned Err");
+        assert_eq!(
+            descendants(&labels, work.id),
+            vec![work.id, client.id, billing.id]
+        );
+        assert_eq!(descendants(&labels, billing.id), vec![billing.id]);
+        let tree = tree(labels);
+        let node = tree.iter().find(|node| node.label.id == work.id).unwrap();
+        assert_eq!(node.children.len(), 1);
+        assert_eq!(node.children[0].label, client);
+        assert_eq!(node.children[0].children[0].label, billing);
+        assert!(node.children[0].children[0].children.is_empty());
+
+        // 子がいる間は削除できない
+        let res = repository.delete(client.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(id)) if *id == client.id
+        ));
+
+        // 0 でトップレベルに戻す
+        let billing = repository
+            .update(billing.id, UpdateLabel::default().with_parent(Some(0)))
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(billing.parent_id, None);
+        let client = repository
+            .update(
+                client.id,
+                UpdateLabel::default().with_parent(Some(billing.id)),
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(client.parent_id, Some(billing.id));
+
+        for id in [client.id, billing.id, work.id] {
+            repository.delete(id).await.expect("[delete] returned Err");
+        }
+    }
+
+    /// 未完了と完了のタスク数，最後に付けた日時
+    pub async fn stats_scenario<L: LabelRepository, T: TaskRepository>(repository: &L, tasks: &T) {
+        let suffix = uuid::Uuid::new_v4();
+        let mut labels = vec![];
+        for name in ["used", "done", "unused"] {
+            let label = repository
+                .create(CreateLabel::new(format!("{} {}", name, suffix)))
+                .await
+                .expect("[create] returned Err");
+            labels.push(label.id);
+        }
+        let (used, done, unused) = (labels[0], labels[1], labels[2]);
+        let open = tasks
+            .create(CreateTask::new(
+                "[stats_scenario] open".to_string(),
+                vec![used],
+            ))
+            .await
+            .expect("[create] returned Err");
+        let completed = tasks
+            .create(CreateTask::new(
+                "[stats_scenario] completed".to_string(),
+                vec![used, done],
+            ))
+            .await
+            .expect("[create] returned Err");
+        tasks
+            .update(completed.id, UpdateTask::new(None, Some(true), None), None)
+            .await
+            .expect("[update] returned Err");
+
+        let stats_of = |stats: &[LabelStats], id: i32| {
+            stats
+                .iter()
+                .find(|stats| stats.label_id == id)
+                .cloned()
+                .unwrap()
+        };
+        let stats = repository.stats().await.expect("[stats] returned Err");
+        let used_stats = stats_of(&stats, used);
+        assert_eq!((used_stats.open_tasks, used_stats.completed_tasks), (1, 1));
+        assert!(used_stats.last_attached_at.is_some());
+        let done_stats = stats_of(&stats, done);
+        assert_eq!((done_stats.open_tasks, done_stats.completed_tasks), (0, 1));
+        let unused_stats = stats_of(&stats, unused);
+        assert_eq!(
+            (unused_stats.open_tasks, unused_stats.completed_tasks),
+            (0, 0)
+        );
+        assert_eq!(unused_stats.last_attached_at, None);
+        let ids: Vec<i32> = stats.iter().map(|stats| stats.label_id).collect();
+        let all: Vec<i32> = repository
+            .all()
+            .await
+            .expect("[all] returned Err")
+            .iter()
+            .map(|label| label.id)
+            .collect();
+        assert_eq!(ids, all);
+
+        // 付け直さなかったラベルは付けた日時が変わらない
+        tasks
+            .update(
+                open.id,
+                UpdateTask::new(None, None, Some(vec![used, unused])),
+                None,
+            )
+            .await
+            .expect("[update] returned Err");
+        let stats = repository.stats().await.expect("[stats] returned Err");
+        assert_eq!(stats_of(&stats, used), used_stats);
+        assert!(stats_of(&stats, unused).last_attached_at.is_some());
+
+        for task in [open.id, completed.id] {
+            tasks
+                .delete(task, None)
+                .await
+                .expect("[delete] returned Err");
+        }
+        for label in labels {
+            repository
+                .delete(label)
+                .await
+                .expect("[delete] returned Err");
+        }
+    }
+
+    /// 重複なく付け替えて元のラベルを削除する，子ラベルは target の下に移る
+    pub async fn merge_scenario<L: LabelRepository, T: TaskRepository>(repository: &L, tasks: &T) {
+        let suffix = uuid::Uuid::new_v4();
+        let create = |name: &str, parent_id: Option<i32>| {
+            repository
+                .create(CreateLabel::new(format!("{} {}", name, suffix)).with_parent(parent_id))
+        };
+        let target = create("bug", None).await.expect("[create] returned Err");
+        let upper = create("Bug", None).await.expect("[create] returned Err");
+        let plural = create("bugs", None).await.expect("[create] returned Err");
+        let child = create("bug/ui", Some(upper.id))
+            .await
+            .expect("[create] returned Err");
+
+        let mut task_ids = vec![];
+        for labels in [
+            vec![target.id, upper.id],
+            vec![upper.id, plural.id],
+            vec![plural.id],
+            vec![target.id],
+        ] {
+            let task = tasks
+                .create(CreateTask::new("[merge_scenario] task".to_string(), labels))
+                .await
+                .expect("[create] returned Err");
+            task_ids.push(task.id);
+        }
+
+        let merged = repository
+            .merge(target.id, &[upper.id, plural.id])
+            .await
+            .expect("[merge] returned Err");
+        assert_eq!(
+            merged.iter().map(|task| task.id).collect::<Vec<_>>(),
+            task_ids[..3]
+        );
+        for id in &task_ids {
+            let task = tasks.find(*id).await.expect("[find] returned Err");
+            assert_eq!(task.labels, vec![target.clone()]);
+            if let Some(merged) = merged.iter().find(|merged| merged.id == *id) {
+                assert_eq!(*merged, task);
+            }
+        }
+        let labels = repository.all().await.expect("[all] returned Err");
+        assert!(!labels
+            .iter()
+            .any(|label| [upper.id, plural.id].contains(&label.id)));
+        let child = labels
+            .into_iter()
+            .find(|label| label.id == child.id)
+            .unwrap();
+        assert_eq!(child.parent_id, Some(target.id));
+
+        // 自分自身や子孫にはまとめられない
+        for (target, sources) in [(target.id, vec![target.id]), (child.id, vec![target.id])] {
+            let res = repository.merge(target, &sources).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::Conflict(_))
+            ));
+        }
+        for (target, sources) in [(target.id, vec![i32::MAX]), (i32::MAX, vec![child.id])] {
+            let res = repository.merge(target, &sources).await;
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(id)) if *id == i32::MAX
+            ));
+        }
+
+        for id in task_ids {
+            tasks.delete(id, None).await.expect("[delete] returned Err");
+        }
+        for id in [child.id, target.id] {
+            repository.delete(id).await.expect("[delete] returned Err");
+        }
+    }
+
+    /// 存在しないラベルや使用中のラベルは削除できない
+    pub async fn delete_scenario<L: LabelRepository, T: TaskRepository>(repository: &L, tasks: &T) {
+        let res = repository.delete(i32::MAX).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == i32::MAX
+        ));
+
+        let label = repository
+            .create(CreateLabel::new(format!(
+                "[delete_scenario] {}",
+                uuid::Uuid::new_v4()
+            )))
+            .await
+            .expect("[create] returned Err");
+        let task = tasks
+            .create(CreateTask::new(
+                "[delete_scenario] task".to_string(),
+                vec![label.id],
+            ))
+            .await
+            .expect("[create] returned Err");
+        let res = repository.delete(label.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(id)) if *id == label.id
+        ));
+
+        // タスクを消せば削除できる
+        tasks
+            .delete(task.id, None)
+            .await
+            .expect("[delete] returned Err");
+        repository
+            .delete(label.id)
+            .await
+            .expect("[delete] returned Err");
+    }
+
+    #[tokio::test]
+    async fn label_crud_scenario() {
+        let name = "label name".to_string();
+        let id = 1;
+        let expected = Label {
+            position: 1,
+            ..Label::new(id, name.clone())
+        };
+        let repository = LabelRepositoryForMemory::new();
+
+        // create
+        let label = repository
+            .create(CreateLabel::new(name))
+            .await
+            .expect("failed create label");
+        assert_eq!(expected, label);
 
         // all
         let labels = repository.all().await.expect("failed get all label");
-        assert_eq!(vec![expected], labels);
+        assert_eq!(vec![expected.clone()], labels);
 
         // update
-        /*
         let name = "update label name".to_string();
         let label = repository
-            .update(
-                1,
-                UpdateLabel {
-                    id,
-                    name: name.clone(),
-                },
-            )
+            .update(id, UpdateLabel::new(Some(name.clone()), None))
             .await
             .expect("failed update label.");
-        assert_eq!(Label { id, name }, label);
-        */
+        assert_eq!(Label { name, ..expected }, label);
 
         // delete
         let res = repository.delete(id).await;
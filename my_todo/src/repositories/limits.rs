@@ -0,0 +1,37 @@
+//! 入力する文字列の長さの上限と下限
+//!
+//! validator の検証と OpenAPI のスキーマの両方をここの定数から作り，食い違わないようにする
+
+use utoipa::openapi::{ObjectBuilder, SchemaType};
+
+/// タスクの本文，ラベルとプロジェクトの名前
+pub const TEXT_MIN_LEN: u64 = 1;
+pub const TEXT_MAX_LEN: u64 = 100;
+/// ラベルの説明
+pub const DESCRIPTION_MAX_LEN: u64 = 500;
+/// webhook の署名用のシークレット
+pub const SECRET_MIN_LEN: u64 = 16;
+pub const SECRET_MAX_LEN: u64 = 256;
+
+fn string_schema(min: Option<u64>, max: Option<u64>) -> ObjectBuilder {
+    ObjectBuilder::new()
+        .schema_type(SchemaType::String)
+        .min_length(min.map(|min| min as usize))
+        .max_length(max.map(|max| max as usize))
+}
+
+pub fn text_schema() -> ObjectBuilder {
+    string_schema(Some(TEXT_MIN_LEN), Some(TEXT_MAX_LEN))
+}
+
+pub fn optional_text_schema() -> ObjectBuilder {
+    text_schema().nullable(true)
+}
+
+pub fn description_schema() -> ObjectBuilder {
+    string_schema(None, Some(DESCRIPTION_MAX_LEN)).nullable(true)
+}
+
+pub fn secret_schema() -> ObjectBuilder {
+    string_schema(Some(SECRET_MIN_LEN), Some(SECRET_MAX_LEN))
+}
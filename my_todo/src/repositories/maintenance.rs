@@ -0,0 +1,779 @@
+//! 管理コマンドから使う，複数のテーブルにまたがる操作
+
+use std::collections::HashMap;
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, FromRow, PgPool, SqlitePool};
+
+use super::{
+    label::{CreateLabel, Label, LabelRepository, UpdateLabel},
+    project::{CreateProject, Project, ProjectRepository},
+    task::{TaskRecord, TaskRepository},
+};
+
+/// export の形式の版，項目を増やしたら上げる
+pub const DUMP_VERSION: u32 = 3;
+
+/// export / import の形式，id は export 元のもの
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dump {
+    pub version: u32,
+    pub projects: Vec<Project>,
+    pub labels: Vec<Label>,
+    pub tasks: Vec<TaskRecord>,
+}
+
+impl Dump {
+    pub fn new(projects: Vec<Project>, labels: Vec<Label>, tasks: Vec<TaskRecord>) -> Self {
+        Self {
+            version: DUMP_VERSION,
+            projects,
+            labels,
+            tasks,
+        }
+    }
+
+    /// 版が違うものは項目が足りないので取り込まない，version が無いものは 1
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        #[derive(Deserialize)]
+        struct Header {
+            version: Option<u32>,
+        }
+        let version = serde_json::from_str::<Header>(json)?.version.unwrap_or(1);
+        if version != DUMP_VERSION {
+            anyhow::bail!(
+                "unsupported dump version {}, expected {}",
+                version,
+                DUMP_VERSION
+            );
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+/// task_labels の 1 行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow)]
+pub struct TaskLabelRow {
+    pub id: i32,
+    pub task_id: i32,
+    pub label_id: i32,
+}
+
+/// リポジトリを通すと見えない行を直接扱う，管理コマンド用の操作
+#[async_trait]
+pub trait MaintenanceRepository: Clone + Send + Sync + 'static {
+    /// タスクかラベルが存在しない task_labels の行
+    async fn orphaned_task_labels(&self) -> anyhow::Result<Vec<TaskLabelRow>>;
+    async fn delete_task_labels(&self, ids: &[i32]) -> anyhow::Result<u64>;
+    /// 削除した行が使っていた領域を回収する，タスクの削除は物理削除なので論理削除の行は無い
+    async fn vacuum(&self) -> anyhow::Result<()>;
+}
+
+/// 全てのプロジェクト，ラベル，タスクを id の昇順で
+pub async fn export<P, L, T>(projects: &P, labels: &L, tasks: &T) -> anyhow::Result<Dump>
+where
+    P: ProjectRepository,
+    L: LabelRepository,
+    T: TaskRepository,
+{
+    let mut projects = projects.all().await?;
+    projects.sort_by_key(|project| project.id);
+    let mut labels = labels.all().await?;
+    labels.sort_by_key(|label| label.id);
+    Ok(Dump::new(projects, labels, tasks.records().await?))
+}
+
+/// id を振り直して取り込む，同名のラベルは既存のものを使う
+/// タスクは完了や version も含めてそのまま登録し，完了済みの繰り返しタスクでも次のタスクは作らない
+/// 操作ごとに確定するので途中で失敗するとそれまでの分は残る，dump の中で参照が欠けていれば何も書かずに弾く
+pub async fn import<P, L, T>(projects: &P, labels: &L, tasks: &T, dump: Dump) -> anyhow::Result<()>
+where
+    P: ProjectRepository,
+    L: LabelRepository,
+    T: TaskRepository,
+{
+    let dump = sorted(dump);
+    check(&dump)?;
+    let mut ids = IdMap::default();
+    for project in &dump.projects {
+        let created = projects
+            .create(CreateProject::new(project.name.clone()))
+            .await?;
+        ids.projects.insert(project.id, created.id);
+    }
+
+    let mut existing = labels
+        .all()
+        .await?
+        .into_iter()
+        .map(|label| (label.name, label.id))
+        .collect::<HashMap<_, _>>();
+    let mut created = vec![];
+    for label in &dump.labels {
+        let id = match existing.get(&label.name) {
+            Some(id) => *id,
+            None => {
+                let payload = CreateLabel::new(label.name.clone())
+                    .with_details(label.color.clone(), label.description.clone())
+                    .with_position(Some(label.position));
+                let id = labels.create(payload).await?.id;
+                existing.insert(label.name.clone(), id);
+                created.push(label);
+                id
+            }
+        };
+        ids.labels.insert(label.id, id);
+    }
+    // 親が子より新しい場合もあるので，全て登録してから親を付ける
+    for label in created {
+        if let Some(parent_id) = label.parent_id {
+            let payload = UpdateLabel::new(None, None).with_parent(Some(ids.label(parent_id)?));
+            labels.update(ids.label(label.id)?, payload).await?;
+        }
+    }
+
+    for task in dump.tasks {
+        let id = task.id;
+        let record = TaskRecord {
+            labels: task
+                .labels
+                .iter()
+                .map(|id| ids.label(*id))
+                .collect::<anyhow::Result<_>>()?,
+            attached_at: task
+                .attached_at
+                .iter()
+                .map(|(id, at)| Ok((ids.label(*id)?, *at)))
+                .collect::<anyhow::Result<_>>()?,
+            series_id: ids.task(task.series_id)?,
+            project_id: ids.project(task.project_id)?,
+            ..task
+        };
+        let created = tasks.restore(record).await?;
+        ids.tasks.insert(id, created.id);
+    }
+    Ok(())
+}
+
+/// 大文字小文字と前後の空白を無視して同じ名前のラベルを，id の昇順でまとめて返す
+pub async fn duplicate_labels<L: LabelRepository>(labels: &L) -> anyhow::Result<Vec<Vec<Label>>> {
+    let mut labels = labels.all().await?;
+    labels.sort_by_cached_key(|label| (name_key(label), label.id));
+    Ok(group_by_name(labels)
+        .into_iter()
+        .filter(|group| group.len() > 1)
+        .collect())
+}
+
+/// export 時の id から取り込み後の id へ
+#[derive(Default)]
+struct IdMap {
+    projects: HashMap<i32, i32>,
+    labels: HashMap<i32, i32>,
+    tasks: HashMap<i32, i32>,
+}
+
+impl IdMap {
+    fn get(ids: &HashMap<i32, i32>, kind: &str, id: i32) -> anyhow::Result<i32> {
+        ids.get(&id)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("{} {} is not in the dump", kind, id))
+    }
+
+    fn project(&self, id: Option<i32>) -> anyhow::Result<Option<i32>> {
+        id.map(|id| Self::get(&self.projects, "project", id))
+            .transpose()
+    }
+
+    fn label(&self, id: i32) -> anyhow::Result<i32> {
+        Self::get(&self.labels, "label", id)
+    }
+
+    /// 繰り返しの最初のタスクは先に取り込まれている
+    fn task(&self, id: Option<i32>) -> anyhow::Result<Option<i32>> {
+        id.map(|id| Self::get(&self.tasks, "task", id)).transpose()
+    }
+}
+
+/// 元の並び順と繰り返しの参照を保つため古いものから取り込む
+fn sorted(mut dump: Dump) -> Dump {
+    dump.projects.sort_by_key(|project| project.id);
+    dump.labels.sort_by_key(|label| label.id);
+    dump.tasks.sort_by_key(|task| task.id);
+    dump
+}
+
+/// 取り込みと同じ順に，id をそのまま写して参照を辿る
+fn check(dump: &Dump) -> anyhow::Result<()> {
+    let mut ids = IdMap::default();
+    ids.projects
+        .extend(dump.projects.iter().map(|project| (project.id, project.id)));
+    ids.labels
+        .extend(dump.labels.iter().map(|label| (label.id, label.id)));
+    for parent_id in dump.labels.iter().filter_map(|label| label.parent_id) {
+        ids.label(parent_id)?;
+    }
+    for task in &dump.tasks {
+        ids.project(task.project_id)?;
+        ids.task(task.series_id)?;
+        for label_id in &task.labels {
+            ids.label(*label_id)?;
+        }
+        ids.tasks.insert(task.id, task.id);
+    }
+    Ok(())
+}
+
+fn name_key(label: &Label) -> String {
+    label.name.trim().to_lowercase()
+}
+
+fn group_by_name(labels: Vec<Label>) -> Vec<Vec<Label>> {
+    let mut groups: Vec<Vec<Label>> = vec![];
+    for label in labels {
+        match groups.last_mut() {
+            Some(group) if name_key(&group[0]) == name_key(&label) => group.push(label),
+            _ => groups.push(vec![label]),
+        }
+    }
+    groups
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceRepositoryForDb {
+    pool: PgPool,
+}
+
+impl MaintenanceRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl MaintenanceRepository for MaintenanceRepositoryForDb {
+    async fn orphaned_task_labels(&self) -> anyhow::Result<Vec<TaskLabelRow>> {
+        let rows = sqlx::query_as::<_, TaskLabelRow>(
+            r#"
+                select tl.id, tl.task_id, tl.label_id
+                from
+                    task_labels as tl
+                    left outer join tasks
+                        on tl.task_id = tasks.id
+                    left outer join labels
+                        on tl.label_id = labels.id
+                where tasks.id is null or labels.id is null
+                order by tl.id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    async fn delete_task_labels(&self, ids: &[i32]) -> anyhow::Result<u64> {
+        let res = sqlx::query("delete from task_labels where id = any($1)")
+            .bind(ids)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn vacuum(&self) -> anyhow::Result<()> {
+        // VACUUM はトランザクションの中では実行できないので，プリペアドステートメントにしない
+        self.pool
+            .execute("vacuum analyze tasks, task_labels, labels, projects, reminder_deliveries")
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MaintenanceRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl MaintenanceRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl MaintenanceRepository for MaintenanceRepositoryForSqlite {
+    async fn orphaned_task_labels(&self) -> anyhow::Result<Vec<TaskLabelRow>> {
+        let rows = sqlx::query_as::<_, TaskLabelRow>(
+            r#"
+                select tl.id, tl.task_id, tl.label_id
+                from
+                    task_labels as tl
+                    left outer join tasks
+                        on tl.task_id = tasks.id
+                    left outer join labels
+                        on tl.label_id = labels.id
+                where tasks.id is null or labels.id is null
+                order by tl.id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows)
+    }
+
+    async fn delete_task_labels(&self, ids: &[i32]) -> anyhow::Result<u64> {
+        let res = sqlx::query(
+            r#"
+                delete from task_labels where id in (select value from json_each(?1))
+            "#,
+        )
+        .bind(serde_json::to_string(ids)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn vacuum(&self) -> anyhow::Result<()> {
+        self.pool.execute("vacuum").await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::{LabelRepository, LabelRepositoryForDb},
+        project::{ProjectRepository, ProjectRepositoryForDb},
+        task::{TaskRepository, TaskRepositoryForDb},
+    };
+    use dotenv::dotenv;
+    use std::env;
+
+    async fn pool() -> PgPool {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url))
+    }
+
+    #[tokio::test]
+    async fn import_scenario() {
+        let pool = pool().await;
+        let projects = ProjectRepositoryForDb::new(pool.clone());
+        let labels = LabelRepositoryForDb::new(pool.clone());
+        let tasks = TaskRepositoryForDb::new(pool);
+        let (dump, imported) = test_utils::import_scenario(&projects, &labels, &tasks).await;
+        for task in dump.tasks.iter().chain(imported.tasks.iter()) {
+            tasks.delete(task.id, None).await.unwrap();
+        }
+        for project in dump.projects.iter().chain(imported.projects.iter()) {
+            projects.delete(project.id).await.unwrap();
+        }
+        // 子から消す
+        for label in &dump.labels {
+            labels.delete(label.id).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn orphaned_task_labels_scenario() {
+        let pool = pool().await;
+
+        // 外部キー制約を無効にして孤立した行を作る (スーパーユーザーが必要)
+        let mut conn = pool.acquire().await.unwrap();
+        sqlx::query("set session_replication_role = replica")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"
+                insert into task_labels (task_id, label_id)
+                values (-1, -1)
+                returning id
+            "#,
+        )
+        .fetch_one(&mut conn)
+        .await
+        .unwrap();
+        sqlx::query("set session_replication_role = origin")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        drop(conn);
+
+        let repository = MaintenanceRepositoryForDb::new(pool);
+        let orphans = repository.orphaned_task_labels().await.unwrap();
+        assert!(orphans.contains(&TaskLabelRow {
+            id,
+            task_id: -1,
+            label_id: -1,
+        }));
+        assert_eq!(repository.delete_task_labels(&[id]).await.unwrap(), 1);
+        assert!(!repository
+            .orphaned_task_labels()
+            .await
+            .unwrap()
+            .iter()
+            .any(|row| row.id == id));
+    }
+
+    #[tokio::test]
+    async fn vacuum_scenario() {
+        let repository = MaintenanceRepositoryForDb::new(pool().await);
+        repository.vacuum().await.expect("[vacuum] returned Err");
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepositoryForSqlite},
+        project::ProjectRepositoryForSqlite,
+        task::{CreateTask, TaskRepositoryForSqlite},
+        test_utils::sqlite_memory_pool,
+    };
+    use chrono::{DateTime, Utc};
+
+    async fn export_all(pool: &SqlitePool) -> Dump {
+        export(
+            &ProjectRepositoryForSqlite::new(pool.clone()),
+            &LabelRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool.clone()),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn reject_old_dump() {
+        let res = Dump::from_json(r#"{"labels": [], "tasks": []}"#);
+        assert!(res.unwrap_err().to_string().contains("version 1"));
+    }
+
+    #[tokio::test]
+    async fn import_scenario() {
+        let pool = sqlite_memory_pool().await;
+        test_utils::import_scenario(
+            &ProjectRepositoryForSqlite::new(pool.clone()),
+            &LabelRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool),
+        )
+        .await;
+    }
+
+    /// 空の DB へ取り込んで export し直すと id まで一致する
+    #[tokio::test]
+    async fn export_import_export() {
+        let source = sqlite_memory_pool().await;
+        test_utils::import_scenario(
+            &ProjectRepositoryForSqlite::new(source.clone()),
+            &LabelRepositoryForSqlite::new(source.clone()),
+            &TaskRepositoryForSqlite::new(source.clone()),
+        )
+        .await;
+        // 付けた日時は古いものと分からないものを残す
+        let attached_at = "2024-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        sqlx::query(
+            r#"
+                update task_labels set attached_at = case
+                    when id = (select min(id) from task_labels) then ?1
+                    else null
+                end
+            "#,
+        )
+        .bind(attached_at)
+        .execute(&source)
+        .await
+        .unwrap();
+        let dump = export_all(&source).await;
+        assert!(dump
+            .tasks
+            .iter()
+            .flat_map(|task| &task.attached_at)
+            .any(|(_, at)| *at == attached_at));
+        assert!(dump
+            .tasks
+            .iter()
+            .any(|task| !task.labels.is_empty() && task.attached_at.is_empty()));
+
+        let restored = sqlite_memory_pool().await;
+        import(
+            &ProjectRepositoryForSqlite::new(restored.clone()),
+            &LabelRepositoryForSqlite::new(restored.clone()),
+            &TaskRepositoryForSqlite::new(restored.clone()),
+            dump.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(export_all(&restored).await, dump);
+    }
+
+    #[tokio::test]
+    async fn duplicate_labels_scenario() {
+        let pool = sqlite_memory_pool().await;
+        let labels = LabelRepositoryForSqlite::new(pool.clone());
+        let target = labels
+            .create(CreateLabel::new("merge".to_string()))
+            .await
+            .unwrap();
+        let source = labels
+            .create(CreateLabel::new(" MERGE".to_string()))
+            .await
+            .unwrap();
+        labels
+            .create(CreateLabel::new("other".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            duplicate_labels(&labels).await.unwrap(),
+            vec![vec![target, source]]
+        );
+    }
+
+    #[tokio::test]
+    async fn orphaned_task_labels_scenario() {
+        let pool = sqlite_memory_pool().await;
+        // SQLite は外部キー制約を接続ごとに無効にできる
+        sqlx::query("pragma foreign_keys = off")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"
+                insert into task_labels (task_id, label_id)
+                values (-1, -1)
+                returning id
+            "#,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let repository = MaintenanceRepositoryForSqlite::new(pool);
+        assert_eq!(
+            repository.orphaned_task_labels().await.unwrap(),
+            vec![TaskLabelRow {
+                id,
+                task_id: -1,
+                label_id: -1,
+            }]
+        );
+        assert_eq!(repository.delete_task_labels(&[id]).await.unwrap(), 1);
+        assert!(repository.orphaned_task_labels().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn vacuum_scenario() {
+        let pool = sqlite_memory_pool().await;
+        let tasks = TaskRepositoryForSqlite::new(pool.clone());
+        let task = tasks
+            .create(CreateTask::new("deleted".to_string(), vec![]))
+            .await
+            .unwrap();
+        tasks.delete(task.id, None).await.unwrap();
+
+        let repository = MaintenanceRepositoryForSqlite::new(pool);
+        repository.vacuum().await.expect("[vacuum] returned Err");
+        assert!(tasks.records().await.unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod memory_test {
+    use super::*;
+    use crate::repositories::{
+        label::LabelRepositoryForMemory, memory::MemoryStore, project::ProjectRepositoryForMemory,
+        task::TaskRepositoryForMemory,
+    };
+
+    #[tokio::test]
+    async fn import_scenario() {
+        let store = MemoryStore::new();
+        test_utils::import_scenario(
+            &ProjectRepositoryForMemory::with_store(store.clone()),
+            &LabelRepositoryForMemory::with_store(store.clone()),
+            &TaskRepositoryForMemory::with_store(store),
+        )
+        .await;
+    }
+
+    /// 参照の欠けた dump は何も書かずに弾く
+    #[tokio::test]
+    async fn reject_dangling_reference() {
+        let store = MemoryStore::new();
+        let projects = ProjectRepositoryForMemory::with_store(store.clone());
+        let labels = LabelRepositoryForMemory::with_store(store.clone());
+        let tasks = TaskRepositoryForMemory::with_store(store);
+        let task = TaskRecord {
+            id: 1,
+            text: "task".to_string(),
+            completed: false,
+            version: 1,
+            labels: vec![9],
+            attached_at: vec![],
+            recurrence: None,
+            due_date: None,
+            series_id: None,
+            project_id: None,
+            position: "a0".to_string(),
+        };
+        let dump = Dump::new(
+            vec![Project {
+                id: 1,
+                name: "project".to_string(),
+            }],
+            vec![],
+            vec![task],
+        );
+
+        let res = import(&projects, &labels, &tasks, dump).await;
+        assert!(res.unwrap_err().to_string().contains("label 9"));
+        assert!(projects.all().await.unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::task::{CreateTask, UpdateTask};
+
+    /// ラベルの詳細と親子，プロジェクト，並び順，完了済みの繰り返しタスクを export して取り込む
+    /// 取り込んだ分の export を返す
+    pub async fn import_scenario<P, L, T>(projects: &P, labels: &L, tasks: &T) -> (Dump, Dump)
+    where
+        P: ProjectRepository,
+        L: LabelRepository,
+        T: TaskRepository,
+    {
+        let suffix = uuid::Uuid::new_v4();
+        let name = |name: &str| format!("[import_scenario] {} {}", name, suffix);
+        let project = Project {
+            id: -1,
+            name: name("project"),
+        };
+        let parent = Label {
+            color: Some("#ff0000".to_string()),
+            description: Some("parent".to_string()),
+            position: 2,
+            ..Label::new(-2, name("parent"))
+        };
+        // 子の方が id が小さくても親を付けられる
+        let child = Label {
+            position: 1,
+            parent_id: Some(parent.id),
+            ..Label::new(-3, name("child"))
+        };
+        import(
+            projects,
+            labels,
+            tasks,
+            Dump::new(
+                vec![project.clone()],
+                vec![child.clone(), parent.clone()],
+                vec![],
+            ),
+        )
+        .await
+        .expect("[import] returned Err");
+        let exported = export(projects, labels, tasks).await.unwrap();
+        let find_label = |name: &str| {
+            exported
+                .labels
+                .iter()
+                .find(|label| label.name == name)
+                .unwrap()
+                .clone()
+        };
+        let (parent, child) = (find_label(&parent.name), find_label(&child.name));
+        assert_eq!(parent.color.as_deref(), Some("#ff0000"));
+        assert_eq!(child.parent_id, Some(parent.id));
+        let project = exported
+            .projects
+            .iter()
+            .find(|p| p.name == project.name)
+            .unwrap()
+            .clone();
+
+        let first = tasks
+            .create(
+                CreateTask::new(name("weekly"), vec![child.id])
+                    .with_recurrence(
+                        Some("FREQ=WEEKLY".to_string()),
+                        Some("2024-01-01".parse().unwrap()),
+                    )
+                    .with_project(Some(project.id)),
+            )
+            .await
+            .unwrap();
+        tasks
+            .update(first.id, UpdateTask::new(None, Some(true), None), None)
+            .await
+            .unwrap();
+        let ids = tasks
+            .occurrences(first.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|task| task.id)
+            .collect::<Vec<_>>();
+        let dump = export(projects, labels, tasks).await.unwrap();
+        let dump = Dump::new(
+            vec![project],
+            vec![child, parent],
+            dump.tasks
+                .into_iter()
+                .filter(|task| ids.contains(&task.id))
+                .collect(),
+        );
+        assert_eq!(dump.tasks.len(), 2);
+
+        // 取り込んでも次のタスクは作られず，状態と並び順はそのまま
+        let before = export(projects, labels, tasks).await.unwrap();
+        import(projects, labels, tasks, dump.clone())
+            .await
+            .expect("[import] returned Err");
+        let after = export(projects, labels, tasks).await.unwrap();
+        let imported = Dump::new(
+            after
+                .projects
+                .into_iter()
+                .filter(|p| !before.projects.contains(p))
+                .collect(),
+            vec![],
+            after
+                .tasks
+                .into_iter()
+                .filter(|task| !before.tasks.iter().any(|t| t.id == task.id))
+                .collect(),
+        );
+        assert_eq!(imported.tasks.len(), 2);
+        let (new_first, new_second) = (&imported.tasks[0], &imported.tasks[1]);
+        let (old_first, old_second) = (&dump.tasks[0], &dump.tasks[1]);
+        // 同名のラベルは既存のものを使う
+        assert_eq!(after.labels.len(), before.labels.len());
+        assert_eq!(
+            new_first,
+            &TaskRecord {
+                id: new_first.id,
+                project_id: Some(imported.projects[0].id),
+                ..old_first.clone()
+            }
+        );
+        assert_eq!(
+            new_second,
+            &TaskRecord {
+                id: new_second.id,
+                series_id: Some(new_first.id),
+                project_id: Some(imported.projects[0].id),
+                ..old_second.clone()
+            }
+        );
+        assert!(old_first.completed && !old_second.completed);
+        (dump, imported)
+    }
+}
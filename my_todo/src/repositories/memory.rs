@@ -0,0 +1,132 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use super::{
+    delivery::Delivery, label::Label, project::Project, webhook::Webhook, RepositoryError,
+};
+
+/// tasks テーブルの 1 行
+#[derive(Debug, Clone)]
+pub(super) struct TaskRow {
+    pub text: String,
+    pub completed: bool,
+    pub version: i32,
+    pub recurrence: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub series_id: Option<i32>,
+    /// 並び順のキー
+    pub position: String,
+    pub project_id: Option<i32>,
+}
+
+/// DB のテーブル構成をそのまま写したもの
+/// id は serial と同じく削除しても再利用しない
+#[derive(Debug, Clone, Default)]
+pub(super) struct Tables {
+    pub tasks: BTreeMap<i32, TaskRow>,
+    pub labels: BTreeMap<i32, Label>,
+    /// (task_id, label_id) と付けた日時，日時を記録する前に付けたものは None
+    pub task_labels: BTreeMap<(i32, i32), Option<DateTime<Utc>>>,
+    pub deliveries: BTreeSet<Delivery>,
+    pub webhooks: BTreeMap<i32, Webhook>,
+    pub projects: BTreeMap<i32, Project>,
+    pub task_seq: i32,
+    pub label_seq: i32,
+    pub webhook_seq: i32,
+    pub project_seq: i32,
+}
+
+impl Tables {
+    pub fn next_task_id(&mut self) -> i32 {
+        self.task_seq += 1;
+        self.task_seq
+    }
+
+    pub fn next_label_id(&mut self) -> i32 {
+        self.label_seq += 1;
+        self.label_seq
+    }
+
+    pub fn next_webhook_id(&mut self) -> i32 {
+        self.webhook_seq += 1;
+        self.webhook_seq
+    }
+
+    pub fn next_project_id(&mut self) -> i32 {
+        self.project_seq += 1;
+        self.project_seq
+    }
+
+    /// 外部キー制約の代わりに存在しないプロジェクトを弾く
+    pub fn check_project(&self, project_id: Option<i32>) -> Result<(), RepositoryError> {
+        match project_id {
+            Some(id) if !self.projects.contains_key(&id) => Err(RepositoryError::NotFound(id)),
+            _ => Ok(()),
+        }
+    }
+
+    /// 全ラベルの (id, parent_id)，親子関係の検査に使う
+    pub fn label_parents(&self) -> Vec<(i32, Option<i32>)> {
+        self.labels
+            .values()
+            .map(|label| (label.id, label.parent_id))
+            .collect()
+    }
+
+    pub fn insert_label(&mut self, label: Label) {
+        self.label_seq = self.label_seq.max(label.id);
+        self.labels.insert(label.id, label);
+    }
+
+    /// 外部キー制約の代わりに存在しないラベルを弾く
+    pub fn set_task_labels(
+        &mut self,
+        task_id: i32,
+        label_ids: &[i32],
+    ) -> Result<(), RepositoryError> {
+        if let Some(id) = label_ids.iter().find(|id| !self.labels.contains_key(id)) {
+            return Err(RepositoryError::NotFound(*id));
+        }
+        // 付け直さなかったラベルは付けた日時をそのまま残す
+        let now = Utc::now();
+        self.task_labels
+            .retain(|(id, label_id), _| *id != task_id || label_ids.contains(label_id));
+        for label_id in label_ids {
+            self.task_labels
+                .entry((task_id, *label_id))
+                .or_insert(Some(now));
+        }
+        Ok(())
+    }
+
+    /// label_id の昇順で返す
+    pub fn labels_of(&self, task_id: i32) -> Vec<Label> {
+        self.task_labels
+            .range((task_id, i32::MIN)..=(task_id, i32::MAX))
+            .filter_map(|((_, label_id), _)| self.labels.get(label_id).cloned())
+            .collect()
+    }
+}
+
+/// プロセス内に保持するストア，タスクとラベルのリポジトリで共有する
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    tables: Arc<RwLock<Tables>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(super) fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap()
+    }
+
+    pub(super) fn write(&self) -> RwLockWriteGuard<'_, Tables> {
+        self.tables.write().unwrap()
+    }
+}
@@ -0,0 +1,102 @@
+use axum::async_trait;
+
+use super::{
+    label::{CreateLabel, Label, LabelRepository, LabelStats, UpdateLabel},
+    project::{CreateProject, Project, ProjectRepository, UpdateProject},
+    task::{CreateTask, MoveTask, TaskEntity, TaskRecord, TaskRepository, UpdateTask},
+};
+use crate::metrics::observe_repository;
+
+/// 各操作の処理時間を `repository_operation_duration_seconds` に記録するラッパー
+#[derive(Debug, Clone)]
+pub struct Metered<T> {
+    inner: T,
+}
+
+impl<T> Metered<T> {
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl<T: TaskRepository> TaskRepository for Metered<T> {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        observe_repository("task", "create", self.inner.create(payload)).await
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        observe_repository("task", "find", self.inner.find(id)).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        observe_repository("task", "all", self.inner.all()).await
+    }
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        observe_repository(
+            "task",
+            "update",
+            self.inner.update_with_next(id, payload, version),
+        )
+        .await
+    }
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+        observe_repository("task", "occurrences", self.inner.occurrences(id)).await
+    }
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        observe_repository("task", "move", self.inner.move_task(id, payload)).await
+    }
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        observe_repository("task", "delete", self.inner.delete(id, version)).await
+    }
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        observe_repository("task", "records", self.inner.records()).await
+    }
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        observe_repository("task", "restore", self.inner.restore(record)).await
+    }
+}
+
+#[async_trait]
+impl<T: LabelRepository> LabelRepository for Metered<T> {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        observe_repository("label", "create", self.inner.create(payload)).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        observe_repository("label", "all", self.inner.all()).await
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        observe_repository("label", "update", self.inner.update(id, payload)).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        observe_repository("label", "delete", self.inner.delete(id)).await
+    }
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        observe_repository("label", "stats", self.inner.stats()).await
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        observe_repository("label", "merge", self.inner.merge(target, sources)).await
+    }
+}
+
+#[async_trait]
+impl<T: ProjectRepository> ProjectRepository for Metered<T> {
+    async fn create(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        observe_repository("project", "create", self.inner.create(payload)).await
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Project> {
+        observe_repository("project", "find", self.inner.find(id)).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Project>> {
+        observe_repository("project", "all", self.inner.all()).await
+    }
+    async fn update(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        observe_repository("project", "update", self.inner.update(id, payload)).await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        observe_repository("project", "delete", self.inner.delete(id)).await
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::Context;
+use sqlx::{
+    migrate::{AppliedMigration, Migrate, Migrator},
+    Database, Pool,
+};
+
+/// 起動時のスキーマ準備
+/// `apply` が false の場合は適用せず，DB がバイナリより新しくないかだけを確認する
+pub async fn prepare_schema<DB>(
+    pool: &Pool<DB>,
+    migrator: &Migrator,
+    apply: bool,
+) -> anyhow::Result<()>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    if apply {
+        run_migrations(pool, migrator).await
+    } else {
+        check_schema(pool, migrator).await
+    }
+}
+
+/// 未適用のマイグレーションを適用する
+/// postgres では advisory lock を取るため，複数プロセスが同時に起動しても順に適用される
+pub async fn run_migrations<DB>(pool: &Pool<DB>, migrator: &Migrator) -> anyhow::Result<()>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    let res = apply_pending(&mut *conn, migrator).await;
+    // 失敗してもロックは解放する
+    unlock(&mut *conn, res).await
+}
+
+async fn apply_pending<C: Migrate>(conn: &mut C, migrator: &Migrator) -> anyhow::Result<()> {
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        anyhow::bail!("migration {} is partially applied", version);
+    }
+    let applied = conn.list_applied_migrations().await?;
+    ensure_not_ahead(&applied, migrator)?;
+
+    for migration in migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        match applied.iter().find(|a| a.version == migration.version) {
+            Some(a) if a.checksum != migration.checksum => {
+                anyhow::bail!(
+                    "migration {} was modified after it was applied",
+                    migration.version
+                );
+            }
+            Some(_) => {}
+            None => {
+                conn.apply(migration).await?;
+                tracing::info!(
+                    "applied migration {} {}",
+                    migration.version,
+                    migration.description
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 最後に適用したマイグレーションを 1 つ戻し，戻したバージョンを返す
+pub async fn revert_last<DB>(pool: &Pool<DB>, migrator: &Migrator) -> anyhow::Result<Option<i64>>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    let res = revert_latest(&mut *conn, migrator).await;
+    unlock(&mut *conn, res).await
+}
+
+/// ロックを解放して `res` を返す
+/// `res` が失敗していれば解放の失敗はログに残すだけにして，元のエラーを返す
+async fn unlock<C: Migrate, T>(conn: &mut C, res: anyhow::Result<T>) -> anyhow::Result<T> {
+    match (res, conn.unlock().await) {
+        (res, Ok(())) => res,
+        (Ok(_), Err(e)) => Err(e).context("failed to release the migration lock"),
+        (Err(e), Err(unlock)) => {
+            tracing::warn!("failed to release the migration lock: {}", unlock);
+            Err(e)
+        }
+    }
+}
+
+async fn revert_latest<C: Migrate>(
+    conn: &mut C,
+    migrator: &Migrator,
+) -> anyhow::Result<Option<i64>> {
+    conn.ensure_migrations_table().await?;
+    if let Some(version) = conn.dirty_version().await? {
+        anyhow::bail!("migration {} is partially applied", version);
+    }
+    let applied = conn.list_applied_migrations().await?;
+    ensure_not_ahead(&applied, migrator)?;
+    let latest = match applied.iter().map(|a| a.version).max() {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+    let migration = migrator
+        .iter()
+        .find(|migration| {
+            migration.version == latest && migration.migration_type.is_down_migration()
+        })
+        .ok_or_else(|| anyhow::anyhow!("migration {} is not reversible", latest))?;
+    conn.revert(migration).await?;
+    tracing::info!(
+        "reverted migration {} {}",
+        migration.version,
+        migration.description
+    );
+    Ok(Some(latest))
+}
+
+pub async fn check_schema<DB>(pool: &Pool<DB>, migrator: &Migrator) -> anyhow::Result<()>
+where
+    DB: Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .context("fail read applied migrations, set AUTO_MIGRATE=true to create the schema")?;
+    ensure_not_ahead(&applied, migrator)
+}
+
+/// バイナリが知らないマイグレーションが適用済みなら古いバイナリで起動しようとしている
+fn ensure_not_ahead(applied: &[AppliedMigration], migrator: &Migrator) -> anyhow::Result<()> {
+    let unknown = applied
+        .iter()
+        .filter(|a| {
+            !migrator
+                .iter()
+                .any(|migration| migration.version == a.version)
+        })
+        .map(|a| a.version.to_string())
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        let latest = migrator
+            .iter()
+            .map(|migration| migration.version)
+            .max()
+            .unwrap_or_default();
+        anyhow::bail!(
+            "database schema is ahead of this binary: unknown migrations [{}] are applied, latest known is {}",
+            unknown.join(", "),
+            latest
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::SQLITE_MIGRATOR;
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    async fn empty_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .expect("fail connect sqlite")
+    }
+
+    #[tokio::test]
+    async fn should_apply_pending_migrations_once() {
+        let pool = empty_pool().await;
+        assert!(check_schema(&pool, &SQLITE_MIGRATOR).await.is_err());
+
+        prepare_schema(&pool, &SQLITE_MIGRATOR, true).await.unwrap();
+        prepare_schema(&pool, &SQLITE_MIGRATOR, true).await.unwrap();
+        prepare_schema(&pool, &SQLITE_MIGRATOR, false)
+            .await
+            .unwrap();
+
+        let applied = sqlx::query_scalar::<_, i64>("select count(*) from _sqlx_migrations")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let ups = SQLITE_MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .count();
+        assert_eq!(applied as usize, ups);
+    }
+
+    #[tokio::test]
+    async fn should_revert_latest_migration() {
+        let pool = empty_pool().await;
+        assert_eq!(revert_last(&pool, &SQLITE_MIGRATOR).await.unwrap(), None);
+        run_migrations(&pool, &SQLITE_MIGRATOR).await.unwrap();
+
+        let foreign_keys = || async {
+            sqlx::query("pragma foreign_key_list(reminder_deliveries)")
+                .fetch_all(&pool)
+                .await
+                .unwrap()
+                .len()
+        };
+        let reverted = revert_last(&pool, &SQLITE_MIGRATOR).await.unwrap();
+        assert_eq!(reverted, Some(20240121090000));
+        assert_eq!(foreign_keys().await, 0);
+
+        // 戻した分は再度適用される
+        run_migrations(&pool, &SQLITE_MIGRATOR).await.unwrap();
+        assert_eq!(foreign_keys().await, 1);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_schema_ahead_of_binary() {
+        let pool = empty_pool().await;
+        run_migrations(&pool, &SQLITE_MIGRATOR).await.unwrap();
+        sqlx::query(
+            r#"
+                insert into _sqlx_migrations (version, description, success, checksum, execution_time)
+                values (29991231000000, 'future', true, x'00', 0)
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        for apply in [true, false] {
+            let err = prepare_schema(&pool, &SQLITE_MIGRATOR, apply)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("29991231000000"));
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::MIGRATOR;
+    use dotenv::dotenv;
+    use sqlx::PgPool;
+    use std::env;
+
+    #[tokio::test]
+    async fn concurrent_runs_succeed() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        let (a, b) = tokio::join!(
+            run_migrations(&pool, &MIGRATOR),
+            run_migrations(&pool, &MIGRATOR)
+        );
+        a.unwrap();
+        b.unwrap();
+        check_schema(&pool, &MIGRATOR).await.unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+//! タスクの並び順のキー
+//!
+//! キーは 62 進の小数 `0.d1d2d3...` として扱い，末尾に `0` を付けない．
+//! そのため任意の 2 つのキーの間に必ず別のキーを作ることができ，
+//! 移動しても他のタスクのキーを書き換える必要はない．
+
+use super::RepositoryError;
+
+/// キーに使う文字，ASCII の順に並んでいるのでバイト順の比較がそのまま値の順になる
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: u8 = DIGITS.len() as u8;
+/// これより長いキーが必要になったら全体を振り直す
+pub const MAX_LEN: usize = 24;
+/// 振り直し後のキーの桁数
+const SPREAD_LEN: u32 = 8;
+
+fn digits(key: &str) -> Option<Vec<u8>> {
+    key.bytes()
+        .map(|b| DIGITS.iter().position(|d| *d == b).map(|d| d as u8))
+        .collect()
+}
+
+fn encode(digits: &[u8]) -> String {
+    digits.iter().map(|d| DIGITS[*d as usize] as char).collect()
+}
+
+/// `lower` と `upper` の間のキー，`lower < upper` であること
+fn midpoint(lower: &[u8], upper: Option<&[u8]>) -> Vec<u8> {
+    if let Some(upper) = upper {
+        // 共通の先頭部分はそのまま使う，lower の足りない桁は 0 とみなす
+        let n = upper
+            .iter()
+            .enumerate()
+            .take_while(|(i, d)| lower.get(*i).copied().unwrap_or(0) == **d)
+            .count();
+        if n > 0 {
+            let mut key = upper[..n].to_vec();
+            key.extend(midpoint(lower.get(n..).unwrap_or(&[]), Some(&upper[n..])));
+            return key;
+        }
+    }
+    let low = lower.first().copied().unwrap_or(0);
+    let high = upper.map_or(BASE, |upper| upper[0]);
+    if high - low > 1 {
+        return vec![(low + high) / 2];
+    }
+    // 隣り合う桁の場合，upper に続きがあれば upper の 1 桁目だけで間に入る
+    if let Some(upper) = upper.filter(|upper| upper.len() > 1) {
+        return vec![upper[0]];
+    }
+    let mut key = vec![low];
+    key.extend(midpoint(lower.get(1..).unwrap_or(&[]), None));
+    key
+}
+
+/// `lower` と `upper` の間のキー，None は先頭または末尾
+/// 不正なキーや順序が逆の場合は None を返すので，振り直して対応する
+pub fn between(lower: Option<&str>, upper: Option<&str>) -> Option<String> {
+    let lower = digits(lower.unwrap_or(""))?;
+    let upper = match upper {
+        Some(upper) => {
+            let upper = digits(upper)?;
+            if upper.last() == Some(&0) || upper <= lower {
+                return None;
+            }
+            Some(upper)
+        }
+        None => None,
+    };
+    Some(encode(&midpoint(&lower, upper.as_deref())))
+}
+
+/// `first` より前のキー，新しいタスクを先頭に置く時に使う
+/// 長さを変えずに 1 だけ減らすので，先頭への追加ではキーが伸びない
+pub fn before(first: Option<&str>) -> String {
+    let Some(first) = first else {
+        return spread(1).remove(0);
+    };
+    if let Some(mut key) = digits(first) {
+        // 末尾が 0 になった場合はもう 1 つ減らす
+        for _ in 0..2 {
+            match key.iter().rposition(|d| *d > 0) {
+                Some(i) => {
+                    key[i] -= 1;
+                    key[i + 1..].iter_mut().for_each(|d| *d = BASE - 1);
+                }
+                None => break,
+            }
+            if key.last().is_some_and(|d| *d > 0) {
+                return encode(&key);
+            }
+        }
+    }
+    between(None, Some(first)).unwrap_or_default()
+}
+
+/// 等間隔に並べた `n` 個のキー，振り直しに使う
+pub fn spread(n: usize) -> Vec<String> {
+    let range = (BASE as u64).pow(SPREAD_LEN);
+    let step = range / (n as u64 + 1);
+    (1..=n as u64)
+        .map(|i| {
+            let mut value = i * step;
+            let mut key = vec![0; SPREAD_LEN as usize];
+            for d in key.iter_mut().rev() {
+                *d = (value % BASE as u64) as u8;
+                value /= BASE as u64;
+            }
+            // 末尾の 0 は避ける，step は十分大きいので隣のキーを越えない
+            if key.last() == Some(&0) {
+                key[SPREAD_LEN as usize - 1] = 1;
+            }
+            encode(&key)
+        })
+        .collect()
+}
+
+/// キーが無い頃に作られたタスクのキー，マイグレーションと同じく id の降順になる
+pub fn legacy(id: i32) -> String {
+    format!("{:08}V", 99_999_999 - id)
+}
+
+/// 移動後のキー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placement {
+    /// 移動したタスクのキーだけを書き換える
+    Key(String),
+    /// 全タスクのキーを振り直す，(id, キー) を新しい順序で
+    Rebalance(Vec<(i32, String)>),
+}
+
+/// `order` は全タスクの (id, キー) を並び順で
+/// `after` の直後，`before` の直前に `id` を置く，両方指定する場合は隣り合っていること
+pub fn place(
+    order: &[(i32, String)],
+    id: i32,
+    after: Option<i32>,
+    before: Option<i32>,
+) -> Result<Placement, RepositoryError> {
+    if !order.iter().any(|(task_id, _)| *task_id == id) {
+        return Err(RepositoryError::NotFound(id));
+    }
+    if after == Some(id) || before == Some(id) {
+        return Err(RepositoryError::Conflict(id));
+    }
+    let others: Vec<&(i32, String)> = order.iter().filter(|(task_id, _)| *task_id != id).collect();
+    let index_of = |target: i32| {
+        others
+            .iter()
+            .position(|(task_id, _)| *task_id == target)
+            .ok_or(RepositoryError::NotFound(target))
+    };
+    let index = match (after, before) {
+        (Some(after), before) => {
+            let index = index_of(after)? + 1;
+            if before.is_some() && others.get(index).map(|(task_id, _)| *task_id) != before {
+                return Err(RepositoryError::Conflict(id));
+            }
+            index
+        }
+        (None, Some(before)) => index_of(before)?,
+        (None, None) => return Err(RepositoryError::Conflict(id)),
+    };
+    let lower = index.checked_sub(1).map(|i| others[i].1.as_str());
+    let upper = others.get(index).map(|(_, key)| key.as_str());
+    match between(lower, upper).filter(|key| key.len() <= MAX_LEN) {
+        Some(key) => Ok(Placement::Key(key)),
+        None => {
+            let mut ids: Vec<i32> = others.iter().map(|(task_id, _)| *task_id).collect();
+            ids.insert(index, id);
+            Ok(Placement::Rebalance(
+                ids.into_iter().zip(spread(order.len())).collect(),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn key(lower: Option<&str>, upper: Option<&str>) -> String {
+        let key = between(lower, upper).unwrap();
+        assert!(
+            lower.is_none_or(|lower| lower < key.as_str()),
+            "{:?} {}",
+            lower,
+            key
+        );
+        assert!(
+            upper.is_none_or(|upper| key.as_str() < upper),
+            "{} {:?}",
+            key,
+            upper
+        );
+        assert!(!key.ends_with('0'));
+        key
+    }
+
+    #[test]
+    fn should_make_key_between() {
+        assert_eq!(key(None, None), "V");
+        assert_eq!(key(Some("V"), None), "k");
+        assert_eq!(key(None, Some("V")), "F");
+        assert_eq!(key(Some("A"), Some("B")), "AV");
+        assert_eq!(key(Some("A"), Some("B1")), "B");
+        assert_eq!(key(Some("A0z"), Some("A1")), "A0zV");
+        key(Some("99999998V"), Some("99999999V"));
+        key(Some("A"), Some("A01"));
+
+        // 同じ隙間に入れ続けてもキーは 1 桁ずつしか伸びない
+        let (lower, mut upper) = ("A".to_string(), "B".to_string());
+        for _ in 0..100 {
+            upper = key(Some(&lower), Some(&upper));
+        }
+        assert!(upper.len() <= 100);
+
+        assert_eq!(between(Some("B"), Some("A")), None);
+        assert_eq!(between(Some("A"), Some("A")), None);
+        assert_eq!(between(Some("A"), Some("B0")), None);
+        assert_eq!(between(Some("-"), None), None);
+    }
+
+    #[test]
+    fn should_prepend_without_growing() {
+        assert_eq!(before(Some("99999998V")), "99999998U");
+        assert_eq!(before(Some("A1")), "9z");
+        assert_eq!(before(Some("B")), "A");
+        // これ以上減らせなければ間に入れる
+        assert_eq!(before(Some("1")), "0V");
+        assert_eq!(before(Some("01")), "00V");
+
+        let mut first = before(None);
+        for _ in 0..1000 {
+            let next = before(Some(&first));
+            assert!(next < first && !next.ends_with('0'));
+            first = next;
+        }
+        assert_eq!(first.len(), SPREAD_LEN as usize);
+    }
+
+    #[test]
+    fn should_spread_keys() {
+        let keys = spread(3);
+        assert_eq!(keys.len(), 3);
+        assert!(keys.windows(2).all(|w| w[0] < w[1]));
+        assert!(keys.iter().all(|key| !key.ends_with('0')));
+        assert!(legacy(2) < legacy(1));
+    }
+
+    #[test]
+    fn should_place_between_neighbours() {
+        let order = vec![
+            (1, "A".to_string()),
+            (2, "B".to_string()),
+            (3, "C".to_string()),
+            (9, "D".to_string()),
+        ];
+        assert_eq!(
+            place(&order, 9, Some(1), None).unwrap(),
+            Placement::Key("AV".to_string())
+        );
+        assert_eq!(
+            place(&order, 9, None, Some(1)).unwrap(),
+            Placement::Key("5".to_string())
+        );
+        assert_eq!(
+            place(&order, 9, Some(3), None).unwrap(),
+            Placement::Key("b".to_string())
+        );
+        assert_eq!(
+            place(&order, 9, Some(2), Some(3)).unwrap(),
+            Placement::Key("BV".to_string())
+        );
+        assert!(matches!(
+            place(&order, 9, Some(1), Some(3)),
+            Err(RepositoryError::Conflict(9))
+        ));
+        assert!(matches!(
+            place(&order, 9, Some(9), None),
+            Err(RepositoryError::Conflict(9))
+        ));
+        assert!(matches!(
+            place(&order, 9, Some(4), None),
+            Err(RepositoryError::NotFound(4))
+        ));
+        assert!(matches!(
+            place(&order, 4, Some(1), None),
+            Err(RepositoryError::NotFound(4))
+        ));
+    }
+
+    #[test]
+    fn should_rebalance_long_keys() {
+        let order = vec![
+            (9, "0V".to_string()),
+            (1, "A".to_string()),
+            (2, format!("A{}1", "0".repeat(MAX_LEN))),
+        ];
+        match place(&order, 9, Some(1), Some(2)).unwrap() {
+            Placement::Rebalance(keys) => {
+                assert_eq!(
+                    keys.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+                    vec![1, 9, 2]
+                );
+                assert!(keys.windows(2).all(|w| w[0].1 < w[1].1));
+            }
+            placement => panic!("{:?}", placement),
+        }
+    }
+}
@@ -0,0 +1,527 @@
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, SqlitePool};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    file::{FileStore, Record, Touched},
+    limits::{optional_text_schema, text_schema, TEXT_MAX_LEN, TEXT_MIN_LEN},
+    memory::MemoryStore,
+    RepositoryError,
+};
+
+#[async_trait]
+pub trait ProjectRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, payload: CreateProject) -> anyhow::Result<Project>;
+    async fn find(&self, id: i32) -> anyhow::Result<Project>;
+    async fn all(&self) -> anyhow::Result<Vec<Project>>;
+    async fn update(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project>;
+    /// タスクが残っている場合は Conflict，先に別のプロジェクトへ移すこと
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, FromRow, ToSchema)]
+pub struct Project {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct CreateProject {
+    #[validate(length(min = "TEXT_MIN_LEN", message = "Can not be empty"))]
+    #[validate(length(max = "TEXT_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = text_schema)]
+    name: String,
+}
+
+impl CreateProject {
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+pub struct UpdateProject {
+    #[validate(length(min = "TEXT_MIN_LEN", message = "Can not be empty"))]
+    #[validate(length(max = "TEXT_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = optional_text_schema)]
+    name: Option<String>,
+}
+
+impl UpdateProject {
+    pub fn new(name: Option<String>) -> Self {
+        Self { name }
+    }
+}
+
+#[derive(Clone)]
+pub struct ProjectRepositoryForDb {
+    pool: PgPool,
+}
+
+impl ProjectRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForDb {
+    async fn create(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                insert into projects (name)
+                values ($1)
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(project)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects where id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Project>> {
+        let projects = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects
+                order by id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(projects)
+    }
+    async fn update(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                update projects
+                set name = coalesce($1, name)
+                where id = $2
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("select id from projects where id = $1 for update")
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        let in_use = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (select 1 from tasks where project_id = $1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if in_use {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        sqlx::query("delete from projects where id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct ProjectRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl ProjectRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForSqlite {
+    async fn create(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                insert into projects (name)
+                values (?1)
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(project)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects where id = ?1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Project>> {
+        let projects = sqlx::query_as::<_, Project>(
+            r#"
+                select * from projects
+                order by id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(projects)
+    }
+    async fn update(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let project = sqlx::query_as::<_, Project>(
+            r#"
+                update projects
+                set name = coalesce(?1, name)
+                where id = ?2
+                returning *
+            "#,
+        )
+        .bind(payload.name)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("select id from projects where id = ?1")
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(RepositoryError::NotFound(id))?;
+        let in_use = sqlx::query_scalar::<_, bool>(
+            r#"
+                select exists (select 1 from tasks where project_id = ?1)
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+        if in_use {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        sqlx::query("delete from projects where id = ?1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// `--storage=memory` 用，プロセス終了でデータは消える
+#[derive(Debug, Clone, Default)]
+pub struct ProjectRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl ProjectRepositoryForMemory {
+    pub fn with_store(store: MemoryStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForMemory {
+    async fn create(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        let mut tables = self.store.write();
+        let id = tables.next_project_id();
+        let project = Project {
+            id,
+            name: payload.name,
+        };
+        tables.projects.insert(id, project.clone());
+        Ok(project)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Project> {
+        let tables = self.store.read();
+        let project = tables
+            .projects
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(project)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Project>> {
+        let tables = self.store.read();
+        Ok(tables.projects.values().cloned().collect())
+    }
+    async fn update(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        let mut tables = self.store.write();
+        let project = tables
+            .projects
+            .get_mut(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        if let Some(name) = payload.name {
+            project.name = name;
+        }
+        Ok(project.clone())
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        if !tables.projects.contains_key(&id) {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        if tables.tasks.values().any(|row| row.project_id == Some(id)) {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        tables.projects.remove(&id);
+        Ok(())
+    }
+}
+
+/// 変更をログファイルに追記する，読み込みはメモリ上のデータから返す
+#[derive(Debug, Clone)]
+pub struct ProjectRepositoryForFile {
+    inner: ProjectRepositoryForMemory,
+    store: FileStore,
+}
+
+impl ProjectRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            inner: ProjectRepositoryForMemory::with_store(store.memory()),
+            store,
+        }
+    }
+}
+
+#[async_trait]
+impl ProjectRepository for ProjectRepositoryForFile {
+    async fn create(&self, payload: CreateProject) -> anyhow::Result<Project> {
+        self.store
+            .commit(
+                |_| Touched::none(),
+                || async {
+                    let project = self.inner.create(payload).await?;
+                    let record = Record::put_project(&project);
+                    Ok((project, vec![record]))
+                },
+            )
+            .await
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<Project> {
+        self.inner.find(id).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Project>> {
+        self.inner.all().await
+    }
+    async fn update(&self, id: i32, payload: UpdateProject) -> anyhow::Result<Project> {
+        self.store
+            .commit(
+                |_| Touched::projects([id]),
+                || async {
+                    let project = self.inner.update(id, payload).await?;
+                    let record = Record::put_project(&project);
+                    Ok((project, vec![record]))
+                },
+            )
+            .await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.store
+            .commit(
+                |_| Touched::projects([id]),
+                || async {
+                    self.inner.delete(id).await?;
+                    Ok(((), vec![Record::DeleteProject { id }]))
+                },
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::task::TaskRepositoryForDb;
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::crud_scenario(
+            &ProjectRepositoryForDb::new(pool.clone()),
+            &TaskRepositoryForDb::new(pool),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::{task::TaskRepositoryForSqlite, test_utils::sqlite_memory_pool};
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_memory_pool().await;
+        test_utils::crud_scenario(
+            &ProjectRepositoryForSqlite::new(pool.clone()),
+            &TaskRepositoryForSqlite::new(pool),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod memory_test {
+    use super::*;
+    use crate::repositories::task::TaskRepositoryForMemory;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let store = MemoryStore::new();
+        test_utils::crud_scenario(
+            &ProjectRepositoryForMemory::with_store(store.clone()),
+            &TaskRepositoryForMemory::with_store(store),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use super::*;
+    use crate::repositories::{file::test_utils::temp_data_file, task::TaskRepositoryForFile};
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let path = temp_data_file();
+        let store = FileStore::open(&path).unwrap();
+        let project = ProjectRepositoryForFile::new(store.clone())
+            .create(CreateProject::new("kept".to_string()))
+            .await
+            .unwrap();
+        test_utils::crud_scenario(
+            &ProjectRepositoryForFile::new(store.clone()),
+            &TaskRepositoryForFile::new(store),
+        )
+        .await;
+
+        // ログから復元される
+        let repository = ProjectRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert_eq!(repository.all().await.unwrap(), vec![project]);
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use crate::repositories::task::{CreateTask, TaskRepository, UpdateTask};
+
+    /// 各 DB 実装で共通の CRUD シナリオ，タスクの移動と削除の制約も確かめる
+    pub async fn crud_scenario<P: ProjectRepository, T: TaskRepository>(repository: &P, tasks: &T) {
+        // create
+        let project = repository
+            .create(CreateProject::new("[crud_scenario] project".to_string()))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(project.name, "[crud_scenario] project");
+
+        // find, all
+        assert_eq!(repository.find(project.id).await.unwrap(), project);
+        assert!(repository.all().await.unwrap().contains(&project));
+
+        // update
+        let project = repository
+            .update(
+                project.id,
+                UpdateProject::new(Some("[crud_scenario] renamed".to_string())),
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(project.name, "[crud_scenario] renamed");
+        assert_eq!(
+            repository
+                .update(project.id, UpdateProject::new(None))
+                .await
+                .unwrap(),
+            project
+        );
+
+        // タスクが残っている間は削除できない
+        let task = tasks
+            .create(
+                CreateTask::new("[crud_scenario] task".to_string(), vec![])
+                    .with_project(Some(project.id)),
+            )
+            .await
+            .expect("[create task] returned Err");
+        assert_eq!(task.project_id, Some(project.id));
+        let res = repository.delete(project.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+
+        // プロジェクトから外すと削除できる
+        let task = tasks
+            .update(
+                task.id,
+                UpdateTask::new(None, None, None).with_project(Some(0)),
+                None,
+            )
+            .await
+            .expect("[update task] returned Err");
+        assert_eq!(task.project_id, None);
+        repository
+            .delete(project.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.find(project.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(_))
+        ));
+        assert!(repository.delete(project.id).await.is_err());
+
+        // 存在しないプロジェクトには入れられない
+        assert!(tasks
+            .update(
+                task.id,
+                UpdateTask::new(None, None, None).with_project(Some(project.id)),
+                None,
+            )
+            .await
+            .is_err());
+        tasks.delete(task.id, None).await.unwrap();
+    }
+}
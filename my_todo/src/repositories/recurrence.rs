@@ -0,0 +1,332 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use validator::ValidationError;
+
+/// 受け付ける期日の年，繰り返しの計算が chrono の範囲を超えないように制限する
+pub const DUE_DATE_YEARS: RangeInclusive<i32> = 1..=9999;
+
+/// タスクの繰り返しルール，RRULE (RFC 5545) のうち以下だけを扱う
+///
+/// - `FREQ=DAILY[;INTERVAL=n]`
+/// - `FREQ=WEEKLY[;INTERVAL=n][;BYDAY=MO,TH]`
+/// - `FREQ=MONTHLY[;INTERVAL=n][;BYMONTHDAY=d]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recurrence {
+    Daily {
+        interval: u32,
+    },
+    /// weekdays が空の場合は基準日と同じ曜日
+    Weekly {
+        interval: u32,
+        weekdays: Vec<Weekday>,
+    },
+    /// day が無い場合は繰り返しの最初の日と同じ日
+    Monthly {
+        interval: u32,
+        day: Option<u32>,
+    },
+}
+
+impl Recurrence {
+    /// `date` より後で最初に該当する日，日付の範囲を超える場合は None
+    /// `anchor` は繰り返しの最初の日，MONTHLY で BYMONTHDAY が無い場合はその日を毎月丸めて使う
+    pub fn next_after(&self, date: NaiveDate, anchor: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Recurrence::Daily { interval } => date.checked_add_days(Days::new(*interval as u64)),
+            Recurrence::Weekly { interval, weekdays } => {
+                let matches = |d: NaiveDate| match weekdays.is_empty() {
+                    true => d.weekday() == date.weekday(),
+                    false => weekdays.contains(&d.weekday()),
+                };
+                // 週は月曜始まり，同じ週の残りに該当する曜日が無ければ interval 週後の週へ
+                let rest_of_week = 6 - date.weekday().num_days_from_monday() as u64;
+                if let Some(d) = (1..=rest_of_week)
+                    .map_while(|n| date.checked_add_days(Days::new(n)))
+                    .find(|d| matches(*d))
+                {
+                    return Some(d);
+                }
+                let monday = date
+                    .checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))?
+                    .checked_add_days(Days::new(7 * *interval as u64))?;
+                (0..7)
+                    .map_while(|n| monday.checked_add_days(Days::new(n)))
+                    .find(|d| matches(*d))
+            }
+            Recurrence::Monthly { interval, day } => {
+                // 月末に丸めた日ではなく元の日を使い，31 日の繰り返しが 2 月以降 29 日にずれないようにする
+                let day = day.unwrap_or(anchor.day());
+                let this_month = on_day(date, day)?;
+                if this_month > date {
+                    return Some(this_month);
+                }
+                on_day(date.checked_add_months(Months::new(*interval))?, day)
+            }
+        }
+    }
+}
+
+/// `date` と同じ月の `day` 日，月末を超える場合は月末に丸める
+fn on_day(date: NaiveDate, day: u32) -> Option<NaiveDate> {
+    let first = date.with_day(1)?;
+    let last = first
+        .checked_add_months(Months::new(1))?
+        .checked_sub_days(Days::new(1))?
+        .day();
+    first.with_day(day.min(last))
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = match s.get(..6) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("RRULE:") => &s[6..],
+            _ => s,
+        };
+        let mut freq = None;
+        let mut interval = 1;
+        let mut weekdays = vec![];
+        let mut day = None;
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("invalid part [{}]", part))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => freq = Some(value.to_ascii_uppercase()),
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| (1..=1000).contains(interval))
+                        .ok_or_else(|| format!("invalid INTERVAL [{}]", value))?;
+                }
+                "BYDAY" => {
+                    weekdays = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<Vec<_>, _>>()?;
+                }
+                "BYMONTHDAY" => {
+                    day = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|day| (1..=31).contains(day))
+                            .ok_or_else(|| format!("invalid BYMONTHDAY [{}]", value))?,
+                    );
+                }
+                _ => return Err(format!("unsupported part [{}]", key)),
+            }
+        }
+
+        match freq.as_deref() {
+            Some("DAILY") if weekdays.is_empty() && day.is_none() => {
+                Ok(Recurrence::Daily { interval })
+            }
+            Some("WEEKLY") if day.is_none() => {
+                weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+                weekdays.dedup();
+                Ok(Recurrence::Weekly { interval, weekdays })
+            }
+            Some("MONTHLY") if weekdays.is_empty() => Ok(Recurrence::Monthly { interval, day }),
+            Some(freq @ ("DAILY" | "WEEKLY" | "MONTHLY")) => {
+                Err(format!("unsupported combination for FREQ={}", freq))
+            }
+            Some(freq) => Err(format!("unsupported FREQ [{}]", freq)),
+            None => Err("FREQ is required".to_string()),
+        }
+    }
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, String> {
+    match s.to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("invalid BYDAY [{}]", s)),
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let interval = match self {
+            Recurrence::Daily { interval }
+            | Recurrence::Weekly { interval, .. }
+            | Recurrence::Monthly { interval, .. } => *interval,
+        };
+        match self {
+            Recurrence::Daily { .. } => write!(f, "FREQ=DAILY")?,
+            Recurrence::Weekly { .. } => write!(f, "FREQ=WEEKLY")?,
+            Recurrence::Monthly { .. } => write!(f, "FREQ=MONTHLY")?,
+        }
+        if interval != 1 {
+            write!(f, ";INTERVAL={}", interval)?;
+        }
+        match self {
+            Recurrence::Weekly { weekdays, .. } if !weekdays.is_empty() => {
+                let days = weekdays
+                    .iter()
+                    .map(|weekday| weekday.to_string()[..2].to_ascii_uppercase())
+                    .collect::<Vec<_>>();
+                write!(f, ";BYDAY={}", days.join(","))
+            }
+            Recurrence::Monthly { day: Some(day), .. } => write!(f, ";BYMONTHDAY={}", day),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 保存する形に揃える，空文字は繰り返し無し
+pub fn normalize(rule: &str) -> anyhow::Result<Option<String>> {
+    if rule.trim().is_empty() {
+        return Ok(None);
+    }
+    let recurrence = rule.parse::<Recurrence>().map_err(anyhow::Error::msg)?;
+    Ok(Some(recurrence.to_string()))
+}
+
+/// `#[validate(custom = "...")]` 用
+pub fn validate_recurrence(rule: &str) -> Result<(), ValidationError> {
+    if rule.trim().is_empty() {
+        return Ok(());
+    }
+    rule.parse::<Recurrence>().map(|_| ()).map_err(|message| {
+        let mut error = ValidationError::new("recurrence");
+        error.message = Some(message.into());
+        error
+    })
+}
+
+/// `#[validate(custom = "...")]` 用
+pub fn validate_due_date(date: &NaiveDate) -> Result<(), ValidationError> {
+    if DUE_DATE_YEARS.contains(&date.year()) {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("due_date");
+    error.message = Some(
+        format!(
+            "year must be between {} and {}",
+            DUE_DATE_YEARS.start(),
+            DUE_DATE_YEARS.end()
+        )
+        .into(),
+    );
+    Err(error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn next(rule: &str, from: &str) -> NaiveDate {
+        rule.parse::<Recurrence>()
+            .unwrap()
+            .next_after(date(from), date(from))
+            .unwrap()
+    }
+
+    #[test]
+    fn should_parse_and_normalize() {
+        assert_eq!(
+            normalize("rrule:freq=weekly;byday=th,mo,th").unwrap(),
+            Some("FREQ=WEEKLY;BYDAY=MO,TH".to_string())
+        );
+        assert_eq!(
+            normalize("RRULE:FREQ=MONTHLY;INTERVAL=2;BYMONTHDAY=31").unwrap(),
+            Some("FREQ=MONTHLY;INTERVAL=2;BYMONTHDAY=31".to_string())
+        );
+        assert_eq!(normalize(" ").unwrap(), None);
+        assert!(normalize("FREQ=YEARLY").is_err());
+        assert!(normalize("FREQ=DAILY;BYDAY=MO").is_err());
+        assert!(normalize("FREQ=DAILY;INTERVAL=0").is_err());
+        assert!(normalize("FREQ=DAILY;COUNT=3").is_err());
+        assert!(normalize("INTERVAL=2").is_err());
+    }
+
+    #[test]
+    fn should_compute_next_daily() {
+        assert_eq!(next("FREQ=DAILY", "2023-12-31"), date("2024-01-01"));
+        assert_eq!(
+            next("FREQ=DAILY;INTERVAL=3", "2024-02-28"),
+            date("2024-03-02")
+        );
+    }
+
+    #[test]
+    fn should_compute_next_weekly() {
+        // 2024-01-01 は月曜日
+        assert_eq!(next("FREQ=WEEKLY", "2024-01-03"), date("2024-01-10"));
+        assert_eq!(
+            next("FREQ=WEEKLY;BYDAY=MO,TH", "2024-01-01"),
+            date("2024-01-04")
+        );
+        assert_eq!(
+            next("FREQ=WEEKLY;BYDAY=MO,TH", "2024-01-04"),
+            date("2024-01-08")
+        );
+        assert_eq!(
+            next("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH", "2024-01-04"),
+            date("2024-01-15")
+        );
+        assert_eq!(
+            next("FREQ=WEEKLY;BYDAY=SU", "2024-01-07"),
+            date("2024-01-14")
+        );
+    }
+
+    #[test]
+    fn should_compute_next_monthly() {
+        assert_eq!(next("FREQ=MONTHLY", "2024-01-15"), date("2024-02-15"));
+        assert_eq!(
+            next("FREQ=MONTHLY;BYMONTHDAY=20", "2024-01-15"),
+            date("2024-01-20")
+        );
+        // 月末を超える日は月末に丸める
+        assert_eq!(
+            next("FREQ=MONTHLY;BYMONTHDAY=31", "2024-01-31"),
+            date("2024-02-29")
+        );
+        assert_eq!(
+            next("FREQ=MONTHLY;BYMONTHDAY=31", "2024-02-29"),
+            date("2024-03-31")
+        );
+        assert_eq!(
+            next("FREQ=MONTHLY;INTERVAL=3;BYMONTHDAY=1", "2024-01-01"),
+            date("2024-04-01")
+        );
+    }
+
+    #[test]
+    fn should_keep_anchor_day_monthly() {
+        let monthly = "FREQ=MONTHLY".parse::<Recurrence>().unwrap();
+        let anchor = date("2024-01-31");
+        let february = monthly.next_after(anchor, anchor).unwrap();
+        assert_eq!(february, date("2024-02-29"));
+        let march = monthly.next_after(february, anchor).unwrap();
+        assert_eq!(march, date("2024-03-31"));
+        assert_eq!(monthly.next_after(march, anchor), Some(date("2024-04-30")));
+    }
+
+    #[test]
+    fn should_stop_at_end_of_date_range() {
+        let last = NaiveDate::MAX;
+        for rule in ["FREQ=DAILY", "FREQ=WEEKLY;BYDAY=MO", "FREQ=MONTHLY"] {
+            let recurrence = rule.parse::<Recurrence>().unwrap();
+            assert_eq!(recurrence.next_after(last, last), None, "{}", rule);
+        }
+        assert!(validate_due_date(&date("9999-12-31")).is_ok());
+        assert!(validate_due_date(&date("+10000-01-01")).is_err());
+        assert!(validate_due_date(&date("0000-12-31")).is_err());
+    }
+}
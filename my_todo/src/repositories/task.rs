@@ -1,9 +1,24 @@
+use std::collections::HashMap;
+
+use async_graphql::{InputObject, SimpleObject};
 use axum::async_trait;
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
-use validator::Validate;
+use sqlx::{
+    Executor, FromRow, PgConnection, PgPool, Postgres, Sqlite, SqliteConnection, SqlitePool,
+};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
 
-use super::{label::Label, RepositoryError};
+use super::{
+    file::{FileStore, Record, Touched},
+    label::Label,
+    limits::{optional_text_schema, text_schema, TEXT_MAX_LEN, TEXT_MIN_LEN},
+    memory::{MemoryStore, Tables, TaskRow},
+    position::{self, Placement},
+    recurrence::{normalize, validate_due_date, validate_recurrence, Recurrence},
+    RepositoryError,
+};
 
 #[derive(Debug, Clone)]
 pub struct TaskRepositoryForDb {
@@ -14,21 +29,31 @@ impl TaskRepositoryForDb {
     pub fn new(pool: PgPool) -> Self {
         TaskRepositoryForDb { pool }
     }
-}
 
-#[async_trait]
-impl TaskRepository for TaskRepositoryForDb {
-    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
-        let tx = self.pool.begin().await?;
-        let row = sqlx::query_as::<_, TaskFromRow>(
+    /// 新しいタスクは先頭に置く，同時に作られてキーが重なった場合は id の降順になる
+    async fn insert(conn: &mut PgConnection, payload: CreateTask) -> anyhow::Result<i32> {
+        Self::check_references(conn, &payload.labels, payload.project_id).await?;
+        let first = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+                select min(position) from tasks
+            "#,
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        let id = sqlx::query_scalar::<_, i32>(
             r#"
-                insert into tasks (text, completed)
-                values ($1, false)
-                returning *;
+                insert into tasks (text, completed, recurrence, due_date, series_id, position, project_id)
+                values ($1, false, $2, $3, $4, $5, $6)
+                returning id;
             "#,
         )
-        .bind(payload.text.clone())
-        .fetch_one(&self.pool)
+        .bind(payload.text)
+        .bind(stored_recurrence(payload.recurrence.as_deref())?)
+        .bind(payload.due_date)
+        .bind(payload.series_id)
+        .bind(position::before(first.as_deref()))
+        .bind(payload.project_id)
+        .fetch_one(&mut *conn)
         .await?;
 
         sqlx::query(
@@ -38,23 +63,157 @@ impl TaskRepository for TaskRepositoryForDb {
                 from unnest($2) as t(id);
             "#,
         )
-        .bind(row.id)
+        .bind(id)
         .bind(payload.labels)
-        .execute(&self.pool)
+        .execute(&mut *conn)
         .await?;
+        Ok(id)
+    }
 
-        tx.commit().await?;
+    /// 外部キー制約の違反からは id が分からないため，存在しないラベルとプロジェクトを先に弾く
+    async fn check_references(
+        conn: &mut PgConnection,
+        labels: &[i32],
+        project_id: Option<i32>,
+    ) -> anyhow::Result<()> {
+        let missing = sqlx::query_scalar::<_, i32>(
+            r#"
+                select id from unnest($1) as t(id)
+                where id not in (select id from labels)
+                limit 1
+            "#,
+        )
+        .bind(labels)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(id) = missing {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        if let Some(id) = project_id {
+            let exists = sqlx::query_scalar::<_, bool>(
+                r#"
+                    select exists (select 1 from projects where id = $1)
+                "#,
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+            if !exists {
+                return Err(RepositoryError::NotFound(id).into());
+            }
+        }
+        Ok(())
+    }
 
-        let task = self.find(row.id).await?;
-        Ok(task)
+    /// 移動先の両隣のキー，None は先頭または末尾
+    /// 移動するタスクと両隣の行だけをロックし，書き換えるまで他の移動を待たせる
+    async fn neighbours(
+        conn: &mut PgConnection,
+        id: i32,
+        after: Option<i32>,
+        before: Option<i32>,
+    ) -> anyhow::Result<(Option<String>, Option<String>)> {
+        let locked = sqlx::query_as::<_, (i32, String)>(
+            r#"
+                select id, position from tasks where id = any($1) for update
+            "#,
+        )
+        .bind(
+            [Some(id), after, before]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>(),
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        let key_of = |target: i32| {
+            locked
+                .iter()
+                .find(|(task_id, _)| *task_id == target)
+                .map(|(_, key)| key.clone())
+                .ok_or(RepositoryError::NotFound(target))
+        };
+        key_of(id)?;
+        if after == Some(id) || before == Some(id) {
+            return Err(RepositoryError::Conflict(id).into());
+        }
+        match (after, before) {
+            (Some(after), before) => {
+                let lower = key_of(after)?;
+                let next = sqlx::query_as::<_, (i32, String)>(
+                    r#"
+                        select id, position from tasks
+                        where id <> $1 and (position > $2 or (position = $2 and id < $3))
+                        order by position asc, id desc
+                        limit 1
+                        for update
+                    "#,
+                )
+                .bind(id)
+                .bind(&lower)
+                .bind(after)
+                .fetch_optional(&mut *conn)
+                .await?;
+                if before.is_some() && next.as_ref().map(|(task_id, _)| *task_id) != before {
+                    return Err(RepositoryError::Conflict(id).into());
+                }
+                Ok((Some(lower), next.map(|(_, key)| key)))
+            }
+            (None, Some(before)) => {
+                let upper = key_of(before)?;
+                let previous = sqlx::query_scalar::<_, String>(
+                    r#"
+                        select position from tasks
+                        where id <> $1 and (position < $2 or (position = $2 and id > $3))
+                        order by position desc, id asc
+                        limit 1
+                        for update
+                    "#,
+                )
+                .bind(id)
+                .bind(&upper)
+                .bind(before)
+                .fetch_optional(&mut *conn)
+                .await?;
+                Ok((previous, Some(upper)))
+            }
+            (None, None) => Err(RepositoryError::Conflict(id).into()),
+        }
     }
-    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+
+    /// 繰り返しの最初のタスクの期日，最初のタスクが削除済みなら None
+    async fn first_due_date(
+        conn: &mut PgConnection,
+        task: &TaskEntity,
+    ) -> anyhow::Result<Option<NaiveDate>> {
+        let Some(series_id) = task.series_id else {
+            return Ok(None);
+        };
+        let due_date = sqlx::query_scalar::<_, Option<NaiveDate>>(
+            r#"
+                select due_date from tasks where id = $1
+            "#,
+        )
+        .bind(series_id)
+        .fetch_optional(conn)
+        .await?;
+        Ok(due_date.flatten())
+    }
+
+    pub(super) async fn select<'e, E>(executor: E, id: i32) -> anyhow::Result<TaskEntity>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let items = sqlx::query_as::<_, TaskWithLabelFromRow>(
             r#"
                 select 
                     tasks.*, 
                     labels.id as label_id, 
-                    labels.name as label_name 
+                    labels.name as label_name,
+                    labels.color as label_color,
+                    labels.description as label_description,
+                    labels.position as label_position,
+                    labels.parent_id as label_parent_id
                 from 
                     tasks 
                     left outer join task_labels as tl
@@ -67,7 +226,7 @@ impl TaskRepository for TaskRepositoryForDb {
             "#,
         )
         .bind(id)
-        .fetch_all(&self.pool)
+        .fetch_all(executor)
         .await
         .map_err(|e| match e {
             sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
@@ -78,13 +237,32 @@ impl TaskRepository for TaskRepositoryForDb {
         let task = tasks.first().ok_or(RepositoryError::NotFound(id))?;
         Ok(task.clone())
     }
+}
+
+#[async_trait]
+impl TaskRepository for TaskRepositoryForDb {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::insert(&mut tx, payload).await?;
+        tx.commit().await?;
+
+        let task = self.find(id).await?;
+        Ok(task)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        Self::select(&self.pool, id).await
+    }
     async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
         let tasks = sqlx::query_as::<_, TaskWithLabelFromRow>(
             r#"
                 select 
                     tasks.*, 
                     labels.id as label_id, 
-                    labels.name as label_name 
+                    labels.name as label_name,
+                    labels.color as label_color,
+                    labels.description as label_description,
+                    labels.position as label_position,
+                    labels.parent_id as label_parent_id
                 from 
                     tasks 
                     left outer join task_labels as tl
@@ -92,63 +270,200 @@ impl TaskRepository for TaskRepositoryForDb {
                     left outer join labels
                         on tl.label_id = labels.id
                 order by
-                    tasks.id desc
+                    tasks.position asc,
+                    tasks.id desc,
+                    labels.id asc
             "#,
         )
         .fetch_all(&self.pool)
         .await?;
         Ok(fold_entities(tasks))
     }
-    async fn update(&self, id: i32, payload: UpdateTask) -> anyhow::Result<TaskEntity> {
-        let tx = self.pool.begin().await?;
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        let mut tx = self.pool.begin().await?;
 
-        let old_task = self.find(id).await?;
+        // 完了の切り替わりを確実に 1 回だけ見るため，更新が終わるまで他の更新を待たせる
+        let old_task = sqlx::query_as::<_, TaskFromRow>(
+            r#"
+                select * from tasks where id = $1 for update
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Self::check_references(
+            &mut tx,
+            payload.labels.as_deref().unwrap_or_default(),
+            payload.project_id.filter(|project_id| *project_id != 0),
+        )
+        .await?;
+        // If-Match が指定された場合のみ更新条件に version を含める
         sqlx::query(
             r#"
                 update tasks
-                set text = $1, completed = $2
-                where id = $3
-                returning * 
+                set text = $1, completed = $2, recurrence = $3, due_date = $4, project_id = $5,
+                    version = version + 1
+                where id = $6 and ($7::int is null or version = $7)
+                returning *
             "#,
         )
         .bind(payload.text.unwrap_or(old_task.text))
         .bind(payload.completed.unwrap_or(old_task.completed))
+        .bind(match payload.recurrence {
+            Some(rule) => normalize(&rule)?,
+            None => old_task.recurrence,
+        })
+        .bind(payload.due_date.or(old_task.due_date))
+        .bind(stored_project(payload.project_id, old_task.project_id))
         .bind(id)
-        .fetch_one(&self.pool)
-        .await?;
+        .bind(version)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(match version {
+            Some(_) => RepositoryError::VersionMismatch(id),
+            None => RepositoryError::NotFound(id),
+        })?;
         if let Some(labels) = payload.labels {
-            // task's label update
-            // 一度関連するレコードを削除
+            // 外したラベルだけ削除し，付け直さなかったラベルは付けた日時を残す
             sqlx::query(
                 r#"
-                    delete from task_labels where task_id = $1
+                    delete from task_labels where task_id = $1 and label_id <> all($2)
                 "#,
             )
             .bind(id)
-            .execute(&self.pool)
+            .bind(&labels)
+            .execute(&mut tx)
             .await?;
 
             sqlx::query(
                 r#"
                     insert into task_labels (task_id, label_id)
                     select $1, id
-                    from unnest($2) as t(id);
+                    from unnest($2) as t(id)
+                    where id not in (select label_id from task_labels where task_id = $1);
                 "#,
             )
             .bind(id)
             .bind(labels)
-            .execute(&self.pool)
+            .execute(&mut tx)
             .await?;
         }
 
+        // 次の繰り返しのタスクも同じトランザクションで作る
+        let task = Self::select(&mut tx, id).await?;
+        let first_due_date = Self::first_due_date(&mut tx, &task).await?;
+        let next = match next_on_completion(old_task.completed, &task, first_due_date) {
+            Some(next) => {
+                let next_id = Self::insert(&mut tx, next).await?;
+                Some(Self::select(&mut tx, next_id).await?)
+            }
+            None => None,
+        };
         tx.commit().await?;
+
+        Ok((task, next))
+    }
+
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
         let task = self.find(id).await?;
+        let tasks = sqlx::query_as::<_, TaskWithLabelFromRow>(
+            r#"
+                select
+                    tasks.*,
+                    labels.id as label_id,
+                    labels.name as label_name,
+                    labels.color as label_color,
+                    labels.description as label_description,
+                    labels.position as label_position,
+                    labels.parent_id as label_parent_id
+                from
+                    tasks
+                    left outer join task_labels as tl
+                        on tasks.id = tl.task_id
+                    left outer join labels
+                        on tl.label_id = labels.id
+                where tasks.id = $1 or tasks.series_id = $1
+                order by
+                    tasks.id asc,
+                    labels.id asc
+            "#,
+        )
+        .bind(task.series_id.unwrap_or(task.id))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(fold_entities(tasks))
+    }
 
-        Ok(task)
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        let mut tx = self.pool.begin().await?;
+        let (lower, upper) = Self::neighbours(&mut tx, id, payload.after, payload.before).await?;
+        match position::between(lower.as_deref(), upper.as_deref())
+            .filter(|key| key.len() <= position::MAX_LEN)
+        {
+            Some(key) => {
+                sqlx::query(
+                    r#"
+                        update tasks set position = $1 where id = $2
+                    "#,
+                )
+                .bind(key)
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+            }
+            // キーが作れない時だけ全体をロックして振り直す
+            None => {
+                let order = sqlx::query_as::<_, (i32, String)>(
+                    r#"
+                        select id, position from tasks
+                        order by position asc, id desc
+                        for update
+                    "#,
+                )
+                .fetch_all(&mut tx)
+                .await?;
+                let keys = match position::place(&order, id, payload.after, payload.before)? {
+                    Placement::Key(key) => vec![(id, key)],
+                    Placement::Rebalance(keys) => keys,
+                };
+                let (ids, keys): (Vec<i32>, Vec<String>) = keys.into_iter().unzip();
+                sqlx::query(
+                    r#"
+                        update tasks set position = t.position
+                        from unnest($1::int4[], $2::text[]) as t(id, position)
+                        where tasks.id = t.id
+                    "#,
+                )
+                .bind(ids)
+                .bind(keys)
+                .execute(&mut tx)
+                .await?;
+            }
+        }
+        tx.commit().await?;
+        self.find(id).await
     }
 
-    async fn delete(&self, id: i32) -> anyhow::Result<()> {
-        let tx = self.pool.begin().await?;
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_scalar::<_, i32>(
+            r#"
+                select version from tasks where id=$1 for update
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        if version.is_some_and(|version| version != current) {
+            return Err(RepositoryError::VersionMismatch(id).into());
+        }
         // task's label delete
         sqlx::query(
             r#"
@@ -156,12 +471,9 @@ impl TaskRepository for TaskRepositoryForDb {
             "#,
         )
         .bind(id)
-        .execute(&self.pool)
+        .execute(&mut tx)
         .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
         // task delete
         sqlx::query(
             r#"
@@ -169,143 +481,1312 @@ impl TaskRepository for TaskRepositoryForDb {
             "#,
         )
         .bind(id)
-        .execute(&self.pool)
+        .execute(&mut tx)
         .await
-        .map_err(|e| match e {
-            sqlx::Error::RowNotFound => RepositoryError::NotFound(id),
-            _ => RepositoryError::Unexpected(e.to_string()),
-        })?;
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
 
         tx.commit().await?;
 
         Ok(())
     }
-}
 
-#[async_trait]
-pub trait TaskRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
-    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity>;
-    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity>;
-    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>>;
-    async fn update(&self, id: i32, payload: UpdateTask) -> anyhow::Result<TaskEntity>;
-    async fn delete(&self, id: i32) -> anyhow::Result<()>;
-}
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        let tasks = sqlx::query_as::<_, TaskRecordRow>(
+            r#"
+                select id, text, completed, version, recurrence, due_date, series_id, project_id, position
+                from tasks
+                order by id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let task_labels = sqlx::query_as::<_, (i32, i32, Option<DateTime<Utc>>)>(
+            r#"
+                select task_id, label_id, attached_at
+                from task_labels
+                order by task_id asc, label_id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(with_labels(tasks, task_labels))
+    }
 
-#[derive(Clone, PartialEq, Eq, FromRow)]
-struct TaskFromRow {
-    id: i32,
-    text: String,
-    completed: bool,
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        let mut tx = self.pool.begin().await?;
+        Self::check_references(&mut tx, &record.labels, record.project_id).await?;
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"
+                insert into tasks
+                    (text, completed, version, recurrence, due_date, series_id, project_id, position)
+                values ($1, $2, $3, $4, $5, $6, $7, $8)
+                returning id
+            "#,
+        )
+        .bind(&record.text)
+        .bind(record.completed)
+        .bind(record.version)
+        .bind(&record.recurrence)
+        .bind(record.due_date)
+        .bind(record.series_id)
+        .bind(record.project_id)
+        .bind(&record.position)
+        .fetch_one(&mut tx)
+        .await?;
+        // 付けた日時は統合で残す値になるので，既定値の現在時刻にせずそのまま戻す
+        for (label_id, attached_at) in record.labels_with_attached_at() {
+            sqlx::query(
+                r#"
+                    insert into task_labels (task_id, label_id, attached_at)
+                    values ($1, $2, $3)
+                "#,
+            )
+            .bind(id)
+            .bind(label_id)
+            .bind(attached_at)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.find(id).await
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
-pub struct TaskWithLabelFromRow {
-    id: i32,
-    text: String,
-    completed: bool,
-    label_id: Option<i32>,
-    label_name: Option<String>,
+#[derive(Debug, Clone)]
+pub struct TaskRepositoryForSqlite {
+    pool: SqlitePool,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
-pub struct TaskEntity {
-    pub id: i32,
-    pub text: String,
-    pub completed: bool,
-    pub labels: Vec<Label>,
-}
+impl TaskRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        TaskRepositoryForSqlite { pool }
+    }
 
-fn fold_entities(rows: Vec<TaskWithLabelFromRow>) -> Vec<TaskEntity> {
-    let mut rows = rows.iter();
-    let mut accum: Vec<TaskEntity> = vec![];
-    'outer: while let Some(row) = rows.next() {
-        let mut tasks = accum.iter_mut();
-        while let Some(task) = tasks.next() {
-            // id が一致 = Task に紐づくラベルが複数存在している
-            if task.id == row.id {
-                task.labels.push(Label {
-                    id: row.label_id.unwrap(),
-                    name: row.label_name.clone().unwrap(),
-                });
-                continue 'outer;
+    async fn insert(conn: &mut SqliteConnection, payload: CreateTask) -> anyhow::Result<i32> {
+        Self::check_references(conn, &payload.labels, payload.project_id).await?;
+        let first = sqlx::query_scalar::<_, Option<String>>(
+            r#"
+                select min(position) from tasks
+            "#,
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"
+                insert into tasks (text, completed, recurrence, due_date, series_id, position, project_id)
+                values (?1, false, ?2, ?3, ?4, ?5, ?6)
+                returning id;
+            "#,
+        )
+        .bind(payload.text)
+        .bind(stored_recurrence(payload.recurrence.as_deref())?)
+        .bind(payload.due_date)
+        .bind(payload.series_id)
+        .bind(position::before(first.as_deref()))
+        .bind(payload.project_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+        // SQLite には unnest が無いため，ラベルの id は JSON 配列として渡す
+        sqlx::query(
+            r#"
+                insert into task_labels (task_id, label_id, attached_at)
+                select ?1, value, current_timestamp
+                from json_each(?2);
+            "#,
+        )
+        .bind(id)
+        .bind(serde_json::to_string(&payload.labels)?)
+        .execute(&mut *conn)
+        .await?;
+        Ok(id)
+    }
+
+    /// 外部キー制約の違反からは id が分からないため，存在しないラベルとプロジェクトを先に弾く
+    async fn check_references(
+        conn: &mut SqliteConnection,
+        labels: &[i32],
+        project_id: Option<i32>,
+    ) -> anyhow::Result<()> {
+        let missing = sqlx::query_scalar::<_, i32>(
+            r#"
+                select value from json_each(?1)
+                where value not in (select id from labels)
+                limit 1
+            "#,
+        )
+        .bind(serde_json::to_string(labels)?)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if let Some(id) = missing {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+        if let Some(id) = project_id {
+            let exists = sqlx::query_scalar::<_, bool>(
+                r#"
+                    select exists (select 1 from projects where id = ?1)
+                "#,
+            )
+            .bind(id)
+            .fetch_one(&mut *conn)
+            .await?;
+            if !exists {
+                return Err(RepositoryError::NotFound(id).into());
             }
         }
+        Ok(())
+    }
 
-        // Task の id に一致がなかった時のみ到達， TaskEntity を作成
-        let labels = if row.label_id.is_some() {
-            vec![Label {
-                id: row.label_id.unwrap(),
-                name: row.label_name.clone().unwrap(),
-            }]
-        } else {
-            vec![]
+    /// 繰り返しの最初のタスクの期日，最初のタスクが削除済みなら None
+    async fn first_due_date(
+        conn: &mut SqliteConnection,
+        task: &TaskEntity,
+    ) -> anyhow::Result<Option<NaiveDate>> {
+        let Some(series_id) = task.series_id else {
+            return Ok(None);
         };
-
-        accum.push(TaskEntity {
-            id: row.id,
-            text: row.text.clone(),
-            completed: row.completed,
-            labels,
-        });
+        let due_date = sqlx::query_scalar::<_, Option<NaiveDate>>(
+            r#"
+                select due_date from tasks where id = ?1
+            "#,
+        )
+        .bind(series_id)
+        .fetch_optional(conn)
+        .await?;
+        Ok(due_date.flatten())
     }
-    accum
-}
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
-pub struct CreateTask {
-    #[validate(length(min = 1, message = "Can not be empty"))]
-    #[validate(length(max = 100, message = "Over text length"))]
-    text: String,
-    labels: Vec<i32>,
-}
+    pub(super) async fn select<'e, E>(executor: E, id: i32) -> anyhow::Result<TaskEntity>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let items = sqlx::query_as::<_, TaskWithLabelFromRow>(
+            r#"
+                select
+                    tasks.*,
+                    labels.id as label_id,
+                    labels.name as label_name,
+                    labels.color as label_color,
+                    labels.description as label_description,
+                    labels.position as label_position,
+                    labels.parent_id as label_parent_id
+                from
+                    tasks
+                    left outer join task_labels as tl
+                        on tasks.id = tl.task_id
+                    left outer join labels
+                        on tl.label_id = labels.id
+                where tasks.id = ?1
+                order by
+                    labels.id asc
+            "#,
+        )
+        .bind(id)
+        .fetch_all(executor)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate)]
-pub struct UpdateTask {
-    #[validate(length(min = 1, message = "Can not be empty"))]
-    #[validate(length(max = 100, message = "Over text length"))]
-    text: Option<String>,
-    completed: Option<bool>,
-    labels: Option<Vec<i32>>,
+        let tasks = fold_entities(items);
+        let task = tasks.first().ok_or(RepositoryError::NotFound(id))?;
+        Ok(task.clone())
+    }
 }
 
-#[cfg(test)]
-#[cfg(feature = "database-test")]
-mod test {
-    use super::*;
-    use dotenv::dotenv;
-    use std::env;
+#[async_trait]
+impl TaskRepository for TaskRepositoryForSqlite {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        let mut tx = self.pool.begin().await?;
+        let id = Self::insert(&mut tx, payload).await?;
+        tx.commit().await?;
 
-    #[test]
-    fn fold_entities_test() {
-        let label_1 = Label {
-            id: 1,
-            name: String::from("label 1"),
-        };
-        let label_2 = Label {
-            id: 2,
-            name: String::from("label 2"),
-        };
-        let rows = vec![
-            TaskWithLabelFromRow {
-                id: 1,
-                text: String::from("task 1"),
-                completed: false,
-                label_id: Some(label_1.id),
-                label_name: Some(label_1.name.clone()),
+        let task = self.find(id).await?;
+        Ok(task)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        Self::select(&self.pool, id).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        let tasks = sqlx::query_as::<_, TaskWithLabelFromRow>(
+            r#"
+                select
+                    tasks.*,
+                    labels.id as label_id,
+                    labels.name as label_name,
+                    labels.color as label_color,
+                    labels.description as label_description,
+                    labels.position as label_position,
+                    labels.parent_id as label_parent_id
+                from
+                    tasks
+                    left outer join task_labels as tl
+                        on tasks.id = tl.task_id
+                    left outer join labels
+                        on tl.label_id = labels.id
+                order by
+                    tasks.position asc,
+                    tasks.id desc,
+                    labels.id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(fold_entities(tasks))
+    }
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        let mut tx = self.pool.begin().await?;
+
+        let old_task = sqlx::query_as::<_, TaskFromRow>(
+            r#"
+                select * from tasks where id = ?1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        Self::check_references(
+            &mut tx,
+            payload.labels.as_deref().unwrap_or_default(),
+            payload.project_id.filter(|project_id| *project_id != 0),
+        )
+        .await?;
+        sqlx::query(
+            r#"
+                update tasks
+                set text = ?1, completed = ?2, recurrence = ?3, due_date = ?4, project_id = ?5,
+                    version = version + 1
+                where id = ?6 and (?7 is null or version = ?7)
+                returning *
+            "#,
+        )
+        .bind(payload.text.unwrap_or(old_task.text))
+        .bind(payload.completed.unwrap_or(old_task.completed))
+        .bind(match payload.recurrence {
+            Some(rule) => normalize(&rule)?,
+            None => old_task.recurrence,
+        })
+        .bind(payload.due_date.or(old_task.due_date))
+        .bind(stored_project(payload.project_id, old_task.project_id))
+        .bind(id)
+        .bind(version)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(match version {
+            Some(_) => RepositoryError::VersionMismatch(id),
+            None => RepositoryError::NotFound(id),
+        })?;
+        if let Some(labels) = payload.labels {
+            let labels = serde_json::to_string(&labels)?;
+            sqlx::query(
+                r#"
+                    delete from task_labels
+                    where task_id = ?1 and label_id not in (select value from json_each(?2))
+                "#,
+            )
+            .bind(id)
+            .bind(&labels)
+            .execute(&mut tx)
+            .await?;
+
+            sqlx::query(
+                r#"
+                    insert into task_labels (task_id, label_id, attached_at)
+                    select ?1, value, current_timestamp
+                    from json_each(?2)
+                    where value not in (select label_id from task_labels where task_id = ?1);
+                "#,
+            )
+            .bind(id)
+            .bind(labels)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        // 次の繰り返しのタスクも同じトランザクションで作る
+        let task = Self::select(&mut tx, id).await?;
+        let first_due_date = Self::first_due_date(&mut tx, &task).await?;
+        let next = match next_on_completion(old_task.completed, &task, first_due_date) {
+            Some(next) => {
+                let next_id = Self::insert(&mut tx, next).await?;
+                Some(Self::select(&mut tx, next_id).await?)
+            }
+            None => None,
+        };
+        tx.commit().await?;
+
+        Ok((task, next))
+    }
+
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+        let task = self.find(id).await?;
+        let tasks = sqlx::query_as::<_, TaskWithLabelFromRow>(
+            r#"
+                select
+                    tasks.*,
+                    labels.id as label_id,
+                    labels.name as label_name,
+                    labels.color as label_color,
+                    labels.description as label_description,
+                    labels.position as label_position,
+                    labels.parent_id as label_parent_id
+                from
+                    tasks
+                    left outer join task_labels as tl
+                        on tasks.id = tl.task_id
+                    left outer join labels
+                        on tl.label_id = labels.id
+                where tasks.id = ?1 or tasks.series_id = ?1
+                order by
+                    tasks.id asc,
+                    labels.id asc
+            "#,
+        )
+        .bind(task.series_id.unwrap_or(task.id))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(fold_entities(tasks))
+    }
+
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        let mut tx = self.pool.begin().await?;
+        let order = sqlx::query_as::<_, (i32, String)>(
+            r#"
+                select id, position from tasks
+                order by position asc, id desc
+            "#,
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        let keys = match position::place(&order, id, payload.after, payload.before)? {
+            Placement::Key(key) => vec![(id, key)],
+            Placement::Rebalance(keys) => keys,
+        };
+        for (id, key) in keys {
+            sqlx::query(
+                r#"
+                    update tasks set position = ?1 where id = ?2
+                "#,
+            )
+            .bind(key)
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.find(id).await
+    }
+
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let current = sqlx::query_scalar::<_, i32>(
+            r#"
+                select version from tasks where id = ?1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut tx)
+        .await?
+        .ok_or(RepositoryError::NotFound(id))?;
+        if version.is_some_and(|version| version != current) {
+            return Err(RepositoryError::VersionMismatch(id).into());
+        }
+        sqlx::query(
+            r#"
+                delete from task_labels where task_id = ?1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+        sqlx::query(
+            r#"
+                delete from tasks where id = ?1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await
+        .map_err(|e| RepositoryError::Unexpected(e.to_string()))?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        let tasks = sqlx::query_as::<_, TaskRecordRow>(
+            r#"
+                select id, text, completed, version, recurrence, due_date, series_id, project_id, position
+                from tasks
+                order by id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let task_labels = sqlx::query_as::<_, (i32, i32, Option<DateTime<Utc>>)>(
+            r#"
+                select task_id, label_id, attached_at
+                from task_labels
+                order by task_id asc, label_id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(with_labels(tasks, task_labels))
+    }
+
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        let mut tx = self.pool.begin().await?;
+        Self::check_references(&mut tx, &record.labels, record.project_id).await?;
+        let id = sqlx::query_scalar::<_, i32>(
+            r#"
+                insert into tasks
+                    (text, completed, version, recurrence, due_date, series_id, project_id, position)
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                returning id
+            "#,
+        )
+        .bind(&record.text)
+        .bind(record.completed)
+        .bind(record.version)
+        .bind(&record.recurrence)
+        .bind(record.due_date)
+        .bind(record.series_id)
+        .bind(record.project_id)
+        .bind(&record.position)
+        .fetch_one(&mut tx)
+        .await?;
+        // 付けた日時は統合で残す値になるので，既定値の現在時刻にせずそのまま戻す
+        for (label_id, attached_at) in record.labels_with_attached_at() {
+            sqlx::query(
+                r#"
+                    insert into task_labels (task_id, label_id, attached_at)
+                    values (?1, ?2, ?3)
+                "#,
+            )
+            .bind(id)
+            .bind(label_id)
+            .bind(attached_at)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        self.find(id).await
+    }
+}
+
+/// `--storage=memory` 用，プロセス終了でデータは消える
+#[derive(Debug, Clone)]
+pub struct TaskRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl TaskRepositoryForMemory {
+    pub fn with_store(store: MemoryStore) -> Self {
+        Self { store }
+    }
+
+    /// 並び順を変え，キーを書き換えたタスクの id を返す
+    fn move_with(&self, id: i32, payload: &MoveTask) -> anyhow::Result<Vec<i32>> {
+        let mut tables = self.store.write();
+        let keys = memory_placement(&tables, id, payload)?;
+        let ids = keys.iter().map(|(id, _)| *id).collect();
+        for (id, key) in keys {
+            if let Some(row) = tables.tasks.get_mut(&id) {
+                row.position = key;
+            }
+        }
+        Ok(ids)
+    }
+}
+
+/// 書き換える (id, キー)，キーを詰め直す場合は複数になる
+fn memory_placement(
+    tables: &Tables,
+    id: i32,
+    payload: &MoveTask,
+) -> Result<Vec<(i32, String)>, RepositoryError> {
+    let order = memory_order(tables);
+    Ok(
+        match position::place(&order, id, payload.after, payload.before)? {
+            Placement::Key(key) => vec![(id, key)],
+            Placement::Rebalance(keys) => keys,
+        },
+    )
+}
+
+/// DB 実装と同じくキーの昇順，同じキーは id の降順
+fn memory_order(tables: &Tables) -> Vec<(i32, String)> {
+    let mut order: Vec<(i32, String)> = tables
+        .tasks
+        .iter()
+        .map(|(id, row)| (*id, row.position.clone()))
+        .collect();
+    order.sort_by(|(a_id, a), (b_id, b)| a.cmp(b).then(b_id.cmp(a_id)));
+    order
+}
+
+pub(super) fn memory_entity(tables: &Tables, id: i32) -> Option<TaskEntity> {
+    let row = tables.tasks.get(&id)?;
+    Some(TaskEntity {
+        id,
+        text: row.text.clone(),
+        completed: row.completed,
+        version: row.version,
+        labels: tables.labels_of(id),
+        recurrence: row.recurrence.clone(),
+        due_date: row.due_date,
+        series_id: row.series_id,
+        project_id: row.project_id,
+    })
+}
+
+fn memory_record(tables: &Tables, id: i32) -> Option<TaskRecord> {
+    let row = tables.tasks.get(&id)?;
+    let task_labels = tables.task_labels.range((id, i32::MIN)..=(id, i32::MAX));
+    Some(TaskRecord {
+        id,
+        text: row.text.clone(),
+        completed: row.completed,
+        version: row.version,
+        labels: task_labels
+            .clone()
+            .map(|((_, label_id), _)| *label_id)
+            .collect(),
+        attached_at: task_labels
+            .filter_map(|((_, label_id), at)| Some((*label_id, (*at)?)))
+            .collect(),
+        recurrence: row.recurrence.clone(),
+        due_date: row.due_date,
+        series_id: row.series_id,
+        project_id: row.project_id,
+        position: row.position.clone(),
+    })
+}
+
+fn memory_insert(tables: &mut Tables, payload: CreateTask) -> anyhow::Result<i32> {
+    let recurrence = stored_recurrence(payload.recurrence.as_deref())?;
+    let position = position::before(tables.tasks.values().map(|row| row.position.as_str()).min());
+    tables.check_project(payload.project_id)?;
+    let id = tables.next_task_id();
+    tables.set_task_labels(id, &payload.labels)?;
+    tables.tasks.insert(
+        id,
+        TaskRow {
+            text: payload.text,
+            completed: false,
+            version: 1,
+            recurrence,
+            due_date: payload.due_date,
+            series_id: payload.series_id,
+            position,
+            project_id: payload.project_id,
+        },
+    );
+    Ok(id)
+}
+
+#[async_trait]
+impl TaskRepository for TaskRepositoryForMemory {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        let mut tables = self.store.write();
+        let id = memory_insert(&mut tables, payload)?;
+        Ok(memory_entity(&tables, id).unwrap())
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        let tables = self.store.read();
+        let task = memory_entity(&tables, id).ok_or(RepositoryError::NotFound(id))?;
+        Ok(task)
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        let tables = self.store.read();
+        let tasks = memory_order(&tables)
+            .iter()
+            .filter_map(|(id, _)| memory_entity(&tables, *id))
+            .collect();
+        Ok(tasks)
+    }
+
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        let mut tables = self.store.write();
+        let old = tables
+            .tasks
+            .get(&id)
+            .cloned()
+            .ok_or(RepositoryError::NotFound(id))?;
+        if version.is_some_and(|version| version != old.version) {
+            return Err(RepositoryError::VersionMismatch(id).into());
+        }
+        let recurrence = match payload.recurrence {
+            Some(rule) => normalize(&rule)?,
+            None => old.recurrence,
+        };
+        let project_id = stored_project(payload.project_id, old.project_id);
+        tables.check_project(project_id)?;
+        if let Some(labels) = payload.labels {
+            tables.set_task_labels(id, &labels)?;
+        }
+        tables.tasks.insert(
+            id,
+            TaskRow {
+                text: payload.text.unwrap_or(old.text),
+                completed: payload.completed.unwrap_or(old.completed),
+                version: old.version + 1,
+                recurrence,
+                due_date: payload.due_date.or(old.due_date),
+                series_id: old.series_id,
+                position: old.position,
+                project_id,
+            },
+        );
+        let task = memory_entity(&tables, id).unwrap();
+        let first_due_date = task
+            .series_id
+            .and_then(|series_id| tables.tasks.get(&series_id))
+            .and_then(|first| first.due_date);
+        let next = match next_on_completion(old.completed, &task, first_due_date) {
+            Some(next) => {
+                let next_id = memory_insert(&mut tables, next)?;
+                memory_entity(&tables, next_id)
+            }
+            None => None,
+        };
+        Ok((task, next))
+    }
+
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+        let tables = self.store.read();
+        let task = memory_entity(&tables, id).ok_or(RepositoryError::NotFound(id))?;
+        let series_id = task.series_id.unwrap_or(task.id);
+        let tasks = tables
+            .tasks
+            .iter()
+            .filter(|(id, row)| **id == series_id || row.series_id == Some(series_id))
+            .filter_map(|(id, _)| memory_entity(&tables, *id))
+            .collect();
+        Ok(tasks)
+    }
+
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        self.move_with(id, &payload)?;
+        self.find(id).await
+    }
+
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        let mut tables = self.store.write();
+        let current = tables
+            .tasks
+            .get(&id)
+            .map(|task| task.version)
+            .ok_or(RepositoryError::NotFound(id))?;
+        if version.is_some_and(|version| version != current) {
+            return Err(RepositoryError::VersionMismatch(id).into());
+        }
+        tables.task_labels.retain(|(task_id, _), _| *task_id != id);
+        // DB では外部キーで消える
+        tables.deliveries.retain(|delivery| delivery.task_id != id);
+        tables.tasks.remove(&id);
+        Ok(())
+    }
+
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        let tables = self.store.read();
+        Ok(tables
+            .tasks
+            .keys()
+            .filter_map(|id| memory_record(&tables, *id))
+            .collect())
+    }
+
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        let mut tables = self.store.write();
+        tables.check_project(record.project_id)?;
+        if let Some(id) = record
+            .labels
+            .iter()
+            .find(|id| !tables.labels.contains_key(id))
+        {
+            return Err(RepositoryError::NotFound(*id).into());
+        }
+        let id = tables.next_task_id();
+        for (label_id, attached_at) in record.labels_with_attached_at() {
+            tables.task_labels.insert((id, label_id), attached_at);
+        }
+        tables.tasks.insert(
+            id,
+            TaskRow {
+                text: record.text,
+                completed: record.completed,
+                version: record.version,
+                recurrence: record.recurrence,
+                due_date: record.due_date,
+                series_id: record.series_id,
+                position: record.position,
+                project_id: record.project_id,
+            },
+        );
+        Ok(memory_entity(&tables, id).unwrap())
+    }
+}
+
+/// 変更をログファイルに追記する，読み込みはメモリ上のデータから返す
+#[derive(Debug, Clone)]
+pub struct TaskRepositoryForFile {
+    inner: TaskRepositoryForMemory,
+    store: FileStore,
+}
+
+impl TaskRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            inner: TaskRepositoryForMemory::with_store(store.memory()),
+            store,
+        }
+    }
+
+    fn put_task(&self, id: i32) -> Record {
+        Record::put_task(&self.store.memory().read(), id)
+    }
+}
+
+#[async_trait]
+impl TaskRepository for TaskRepositoryForFile {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        self.store
+            .commit(
+                |_| Touched::none(),
+                || async {
+                    let task = self.inner.create(payload).await?;
+                    let record = self.put_task(task.id);
+                    Ok((task, vec![record]))
+                },
+            )
+            .await
+    }
+
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        self.inner.find(id).await
+    }
+
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        self.inner.all().await
+    }
+
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        self.store
+            .commit(
+                |_| Touched::tasks([id]),
+                || async {
+                    let (task, next) = self.inner.update_with_next(id, payload, version).await?;
+                    let records = std::iter::once(task.id)
+                        .chain(next.as_ref().map(|next| next.id))
+                        .map(|id| self.put_task(id))
+                        .collect();
+                    Ok(((task, next), records))
+                },
+            )
+            .await
+    }
+
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+        self.inner.occurrences(id).await
+    }
+
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        // 失敗する移動は何も書き換えない
+        let touched = |tables: &Tables| {
+            let keys = memory_placement(tables, id, &payload).unwrap_or_default();
+            Touched::tasks(keys.into_iter().map(|(id, _)| id))
+        };
+        self.store
+            .commit(touched, || async {
+                let moved = self.inner.move_with(id, &payload)?;
+                let records = moved.into_iter().map(|id| self.put_task(id)).collect();
+                Ok(((), records))
+            })
+            .await?;
+        self.inner.find(id).await
+    }
+
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        self.store
+            .commit(
+                |_| Touched::tasks([id]),
+                || async {
+                    self.inner.delete(id, version).await?;
+                    Ok(((), vec![Record::DeleteTask { id }]))
+                },
+            )
+            .await
+    }
+
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        self.inner.records().await
+    }
+
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        self.store
+            .commit(
+                |_| Touched::none(),
+                || async {
+                    let task = self.inner.restore(record).await?;
+                    let record = self.put_task(task.id);
+                    Ok((task, vec![record]))
+                },
+            )
+            .await
+    }
+}
+
+#[async_trait]
+pub trait TaskRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity>;
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity>;
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>>;
+    async fn update(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<TaskEntity> {
+        let (task, _) = self.update_with_next(id, payload, version).await?;
+        Ok(task)
+    }
+    /// 更新したタスクと，完了により作った次の繰り返しのタスク
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)>;
+    /// 繰り返しで作られた一連のタスク，最初のタスクから id の昇順
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>>;
+    /// 並び順の変更，version は変えない
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity>;
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()>;
+    /// 並び順のキーとラベルを付けた日時まで含めて id の昇順，export に使う
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>>;
+    /// 完了や version，並び順のキーもそのまま登録し，id だけ振り直す，import に使う
+    /// `series_id` と `project_id`，ラベルは登録先の id で渡す，完了済みでも次のタスクは作らない
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity>;
+}
+
+#[derive(Clone, PartialEq, Eq, FromRow)]
+struct TaskFromRow {
+    id: i32,
+    text: String,
+    completed: bool,
+    version: i32,
+    recurrence: Option<String>,
+    due_date: Option<NaiveDate>,
+    series_id: Option<i32>,
+    project_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, FromRow)]
+pub struct TaskWithLabelFromRow {
+    id: i32,
+    text: String,
+    completed: bool,
+    version: i32,
+    recurrence: Option<String>,
+    due_date: Option<NaiveDate>,
+    series_id: Option<i32>,
+    project_id: Option<i32>,
+    label_id: Option<i32>,
+    label_name: Option<String>,
+    label_color: Option<String>,
+    label_description: Option<String>,
+    label_position: Option<i32>,
+    label_parent_id: Option<i32>,
+}
+
+impl TaskWithLabelFromRow {
+    /// left outer join なのでラベルが無い行もある
+    fn label(&self) -> Option<Label> {
+        Some(Label {
+            id: self.label_id?,
+            name: self.label_name.clone()?,
+            color: self.label_color.clone(),
+            description: self.label_description.clone(),
+            position: self.label_position.unwrap_or_default(),
+            parent_id: self.label_parent_id,
+        })
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, ToSchema, SimpleObject)]
+#[graphql(name = "Task")]
+pub struct TaskEntity {
+    pub id: i32,
+    pub text: String,
+    pub completed: bool,
+    pub version: i32,
+    pub labels: Vec<Label>,
+    /// RRULE 形式の繰り返しルール
+    pub recurrence: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    /// 繰り返しで作られたタスクの場合，最初のタスクの id
+    pub series_id: Option<i32>,
+    pub project_id: Option<i32>,
+}
+
+impl TaskEntity {
+    /// 次の繰り返しのタスク，期日が無い場合は `today` を基準にする
+    /// `first_due_date` は繰り返しの最初のタスクの期日，分からなければこのタスクの期日を使う
+    /// 次の期日が日付の範囲を超える場合は作らない
+    pub fn next_occurrence(
+        &self,
+        today: NaiveDate,
+        first_due_date: Option<NaiveDate>,
+    ) -> Option<CreateTask> {
+        let recurrence = self.recurrence.as_ref()?.parse::<Recurrence>().ok()?;
+        let date = self.due_date.unwrap_or(today);
+        let due_date = recurrence.next_after(date, first_due_date.unwrap_or(date))?;
+        Some(CreateTask {
+            text: self.text.clone(),
+            labels: self.labels.iter().map(|label| label.id).collect(),
+            recurrence: self.recurrence.clone(),
+            due_date: Some(due_date),
+            series_id: Some(self.series_id.unwrap_or(self.id)),
+            project_id: self.project_id,
+        })
+    }
+}
+
+/// export / import するタスク，ラベルは id だけを持つ
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskRecord {
+    pub id: i32,
+    pub text: String,
+    pub completed: bool,
+    pub version: i32,
+    pub labels: Vec<i32>,
+    /// (label_id, 付けた日時)，日時の無いラベルは含めない
+    pub attached_at: Vec<(i32, DateTime<Utc>)>,
+    pub recurrence: Option<String>,
+    pub due_date: Option<NaiveDate>,
+    pub series_id: Option<i32>,
+    pub project_id: Option<i32>,
+    pub position: String,
+}
+
+impl TaskRecord {
+    /// ラベルごとの付けた日時，分からないものは None
+    pub fn labels_with_attached_at(
+        &self,
+    ) -> impl Iterator<Item = (i32, Option<DateTime<Utc>>)> + '_ {
+        self.labels.iter().map(move |label_id| {
+            let attached_at = self
+                .attached_at
+                .iter()
+                .find(|(id, _)| id == label_id)
+                .map(|(_, at)| *at);
+            (*label_id, attached_at)
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct TaskRecordRow {
+    id: i32,
+    text: String,
+    completed: bool,
+    version: i32,
+    recurrence: Option<String>,
+    due_date: Option<NaiveDate>,
+    series_id: Option<i32>,
+    project_id: Option<i32>,
+    position: String,
+}
+
+/// `task_labels` は (task_id, label_id, attached_at)
+fn with_labels(
+    rows: Vec<TaskRecordRow>,
+    task_labels: Vec<(i32, i32, Option<DateTime<Utc>>)>,
+) -> Vec<TaskRecord> {
+    let mut labels: HashMap<i32, Vec<i32>> = HashMap::new();
+    let mut attached_at: HashMap<i32, Vec<(i32, DateTime<Utc>)>> = HashMap::new();
+    for (task_id, label_id, at) in task_labels {
+        labels.entry(task_id).or_default().push(label_id);
+        if let Some(at) = at {
+            attached_at.entry(task_id).or_default().push((label_id, at));
+        }
+    }
+    rows.into_iter()
+        .map(|row| TaskRecord {
+            labels: labels.remove(&row.id).unwrap_or_default(),
+            attached_at: attached_at.remove(&row.id).unwrap_or_default(),
+            id: row.id,
+            text: row.text,
+            completed: row.completed,
+            version: row.version,
+            recurrence: row.recurrence,
+            due_date: row.due_date,
+            series_id: row.series_id,
+            project_id: row.project_id,
+            position: row.position,
+        })
+        .collect()
+}
+
+/// 未完了から完了になった場合のみ次のタスクを作る
+fn next_on_completion(
+    was_completed: bool,
+    task: &TaskEntity,
+    first_due_date: Option<NaiveDate>,
+) -> Option<CreateTask> {
+    if was_completed || !task.completed {
+        return None;
+    }
+    task.next_occurrence(Local::now().date_naive(), first_due_date)
+}
+
+/// 0 はプロジェクトから外す，None は変更しない
+fn stored_project(payload: Option<i32>, old: Option<i32>) -> Option<i32> {
+    match payload {
+        Some(0) => None,
+        Some(id) => Some(id),
+        None => old,
+    }
+}
+
+fn stored_recurrence(rule: Option<&str>) -> anyhow::Result<Option<String>> {
+    match rule {
+        Some(rule) => normalize(rule),
+        None => Ok(None),
+    }
+}
+
+fn fold_entities(rows: Vec<TaskWithLabelFromRow>) -> Vec<TaskEntity> {
+    let mut accum: Vec<TaskEntity> = vec![];
+    'outer: for row in rows.iter() {
+        for task in accum.iter_mut() {
+            // id が一致 = Task に紐づくラベルが複数存在している
+            if task.id == row.id {
+                task.labels.extend(row.label());
+                continue 'outer;
+            }
+        }
+
+        // Task の id に一致がなかった時のみ到達， TaskEntity を作成
+        let labels = row.label().into_iter().collect();
+
+        accum.push(TaskEntity {
+            id: row.id,
+            text: row.text.clone(),
+            completed: row.completed,
+            version: row.version,
+            labels,
+            recurrence: row.recurrence.clone(),
+            due_date: row.due_date,
+            series_id: row.series_id,
+            project_id: row.project_id,
+        });
+    }
+    accum
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema, InputObject)]
+#[graphql(name = "CreateTaskInput")]
+pub struct CreateTask {
+    #[validate(length(min = "TEXT_MIN_LEN", message = "Can not be empty"))]
+    #[validate(length(max = "TEXT_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = text_schema)]
+    text: String,
+    labels: Vec<i32>,
+    /// RRULE 形式，例えば `FREQ=WEEKLY;BYDAY=MO,TH`
+    #[validate(custom = "validate_recurrence")]
+    recurrence: Option<String>,
+    #[validate(custom = "validate_due_date")]
+    due_date: Option<NaiveDate>,
+    /// 繰り返しで次のタスクを作る時だけ内部で設定する
+    #[serde(skip)]
+    #[graphql(skip)]
+    series_id: Option<i32>,
+    project_id: Option<i32>,
+}
+
+impl CreateTask {
+    pub fn new(text: String, labels: Vec<i32>) -> Self {
+        Self {
+            text,
+            labels,
+            recurrence: None,
+            due_date: None,
+            series_id: None,
+            project_id: None,
+        }
+    }
+
+    pub fn with_recurrence(
+        mut self,
+        recurrence: Option<String>,
+        due_date: Option<NaiveDate>,
+    ) -> Self {
+        self.recurrence = recurrence;
+        self.due_date = due_date;
+        self
+    }
+
+    pub fn with_project(mut self, project_id: Option<i32>) -> Self {
+        self.project_id = project_id;
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema, InputObject)]
+#[graphql(name = "UpdateTaskInput")]
+pub struct UpdateTask {
+    #[validate(length(min = "TEXT_MIN_LEN", message = "Can not be empty"))]
+    #[validate(length(max = "TEXT_MAX_LEN", message = "Over text length"))]
+    #[schema(schema_with = optional_text_schema)]
+    text: Option<String>,
+    completed: Option<bool>,
+    labels: Option<Vec<i32>>,
+    /// 空文字で繰り返しを止める
+    #[validate(custom = "validate_recurrence")]
+    recurrence: Option<String>,
+    #[validate(custom = "validate_due_date")]
+    due_date: Option<NaiveDate>,
+    /// 0 でプロジェクトから外す
+    project_id: Option<i32>,
+}
+
+impl UpdateTask {
+    pub fn new(text: Option<String>, completed: Option<bool>, labels: Option<Vec<i32>>) -> Self {
+        Self {
+            text,
+            completed,
+            labels,
+            recurrence: None,
+            due_date: None,
+            project_id: None,
+        }
+    }
+
+    pub fn with_recurrence(
+        mut self,
+        recurrence: Option<String>,
+        due_date: Option<NaiveDate>,
+    ) -> Self {
+        self.recurrence = recurrence;
+        self.due_date = due_date;
+        self
+    }
+
+    pub fn with_project(mut self, project_id: Option<i32>) -> Self {
+        self.project_id = project_id;
+        self
+    }
+}
+
+/// 移動先，少なくとも一方を指定する
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Validate, ToSchema)]
+#[validate(schema(function = "validate_move_task"))]
+pub struct MoveTask {
+    /// このタスクの直後に置く
+    after: Option<i32>,
+    /// このタスクの直前に置く
+    before: Option<i32>,
+}
+
+impl MoveTask {
+    pub fn new(after: Option<i32>, before: Option<i32>) -> Self {
+        Self { after, before }
+    }
+}
+
+fn validate_move_task(payload: &MoveTask) -> Result<(), ValidationError> {
+    if payload.after.is_none() && payload.before.is_none() {
+        return Err(ValidationError::new("after or before is required"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use crate::repositories::label::{CreateLabel, LabelRepository, LabelRepositoryForDb};
+    use dotenv::dotenv;
+    use std::env;
+
+    #[test]
+    fn fold_entities_test() {
+        let label_1 = Label {
+            color: Some("#ff8800".to_string()),
+            description: Some("label 1 description".to_string()),
+            position: 2,
+            ..Label::new(1, String::from("label 1"))
+        };
+        let label_2 = Label::new(2, String::from("label 2"));
+        let rows = vec![
+            TaskWithLabelFromRow {
+                id: 1,
+                text: String::from("task 1"),
+                completed: false,
+                version: 1,
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
+                label_id: Some(label_1.id),
+                label_name: Some(label_1.name.clone()),
+                label_color: label_1.color.clone(),
+                label_description: label_1.description.clone(),
+                label_position: Some(label_1.position),
+                label_parent_id: label_1.parent_id,
             },
             TaskWithLabelFromRow {
                 id: 1,
                 text: String::from("task 1"),
                 completed: false,
+                version: 1,
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
                 label_id: Some(label_2.id),
                 label_name: Some(label_2.name.clone()),
+                label_color: label_2.color.clone(),
+                label_description: label_2.description.clone(),
+                label_position: Some(label_2.position),
+                label_parent_id: label_2.parent_id,
             },
             TaskWithLabelFromRow {
                 id: 2,
                 text: String::from("task 2"),
                 completed: false,
+                version: 1,
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
                 label_id: Some(label_1.id),
                 label_name: Some(label_1.name.clone()),
+                label_color: label_1.color.clone(),
+                label_description: label_1.description.clone(),
+                label_position: Some(label_1.position),
+                label_parent_id: label_1.parent_id,
             },
         ];
 
@@ -317,13 +1798,23 @@ mod test {
                     id: 1,
                     text: String::from("task 1"),
                     completed: false,
+                    version: 1,
                     labels: vec![label_1.clone(), label_2.clone()],
+                    recurrence: None,
+                    due_date: None,
+                    series_id: None,
+                    project_id: None,
                 },
                 TaskEntity {
                     id: 2,
                     text: String::from("task 2"),
                     completed: false,
+                    version: 1,
                     labels: vec![label_1],
+                    recurrence: None,
+                    due_date: None,
+                    series_id: None,
+                    project_id: None,
                 },
             ]
         );
@@ -331,41 +1822,421 @@ mod test {
 
     #[tokio::test]
     async fn crud_scenario() {
-        dotenv().ok();
-        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
-        let pool = PgPool::connect(database_url)
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        // label data prepare
+        let label_name = String::from("test label");
+        let optional_label = sqlx::query_as::<_, Label>(
+            r#"
+                select * from labels where name = $1
+            "#,
+        )
+        .bind(label_name.clone())
+        .fetch_optional(&pool)
+        .await
+        .expect("Failed to prepare label data.");
+        let label_1 = if let Some(label) = optional_label {
+            label
+        } else {
+            let label = sqlx::query_as::<_, Label>(
+                r#"
+                    insert into labels ( name )
+                    values ( $1 )
+                    returning *
+                "#,
+            )
+            .bind(label_name)
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to insert label data.");
+            label
+        };
+
+        let repository = TaskRepositoryForDb::new(pool.clone());
+        let task = test_utils::crud_scenario(&repository, label_1).await;
+
+        let task_rows = sqlx::query(
+            r#"
+                select * from tasks where id=$1
+            "#,
+        )
+        .bind(task.id)
+        .fetch_all(&pool)
+        .await
+        .expect("[delete] task_labelss fetch error");
+        assert!(task_rows.is_empty());
+
+        let rows = sqlx::query(
+            r#"
+                select * from task_labels where task_id=$1
+            "#,
+        )
+        .bind(task.id)
+        .fetch_all(&pool)
+        .await
+        .expect("[delete] task_labels fetch error");
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn recurrence_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+        let labels = LabelRepositoryForDb::new(pool.clone());
+        let label_1 = labels
+            .create(CreateLabel::new(format!(
+                "recurrence {}",
+                uuid::Uuid::new_v4()
+            )))
+            .await
+            .expect("Failed to insert label data.");
+
+        let repository = TaskRepositoryForDb::new(pool);
+        let tasks = test_utils::recurrence_scenario(&repository, label_1.clone()).await;
+        for task in tasks {
+            repository.delete(task.id, None).await.unwrap();
+        }
+        labels.delete(label_1.id).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn move_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        let repository = TaskRepositoryForDb::new(pool);
+        for task in test_utils::move_scenario(&repository).await {
+            repository.delete(task.id, None).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn monthly_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        let repository = TaskRepositoryForDb::new(pool);
+        for task in test_utils::monthly_scenario(&repository).await {
+            repository.delete(task.id, None).await.unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::{
+        label::{CreateLabel, LabelRepository, LabelRepositoryForSqlite},
+        test_utils::sqlite_memory_pool,
+    };
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let pool = sqlite_memory_pool().await;
+        let label_1 = LabelRepositoryForSqlite::new(pool.clone())
+            .create(CreateLabel::new(String::from("test label")))
             .await
-            .expect(&format!("fail connect database, url is [{}]", database_url));
+            .expect("Failed to insert label data.");
 
-        // label data prepare
-        let label_name = String::from("test label");
-        let optional_label = sqlx::query_as::<_, Label>(
+        let repository = TaskRepositoryForSqlite::new(pool.clone());
+        let task = test_utils::crud_scenario(&repository, label_1).await;
+
+        let rows = sqlx::query(
             r#"
-                select * from labels where name = $1
+                select * from task_labels where task_id = ?1
             "#,
         )
-        .bind(label_name.clone())
-        .fetch_optional(&pool)
+        .bind(task.id)
+        .fetch_all(&pool)
         .await
-        .expect("Failed to prepare label data.");
-        let label_1 = if let Some(label) = optional_label {
-            label
-        } else {
-            let label = sqlx::query_as::<_, Label>(
-                r#"
-                    insert into labels ( name )
-                    values ( $1 )
-                    returning *
-                "#,
+        .expect("[delete] task_labels fetch error");
+        assert!(rows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_references_fail_with_not_found() {
+        let repository = TaskRepositoryForSqlite::new(sqlite_memory_pool().await);
+        let task = repository
+            .create(CreateTask::new("task".to_string(), vec![]))
+            .await
+            .unwrap();
+        let results = [
+            repository
+                .create(CreateTask::new("task".to_string(), vec![99]))
+                .await,
+            repository
+                .create(CreateTask::new("task".to_string(), vec![]).with_project(Some(98)))
+                .await,
+            repository
+                .update(task.id, UpdateTask::new(None, None, Some(vec![97])), None)
+                .await,
+        ];
+        for (res, id) in results.into_iter().zip([99, 98, 97]) {
+            assert!(matches!(
+                res.unwrap_err().downcast_ref::<RepositoryError>(),
+                Some(RepositoryError::NotFound(missing)) if *missing == id
+            ));
+        }
+        assert_eq!(repository.all().await.unwrap(), vec![task]);
+    }
+
+    #[tokio::test]
+    async fn recurrence_scenario() {
+        let pool = sqlite_memory_pool().await;
+        let label_1 = LabelRepositoryForSqlite::new(pool.clone())
+            .create(CreateLabel::new(String::from("test label")))
+            .await
+            .expect("Failed to insert label data.");
+
+        let repository = TaskRepositoryForSqlite::new(pool);
+        test_utils::recurrence_scenario(&repository, label_1).await;
+    }
+
+    #[tokio::test]
+    async fn move_scenario() {
+        let repository = TaskRepositoryForSqlite::new(sqlite_memory_pool().await);
+        test_utils::move_scenario(&repository).await;
+    }
+
+    #[tokio::test]
+    async fn monthly_scenario() {
+        let repository = TaskRepositoryForSqlite::new(sqlite_memory_pool().await);
+        test_utils::monthly_scenario(&repository).await;
+    }
+}
+
+#[cfg(test)]
+mod memory_test {
+    use super::*;
+    use crate::repositories::label::{CreateLabel, LabelRepository, LabelRepositoryForMemory};
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let store = MemoryStore::new();
+        let label_1 = LabelRepositoryForMemory::with_store(store.clone())
+            .create(CreateLabel::new(String::from("test label")))
+            .await
+            .expect("Failed to insert label data.");
+
+        let repository = TaskRepositoryForMemory::with_store(store.clone());
+        let task = test_utils::crud_scenario(&repository, label_1).await;
+        assert!(store.read().labels_of(task.id).is_empty());
+    }
+
+    #[tokio::test]
+    async fn recurrence_scenario() {
+        let store = MemoryStore::new();
+        let label_1 = LabelRepositoryForMemory::with_store(store.clone())
+            .create(CreateLabel::new(String::from("test label")))
+            .await
+            .expect("Failed to insert label data.");
+
+        let repository = TaskRepositoryForMemory::with_store(store);
+        test_utils::recurrence_scenario(&repository, label_1).await;
+    }
+
+    #[tokio::test]
+    async fn should_not_spawn_occurrence_past_end_of_date_range() {
+        let repository = TaskRepositoryForMemory::new(vec![]);
+        // 検証を通らない期日も，取り込みなどで保存されている場合がある
+        let task = repository
+            .create(
+                CreateTask::new("last day".to_string(), vec![])
+                    .with_recurrence(Some("FREQ=DAILY".to_string()), Some(NaiveDate::MAX)),
             )
-            .bind(label_name)
-            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let task = repository
+            .update(task.id, UpdateTask::new(None, Some(true), None), None)
+            .await
+            .unwrap();
+        assert!(task.completed);
+        assert_eq!(repository.all().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn move_scenario() {
+        let repository = TaskRepositoryForMemory::new(vec![]);
+        test_utils::move_scenario(&repository).await;
+    }
+
+    #[tokio::test]
+    async fn monthly_scenario() {
+        test_utils::monthly_scenario(&TaskRepositoryForMemory::new(vec![])).await;
+    }
+
+    #[tokio::test]
+    async fn should_rebalance_when_keys_get_too_long() {
+        let store = MemoryStore::new();
+        let repository = TaskRepositoryForMemory::with_store(store.clone());
+        for text in ["a", "b", "c"] {
+            repository
+                .create(CreateTask::new(text.to_string(), vec![]))
+                .await
+                .unwrap();
+        }
+        // 同じ隙間へ交互に入れ続けるとキーが伸び，上限に達すると振り直される
+        for _ in 0..100 {
+            for (id, after, before) in [(1, 3, 2), (2, 3, 1)] {
+                repository
+                    .move_task(id, MoveTask::new(Some(after), Some(before)))
+                    .await
+                    .unwrap();
+                let ids: Vec<i32> = repository
+                    .all()
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|task| task.id)
+                    .collect();
+                assert_eq!(ids, vec![after, id, before]);
+            }
+        }
+        let tables = store.read();
+        assert!(tables
+            .tasks
+            .values()
+            .all(|row| row.position.len() <= position::MAX_LEN));
+    }
+
+    #[tokio::test]
+    async fn ids_are_not_reused_and_all_is_ordered_by_id_desc() {
+        let repository = TaskRepositoryForMemory::new(vec![]);
+        for text in ["first", "second", "third"] {
+            repository
+                .create(CreateTask::new(text.to_string(), vec![]))
+                .await
+                .unwrap();
+        }
+        repository.delete(3, None).await.unwrap();
+        let task = repository
+            .create(CreateTask::new("fourth".to_string(), vec![]))
+            .await
+            .unwrap();
+        assert_eq!(task.id, 4);
+
+        let ids: Vec<i32> = repository
+            .all()
+            .await
+            .unwrap()
+            .iter()
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(ids, vec![4, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn create_with_unknown_label_fails() {
+        let repository = TaskRepositoryForMemory::new(vec![]);
+        let res = repository
+            .create(CreateTask::new("text".to_string(), vec![999]))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(999))
+        ));
+        assert!(repository.all().await.unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use super::*;
+    use crate::repositories::{
+        file::test_utils::temp_data_file,
+        label::{CreateLabel, LabelRepository, LabelRepositoryForFile},
+    };
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let store = FileStore::open(temp_data_file()).unwrap();
+        let label_1 = LabelRepositoryForFile::new(store.clone())
+            .create(CreateLabel::new(String::from("test label")))
             .await
             .expect("Failed to insert label data.");
-            label
-        };
 
-        let repository = TaskRepositoryForDb::new(pool.clone());
+        let repository = TaskRepositoryForFile::new(store);
+        test_utils::crud_scenario(&repository, label_1).await;
+    }
+
+    #[tokio::test]
+    async fn recurrence_scenario() {
+        let path = temp_data_file();
+        let store = FileStore::open(&path).unwrap();
+        let label_1 = LabelRepositoryForFile::new(store.clone())
+            .create(CreateLabel::new(String::from("test label")))
+            .await
+            .expect("Failed to insert label data.");
+
+        let repository = TaskRepositoryForFile::new(store);
+        let tasks = test_utils::recurrence_scenario(&repository, label_1).await;
+        drop(repository);
+
+        // 完了で作られたタスクもログから復元される
+        let repository = TaskRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert_eq!(repository.occurrences(tasks[0].id).await.unwrap(), tasks);
+    }
+
+    #[tokio::test]
+    async fn move_scenario() {
+        let path = temp_data_file();
+        let repository = TaskRepositoryForFile::new(FileStore::open(&path).unwrap());
+        test_utils::move_scenario(&repository).await;
+        let tasks = repository.all().await.unwrap();
+        drop(repository);
+
+        // 並び順もログから復元される
+        let repository = TaskRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert_eq!(repository.all().await.unwrap(), tasks);
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    impl TaskEntity {
+        pub fn new(id: i32, text: String, labels: Vec<Label>) -> Self {
+            Self {
+                id,
+                text,
+                completed: false,
+                version: 1,
+                labels,
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
+            }
+        }
+    }
+
+    impl TaskRepositoryForMemory {
+        /// 与えたラベルだけが登録されたストアで作成する
+        pub fn new(labels: Vec<Label>) -> Self {
+            let store = MemoryStore::new();
+            for label in labels {
+                store.write().insert_label(label);
+            }
+            Self::with_store(store)
+        }
+    }
+
+    /// 各 DB 実装で共通の CRUD シナリオ，削除したタスクを返す
+    pub async fn crud_scenario<T: TaskRepository>(repository: &T, label_1: Label) -> TaskEntity {
         let task_text = "[crud_scenario] text";
 
         // create
@@ -398,156 +2269,261 @@ mod test {
                     text: Some(updated_text.to_string()),
                     completed: Some(true),
                     labels: Some(vec![]),
+                    recurrence: None,
+                    due_date: None,
+                    project_id: None,
                 },
+                Some(created.version),
             )
             .await
             .expect("[update] returned Err");
         assert_eq!(created.id, task.id);
         assert_eq!(task.text, updated_text);
-        assert!(task.labels.len() == 0);
+        assert_eq!(task.version, created.version + 1);
+        assert!(task.labels.is_empty());
+
+        // update with stale version
+        let res = repository
+            .update(
+                task.id,
+                UpdateTask {
+                    text: Some(task_text.to_string()),
+                    completed: None,
+                    labels: None,
+                    recurrence: None,
+                    due_date: None,
+                    project_id: None,
+                },
+                Some(created.version),
+            )
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::VersionMismatch(_))
+        ));
+
+        // If-Match が無ければ version は確かめない
+        let task = repository
+            .update(task.id, UpdateTask::new(None, None, None), None)
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(task.version, created.version + 2);
 
         // delete
-        let _ = repository
-            .delete(task.id)
+        let res = repository.delete(task.id, Some(created.version)).await; // expect version mismatch err
+        assert!(res.is_err());
+        repository
+            .delete(task.id, Some(task.version))
             .await
             .expect("[delete] returned Err");
         let res = repository.find(created.id).await; // expect not found err
         assert!(res.is_err());
-
-        let task_rows = sqlx::query(
-            r#"
-                select * from tasks where id=$1
-            "#,
-        )
-        .bind(task.id)
-        .fetch_all(&pool)
-        .await
-        .expect("[delete] task_labelss fetch error");
-        assert!(task_rows.len() == 0);
-
-        let rows = sqlx::query(
-            r#"
-                select * from task_labels where task_id=$1
-            "#,
-        )
-        .bind(task.id)
-        .fetch_all(&pool)
-        .await
-        .expect("[delete] task_labels fetch error");
-        assert!(rows.len() == 0);
+        task
     }
-}
 
-#[cfg(test)]
-pub mod test_utils {
-    use super::*;
-    use anyhow::Context;
-    use axum::async_trait;
-    use std::{
-        collections::HashMap,
-        sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
-    };
+    /// 繰り返しタスクの完了で次のタスクが作られるシナリオ，作られた一連のタスクを返す
+    pub async fn recurrence_scenario<T: TaskRepository>(
+        repository: &T,
+        label_1: Label,
+    ) -> Vec<TaskEntity> {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let done = || UpdateTask::new(None, Some(true), None);
 
-    impl TaskEntity {
-        pub fn new(id: i32, text: String, labels: Vec<Label>) -> Self {
-            Self {
-                id,
-                text,
-                completed: false,
-                labels,
-            }
-        }
-    }
+        // 2024-01-01 は月曜日
+        let first = repository
+            .create(
+                CreateTask::new("[recurrence_scenario] text".to_string(), vec![label_1.id])
+                    .with_recurrence(
+                        Some("freq=weekly;byday=th,mo".to_string()),
+                        Some(date("2024-01-01")),
+                    ),
+            )
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(first.recurrence.as_deref(), Some("FREQ=WEEKLY;BYDAY=MO,TH"));
+        assert_eq!(first.series_id, None);
 
-    impl CreateTask {
-        pub fn new(text: String, labels: Vec<i32>) -> Self {
-            Self { text, labels }
-        }
-    }
+        // 完了以外の更新では作られない
+        let updated_text = "[recurrence_scenario] updated text";
+        repository
+            .update(
+                first.id,
+                UpdateTask::new(Some(updated_text.to_string()), None, None),
+                None,
+            )
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(repository.occurrences(first.id).await.unwrap().len(), 1);
 
-    type TaskData = HashMap<i32, TaskEntity>;
+        let first = repository
+            .update(first.id, done(), None)
+            .await
+            .expect("[update] returned Err");
+        let tasks = repository
+            .occurrences(first.id)
+            .await
+            .expect("[occurrences] returned Err");
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0], first);
+        let second = tasks[1].clone();
+        assert_eq!(second.text, updated_text);
+        assert!(!second.completed);
+        assert_eq!(second.labels, vec![label_1]);
+        assert_eq!(second.recurrence, first.recurrence);
+        assert_eq!(second.due_date, Some(date("2024-01-04")));
+        assert_eq!(second.series_id, Some(first.id));
 
-    #[derive(Debug, Clone)]
-    pub struct TaskRepositoryForMemory {
-        store: Arc<RwLock<TaskData>>,
-        labels: Vec<Label>,
-    }
+        // 完了済みのまま更新しても作られない
+        repository
+            .update(first.id, done(), None)
+            .await
+            .expect("[update] returned Err");
+        assert_eq!(repository.occurrences(second.id).await.unwrap().len(), 2);
 
-    impl TaskRepositoryForMemory {
-        pub fn new(labels: Vec<Label>) -> Self {
-            TaskRepositoryForMemory {
-                store: Arc::default(),
-                labels,
-            }
-        }
+        repository
+            .update(second.id, done(), None)
+            .await
+            .expect("[update] returned Err");
+        let tasks = repository
+            .occurrences(second.id)
+            .await
+            .expect("[occurrences] returned Err");
+        let due_dates = tasks
+            .iter()
+            .map(|task| task.due_date.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            due_dates,
+            vec![date("2024-01-01"), date("2024-01-04"), date("2024-01-08")]
+        );
 
-        fn write_store_ref(&self) -> RwLockWriteGuard<TaskData> {
-            self.store.write().unwrap()
-        }
+        // 繰り返しを止めると次は作られない
+        let third = tasks[2].clone();
+        repository
+            .update(
+                third.id,
+                done().with_recurrence(Some("".to_string()), None),
+                None,
+            )
+            .await
+            .expect("[update] returned Err");
+        let tasks = repository.occurrences(third.id).await.unwrap();
+        assert_eq!(tasks.len(), 3);
+        assert_eq!(tasks[2].recurrence, None);
+        tasks
+    }
 
-        fn read_store_ref(&self) -> RwLockReadGuard<TaskData> {
-            self.store.read().unwrap()
+    /// 日の指定が無い毎月の繰り返しは，月末に丸めても最初のタスクの日に戻る
+    pub async fn monthly_scenario<T: TaskRepository>(repository: &T) -> Vec<TaskEntity> {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+        let mut task = repository
+            .create(
+                CreateTask::new("[monthly_scenario] text".to_string(), vec![])
+                    .with_recurrence(Some("FREQ=MONTHLY".to_string()), Some(date("2024-01-31"))),
+            )
+            .await
+            .expect("[create] returned Err");
+        for _ in 0..2 {
+            repository
+                .update(task.id, UpdateTask::new(None, Some(true), None), None)
+                .await
+                .expect("[update] returned Err");
+            task = repository
+                .occurrences(task.id)
+                .await
+                .expect("[occurrences] returned Err")
+                .pop()
+                .unwrap();
         }
+        let tasks = repository.occurrences(task.id).await.unwrap();
+        let due_dates = tasks
+            .iter()
+            .map(|task| task.due_date.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            due_dates,
+            vec![date("2024-01-31"), date("2024-02-29"), date("2024-03-31")]
+        );
+        tasks
+    }
 
-        fn resolve_labels(&self, labels: Vec<i32>) -> Vec<Label> {
-            let mut label_list = self.labels.iter().cloned();
-            let labels = labels
-                .iter()
-                .map(|id| label_list.find(|label| label.id == *id).unwrap())
-                .collect();
-            labels
-        }
+    /// `ids` のタスクだけを取り出した並び順
+    async fn order_of<T: TaskRepository>(repository: &T, ids: &[i32]) -> Vec<i32> {
+        repository
+            .all()
+            .await
+            .expect("[all] returned Err")
+            .iter()
+            .map(|task| task.id)
+            .filter(|id| ids.contains(id))
+            .collect()
     }
 
-    #[async_trait]
-    impl TaskRepository for TaskRepositoryForMemory {
-        async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
-            let mut store = self.write_store_ref();
-            let id = (store.len() + 1) as i32;
-            let labels = self.resolve_labels(payload.labels);
-            let task = TaskEntity::new(id, payload.text.clone(), labels);
-            store.insert(id, task.clone());
-            Ok(task)
+    /// 並び順を変えるシナリオ，作ったタスクを返す
+    /// 他のテストとテーブルを共有する場合もあるので，作ったタスク同士の順序だけを確かめる
+    pub async fn move_scenario<T: TaskRepository>(repository: &T) -> Vec<TaskEntity> {
+        let mut tasks = vec![];
+        for text in ["a", "b", "c"] {
+            let task = repository
+                .create(CreateTask::new(format!("[move_scenario] {}", text), vec![]))
+                .await
+                .expect("[create] returned Err");
+            tasks.push(task);
         }
+        let (a, b, c) = (tasks[0].id, tasks[1].id, tasks[2].id);
+        // 新しいタスクが先頭
+        assert_eq!(order_of(repository, &[a, b, c]).await, vec![c, b, a]);
 
-        async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
-            let store = self.read_store_ref();
-            let task = store
-                .get(&id)
-                .map(|task| task.clone())
-                .ok_or(RepositoryError::NotFound(id))?;
-            Ok(task)
-        }
+        let moved = repository
+            .move_task(a, MoveTask::new(Some(c), None))
+            .await
+            .expect("[move] returned Err");
+        assert_eq!(moved, tasks[0]);
+        assert_eq!(order_of(repository, &[a, b, c]).await, vec![c, a, b]);
 
-        async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
-            let store = self.read_store_ref();
-            Ok(Vec::from_iter(store.values().map(|task| task.clone())))
-        }
+        repository
+            .move_task(b, MoveTask::new(None, Some(c)))
+            .await
+            .expect("[move] returned Err");
+        assert_eq!(order_of(repository, &[a, b, c]).await, vec![b, c, a]);
 
-        async fn update(&self, id: i32, payload: UpdateTask) -> anyhow::Result<TaskEntity> {
-            let mut store = self.write_store_ref();
-            let task = store.get(&id).context(RepositoryError::NotFound(id))?;
-            let text = payload.text.unwrap_or(task.text.clone());
-            let completed = payload.completed.unwrap_or(task.completed);
-            let labels = match payload.labels {
-                Some(label_ids) => self.resolve_labels(label_ids),
-                None => task.labels.clone(),
-            };
-            let task = TaskEntity {
-                id,
-                text,
-                completed,
-                labels,
-            };
-            store.insert(id, task.clone());
-            Ok(task)
-        }
+        // 移動するタスク自身を除いて隣り合っていればよい
+        repository
+            .move_task(c, MoveTask::new(Some(b), Some(a)))
+            .await
+            .expect("[move] returned Err");
+        assert_eq!(order_of(repository, &[a, b, c]).await, vec![b, c, a]);
 
-        async fn delete(&self, id: i32) -> anyhow::Result<()> {
-            let mut store = self.write_store_ref();
-            store.remove(&id).ok_or(RepositoryError::NotFound(id))?;
-            Ok(())
+        let res = repository
+            .move_task(a, MoveTask::new(Some(c), Some(b)))
+            .await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::Conflict(_))
+        ));
+        let res = repository.move_task(a, MoveTask::new(Some(-1), None)).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(-1))
+        ));
+        let res = repository.move_task(-1, MoveTask::new(Some(a), None)).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(-1))
+        ));
+        assert_eq!(order_of(repository, &[a, b, c]).await, vec![b, c, a]);
+
+        // 同じ隙間に入れ続けてキーが上限を超えたら振り直す
+        let (mut x, mut y) = (a, c);
+        for _ in 0..(position::MAX_LEN * 8) {
+            repository
+                .move_task(x, MoveTask::new(Some(b), Some(y)))
+                .await
+                .expect("[move] returned Err");
+            assert_eq!(order_of(repository, &[a, b, c]).await, vec![b, x, y]);
+            (x, y) = (y, x);
         }
+        tasks
     }
 
     #[cfg(test)]
@@ -558,23 +2534,22 @@ pub mod test_utils {
         async fn task_crud_scenario() {
             let text = "task text".to_string();
             let id = 1;
-            let label_data = Label {
-                id: 1,
-                name: "test label".to_string(),
-            };
+            let label_data = Label::new(1, "test label".to_string());
             let labels = vec![label_data.clone()];
             let expected = TaskEntity {
                 id,
                 text: text.clone(),
                 completed: false,
+                version: 1,
                 labels: labels.clone(),
+                recurrence: None,
+                due_date: None,
+                series_id: None,
+                project_id: None,
             };
 
             // create
-            let label_data = Label {
-                id: 1,
-                name: "test label".to_string(),
-            };
+            let label_data = Label::new(1, "test label".to_string());
             let labels = vec![label_data.clone()];
             let repository = TaskRepositoryForMemory::new(labels.clone());
             let task = repository
@@ -600,7 +2575,11 @@ pub mod test_utils {
                         text: Some(text.clone()),
                         completed: Some(true),
                         labels: Some(vec![]),
+                        recurrence: None,
+                        due_date: None,
+                        project_id: None,
                     },
+                    Some(1),
                 )
                 .await
                 .expect("failed update task.");
@@ -609,13 +2588,37 @@ pub mod test_utils {
                     id,
                     text,
                     completed: true,
+                    version: 2,
                     labels: vec![],
+                    recurrence: None,
+                    due_date: None,
+                    series_id: None,
+                    project_id: None,
                 },
                 task
             );
 
+            // update with stale version
+            let res = repository
+                .update(
+                    1,
+                    UpdateTask {
+                        text: None,
+                        completed: Some(false),
+                        labels: None,
+                        recurrence: None,
+                        due_date: None,
+                        project_id: None,
+                    },
+                    Some(1),
+                )
+                .await;
+            assert!(res.is_err());
+
             // delete
-            let res = repository.delete(id).await;
+            let res = repository.delete(id, Some(1)).await;
+            assert!(res.is_err());
+            let res = repository.delete(id, Some(2)).await;
             assert!(res.is_ok());
         }
     }
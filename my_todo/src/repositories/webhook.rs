@@ -0,0 +1,475 @@
+use std::{fmt, str::FromStr};
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, SqlitePool};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use super::{
+    file::{FileStore, Record, Touched},
+    limits::{secret_schema, SECRET_MAX_LEN, SECRET_MIN_LEN},
+    memory::MemoryStore,
+    RepositoryError,
+};
+
+#[async_trait]
+pub trait WebhookRepository: Clone + std::marker::Send + std::marker::Sync + 'static {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<Webhook>;
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>>;
+    async fn delete(&self, id: i32) -> anyhow::Result<()>;
+}
+
+/// 通知するイベントの種類
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, ToSchema,
+)]
+pub enum EventType {
+    #[serde(rename = "task.created")]
+    TaskCreated,
+    #[serde(rename = "task.updated")]
+    TaskUpdated,
+    #[serde(rename = "task.deleted")]
+    TaskDeleted,
+    #[serde(rename = "label.created")]
+    LabelCreated,
+    #[serde(rename = "label.updated")]
+    LabelUpdated,
+    #[serde(rename = "label.deleted")]
+    LabelDeleted,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventType::TaskCreated => "task.created",
+            EventType::TaskUpdated => "task.updated",
+            EventType::TaskDeleted => "task.deleted",
+            EventType::LabelCreated => "label.created",
+            EventType::LabelUpdated => "label.updated",
+            EventType::LabelDeleted => "label.deleted",
+        }
+    }
+}
+
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "task.created" => Ok(EventType::TaskCreated),
+            "task.updated" => Ok(EventType::TaskUpdated),
+            "task.deleted" => Ok(EventType::TaskDeleted),
+            "label.created" => Ok(EventType::LabelCreated),
+            "label.updated" => Ok(EventType::LabelUpdated),
+            "label.deleted" => Ok(EventType::LabelDeleted),
+            _ => Err(format!("unknown event [{}]", s)),
+        }
+    }
+}
+
+/// secret は署名にだけ使い，レスポンスには含めない
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    #[serde(skip)]
+    pub secret: String,
+    pub events: Vec<EventType>,
+}
+
+impl Webhook {
+    pub fn subscribes(&self, event: EventType) -> bool {
+        self.events.contains(&event)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, ToSchema)]
+pub struct CreateWebhook {
+    #[validate(url(message = "Invalid url"))]
+    #[schema(example = "http://localhost:8080/hook")]
+    url: String,
+    #[validate(length(min = "SECRET_MIN_LEN", message = "Too short secret"))]
+    #[validate(length(max = "SECRET_MAX_LEN", message = "Over secret length"))]
+    #[schema(schema_with = secret_schema)]
+    secret: String,
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    events: Vec<EventType>,
+}
+
+impl CreateWebhook {
+    pub fn new(url: String, secret: String, events: Vec<EventType>) -> Self {
+        Self {
+            url,
+            secret,
+            events,
+        }
+    }
+
+    /// 重複を除いて並べ替えたもの
+    fn events(&self) -> Vec<EventType> {
+        let mut events = self.events.clone();
+        events.sort();
+        events.dedup();
+        events
+    }
+}
+
+/// webhooks テーブルの 1 行，events はカンマ区切りで保存する
+#[derive(Debug, FromRow)]
+struct WebhookFromRow {
+    id: i32,
+    url: String,
+    secret: String,
+    events: String,
+}
+
+impl TryFrom<WebhookFromRow> for Webhook {
+    type Error = anyhow::Error;
+
+    fn try_from(row: WebhookFromRow) -> Result<Self, Self::Error> {
+        let events = row
+            .events
+            .split(',')
+            .map(|event| event.parse().map_err(anyhow::Error::msg))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Webhook {
+            id: row.id,
+            url: row.url,
+            secret: row.secret,
+            events,
+        })
+    }
+}
+
+fn join_events(events: &[EventType]) -> String {
+    events
+        .iter()
+        .map(EventType::as_str)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Clone)]
+pub struct WebhookRepositoryForDb {
+    pool: PgPool,
+}
+
+impl WebhookRepositoryForDb {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForDb {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<Webhook> {
+        let row = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                insert into webhooks (url, secret, events)
+                values ($1, $2, $3)
+                returning *
+            "#,
+        )
+        .bind(&payload.url)
+        .bind(&payload.secret)
+        .bind(join_events(&payload.events()))
+        .fetch_one(&self.pool)
+        .await?;
+
+        row.try_into()
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>> {
+        let rows = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                select * from webhooks
+                order by id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let res = sqlx::query(
+            r#"
+                delete from webhooks where id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct WebhookRepositoryForSqlite {
+    pool: SqlitePool,
+}
+
+impl WebhookRepositoryForSqlite {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForSqlite {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<Webhook> {
+        let row = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                insert into webhooks (url, secret, events)
+                values (?1, ?2, ?3)
+                returning *
+            "#,
+        )
+        .bind(&payload.url)
+        .bind(&payload.secret)
+        .bind(join_events(&payload.events()))
+        .fetch_one(&self.pool)
+        .await?;
+
+        row.try_into()
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>> {
+        let rows = sqlx::query_as::<_, WebhookFromRow>(
+            r#"
+                select * from webhooks
+                order by id asc
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(TryInto::try_into).collect()
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        let res = sqlx::query(
+            r#"
+                delete from webhooks where id = ?1
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Err(RepositoryError::NotFound(id).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// `--storage=memory` 用，プロセス終了でデータは消える
+#[derive(Debug, Clone, Default)]
+pub struct WebhookRepositoryForMemory {
+    store: MemoryStore,
+}
+
+impl WebhookRepositoryForMemory {
+    pub fn with_store(store: MemoryStore) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForMemory {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<Webhook> {
+        let mut tables = self.store.write();
+        let webhook = Webhook {
+            id: tables.next_webhook_id(),
+            events: payload.events(),
+            url: payload.url,
+            secret: payload.secret,
+        };
+        tables.webhooks.insert(webhook.id, webhook.clone());
+        Ok(webhook)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>> {
+        Ok(self.store.read().webhooks.values().cloned().collect())
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.store
+            .write()
+            .webhooks
+            .remove(&id)
+            .ok_or(RepositoryError::NotFound(id))?;
+        Ok(())
+    }
+}
+
+/// 変更をログファイルに追記する，読み込みはメモリ上のデータから返す
+#[derive(Debug, Clone)]
+pub struct WebhookRepositoryForFile {
+    inner: WebhookRepositoryForMemory,
+    store: FileStore,
+}
+
+impl WebhookRepositoryForFile {
+    pub fn new(store: FileStore) -> Self {
+        Self {
+            inner: WebhookRepositoryForMemory::with_store(store.memory()),
+            store,
+        }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for WebhookRepositoryForFile {
+    async fn create(&self, payload: CreateWebhook) -> anyhow::Result<Webhook> {
+        self.store
+            .commit(
+                |_| Touched::none(),
+                || async {
+                    let webhook = self.inner.create(payload).await?;
+                    let record = Record::put_webhook(&webhook);
+                    Ok((webhook, vec![record]))
+                },
+            )
+            .await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Webhook>> {
+        self.inner.all().await
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.store
+            .commit(
+                |_| Touched::webhooks([id]),
+                || async {
+                    self.inner.delete(id).await?;
+                    Ok(((), vec![Record::DeleteWebhook { id }]))
+                },
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "database-test")]
+mod test {
+    use super::*;
+    use dotenv::dotenv;
+    use std::env;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        dotenv().ok();
+        let database_url = &env::var("DATABASE_URL").expect("undefined [DATABASE_URL]");
+        let pool = PgPool::connect(database_url)
+            .await
+            .unwrap_or_else(|_| panic!("fail connect database, url is [{}]", database_url));
+
+        test_utils::crud_scenario(&WebhookRepositoryForDb::new(pool)).await;
+    }
+}
+
+#[cfg(test)]
+mod sqlite_test {
+    use super::*;
+    use crate::repositories::test_utils::sqlite_memory_pool;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let repository = WebhookRepositoryForSqlite::new(sqlite_memory_pool().await);
+        test_utils::crud_scenario(&repository).await;
+    }
+}
+
+#[cfg(test)]
+mod memory_test {
+    use super::*;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        test_utils::crud_scenario(&WebhookRepositoryForMemory::default()).await;
+    }
+}
+
+#[cfg(test)]
+mod file_test {
+    use super::*;
+    use crate::repositories::file::test_utils::temp_data_file;
+
+    #[tokio::test]
+    async fn crud_scenario() {
+        let path = temp_data_file();
+        let webhook = {
+            let repository = WebhookRepositoryForFile::new(FileStore::open(&path).unwrap());
+            test_utils::crud_scenario(&repository).await;
+            repository
+                .create(test_utils::payload("http://localhost/kept"))
+                .await
+                .unwrap()
+        };
+
+        // secret も含めて復元される，削除済みの id は再利用しない
+        let repository = WebhookRepositoryForFile::new(FileStore::open(&path).unwrap());
+        assert_eq!(repository.all().await.unwrap(), vec![webhook.clone()]);
+        let created = repository
+            .create(test_utils::payload("http://localhost/new"))
+            .await
+            .unwrap();
+        assert_eq!(created.id, webhook.id + 1);
+    }
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+
+    pub fn payload(url: &str) -> CreateWebhook {
+        CreateWebhook::new(
+            url.to_string(),
+            "0123456789abcdef".to_string(),
+            vec![
+                EventType::TaskUpdated,
+                EventType::TaskCreated,
+                EventType::TaskUpdated,
+            ],
+        )
+    }
+
+    /// 各実装で共通の CRUD シナリオ
+    pub async fn crud_scenario<T: WebhookRepository>(repository: &T) {
+        let url = format!("http://localhost/{}", uuid::Uuid::new_v4());
+
+        // create
+        let webhook = repository
+            .create(payload(&url))
+            .await
+            .expect("[create] returned Err");
+        assert_eq!(webhook.url, url);
+        assert_eq!(webhook.secret, "0123456789abcdef");
+        assert_eq!(
+            webhook.events,
+            vec![EventType::TaskCreated, EventType::TaskUpdated]
+        );
+
+        // all
+        let webhooks = repository.all().await.expect("[all] returned Err");
+        assert!(webhooks.contains(&webhook));
+
+        // delete
+        repository
+            .delete(webhook.id)
+            .await
+            .expect("[delete] returned Err");
+        let res = repository.delete(webhook.id).await;
+        assert!(matches!(
+            res.unwrap_err().downcast_ref::<RepositoryError>(),
+            Some(RepositoryError::NotFound(id)) if *id == webhook.id
+        ));
+    }
+}
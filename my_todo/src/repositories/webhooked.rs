@@ -0,0 +1,295 @@
+use axum::async_trait;
+use serde_json::json;
+
+use super::{
+    label::{CreateLabel, Label, LabelRepository, LabelStats, UpdateLabel},
+    task::{CreateTask, MoveTask, TaskEntity, TaskRecord, TaskRepository, UpdateTask},
+    webhook::{EventType, WebhookRepository},
+};
+use crate::webhook::Webhooks;
+
+/// 成功した変更操作ごとに webhook へイベントを送るラッパー
+#[derive(Clone)]
+pub struct Webhooked<T, W> {
+    inner: T,
+    webhooks: Webhooks<W>,
+}
+
+impl<T, W> Webhooked<T, W> {
+    pub fn new(inner: T, webhooks: Webhooks<W>) -> Self {
+        Self { inner, webhooks }
+    }
+}
+
+#[async_trait]
+impl<T: TaskRepository, W: WebhookRepository> TaskRepository for Webhooked<T, W> {
+    async fn create(&self, payload: CreateTask) -> anyhow::Result<TaskEntity> {
+        let task = self.inner.create(payload).await?;
+        self.webhooks.emit(EventType::TaskCreated, &task);
+        Ok(task)
+    }
+    async fn find(&self, id: i32) -> anyhow::Result<TaskEntity> {
+        self.inner.find(id).await
+    }
+    async fn all(&self) -> anyhow::Result<Vec<TaskEntity>> {
+        self.inner.all().await
+    }
+    async fn update_with_next(
+        &self,
+        id: i32,
+        payload: UpdateTask,
+        version: Option<i32>,
+    ) -> anyhow::Result<(TaskEntity, Option<TaskEntity>)> {
+        let (task, next) = self.inner.update_with_next(id, payload, version).await?;
+        self.webhooks.emit(EventType::TaskUpdated, &task);
+        if let Some(next) = &next {
+            self.webhooks.emit(EventType::TaskCreated, next);
+        }
+        Ok((task, next))
+    }
+    async fn occurrences(&self, id: i32) -> anyhow::Result<Vec<TaskEntity>> {
+        self.inner.occurrences(id).await
+    }
+    async fn move_task(&self, id: i32, payload: MoveTask) -> anyhow::Result<TaskEntity> {
+        let task = self.inner.move_task(id, payload).await?;
+        self.webhooks.emit(EventType::TaskUpdated, &task);
+        Ok(task)
+    }
+    async fn delete(&self, id: i32, version: Option<i32>) -> anyhow::Result<()> {
+        self.inner.delete(id, version).await?;
+        self.webhooks
+            .emit(EventType::TaskDeleted, &json!({ "id": id }));
+        Ok(())
+    }
+    async fn records(&self) -> anyhow::Result<Vec<TaskRecord>> {
+        self.inner.records().await
+    }
+    async fn restore(&self, record: TaskRecord) -> anyhow::Result<TaskEntity> {
+        let task = self.inner.restore(record).await?;
+        self.webhooks.emit(EventType::TaskCreated, &task);
+        Ok(task)
+    }
+}
+
+#[async_trait]
+impl<T: LabelRepository, W: WebhookRepository> LabelRepository for Webhooked<T, W> {
+    async fn create(&self, payload: CreateLabel) -> anyhow::Result<Label> {
+        let label = self.inner.create(payload).await?;
+        self.webhooks.emit(EventType::LabelCreated, &label);
+        Ok(label)
+    }
+    async fn all(&self) -> anyhow::Result<Vec<Label>> {
+        self.inner.all().await
+    }
+    async fn update(&self, id: i32, payload: UpdateLabel) -> anyhow::Result<Label> {
+        let label = self.inner.update(id, payload).await?;
+        self.webhooks.emit(EventType::LabelUpdated, &label);
+        Ok(label)
+    }
+    async fn delete(&self, id: i32) -> anyhow::Result<()> {
+        self.inner.delete(id).await?;
+        self.webhooks
+            .emit(EventType::LabelDeleted, &json!({ "id": id }));
+        Ok(())
+    }
+    async fn stats(&self) -> anyhow::Result<Vec<LabelStats>> {
+        self.inner.stats().await
+    }
+    async fn merge(&self, target: i32, sources: &[i32]) -> anyhow::Result<Vec<TaskEntity>> {
+        let mut sources = sources.to_vec();
+        sources.sort_unstable();
+        sources.dedup();
+        // 統合元の子は統合先へ付け替わるので，統合前に調べておく
+        let children = self
+            .inner
+            .all()
+            .await?
+            .into_iter()
+            .filter(|label| {
+                !sources.contains(&label.id)
+                    && label.parent_id.is_some_and(|id| sources.contains(&id))
+            })
+            .map(|label| label.id)
+            .collect::<Vec<_>>();
+        let tasks = self.inner.merge(target, &sources).await?;
+        for task in &tasks {
+            self.webhooks.emit(EventType::TaskUpdated, task);
+        }
+        if !children.is_empty() {
+            for label in self.inner.all().await? {
+                if children.contains(&label.id) {
+                    self.webhooks.emit(EventType::LabelUpdated, &label);
+                }
+            }
+        }
+        for id in &sources {
+            self.webhooks
+                .emit(EventType::LabelDeleted, &json!({ "id": id }));
+        }
+        Ok(tasks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::repositories::{
+        label::LabelRepositoryForMemory,
+        memory::MemoryStore,
+        task::TaskRepositoryForMemory,
+        webhook::{CreateWebhook, WebhookRepositoryForMemory},
+    };
+    use crate::webhook::{test_utils::Receiver, WebhookConfig, EVENT_HEADER};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn should_emit_events_after_mutations() {
+        let receiver = Receiver::spawn(0);
+        let store = MemoryStore::new();
+        let webhook_repository = WebhookRepositoryForMemory::with_store(store.clone());
+        webhook_repository
+            .create(CreateWebhook::new(
+                receiver.url.clone(),
+                "0123456789abcdef".to_string(),
+                vec![
+                    EventType::TaskCreated,
+                    EventType::TaskUpdated,
+                    EventType::TaskDeleted,
+                    EventType::LabelCreated,
+                ],
+            ))
+            .await
+            .unwrap();
+        let webhooks = Webhooks::new(
+            webhook_repository,
+            WebhookConfig::new(1, Duration::ZERO, 10),
+        );
+        let tasks = Webhooked::new(
+            TaskRepositoryForMemory::with_store(store.clone()),
+            webhooks.clone(),
+        );
+        let labels = Webhooked::new(LabelRepositoryForMemory::with_store(store), webhooks);
+
+        // 送信は spawn されるので，順序を確かめるために 1 件ずつ待つ
+        let label = labels
+            .create(CreateLabel::new("label".to_string()))
+            .await
+            .unwrap();
+        receiver.wait_for(1).await;
+        let task = tasks
+            .create(CreateTask::new("task".to_string(), vec![]).with_recurrence(
+                Some("FREQ=DAILY".to_string()),
+                Some("2024-01-01".parse().unwrap()),
+            ))
+            .await
+            .unwrap();
+        receiver.wait_for(2).await;
+        let (_, next) = tasks
+            .update_with_next(task.id, UpdateTask::new(None, Some(true), None), None)
+            .await
+            .unwrap();
+        let next = next.unwrap();
+        receiver.wait_for(4).await;
+        tasks.delete(task.id, None).await.unwrap();
+        receiver.wait_for(5).await;
+        // 失敗した操作と購読していないイベントは送らない
+        assert!(tasks.delete(task.id, None).await.is_err());
+        let source = labels
+            .create(CreateLabel::new("source".to_string()))
+            .await
+            .unwrap();
+        receiver.wait_for(6).await;
+        tasks
+            .update(
+                next.id,
+                UpdateTask::new(None, None, Some(vec![source.id])),
+                None,
+            )
+            .await
+            .unwrap();
+        receiver.wait_for(7).await;
+        // 付け替えたタスクを更新として送る，購読していない label.deleted は送らない
+        labels.merge(label.id, &[source.id]).await.unwrap();
+        receiver.wait_for(8).await;
+
+        let mut received = receiver.received();
+        // 完了と次のタスクの作成は続けて送るので順序は決まらない
+        received[2..4].sort_by_key(|r| r.header(EVENT_HEADER).to_string());
+        assert_eq!(
+            received
+                .iter()
+                .map(|r| r.header(EVENT_HEADER))
+                .collect::<Vec<_>>(),
+            vec![
+                "label.created",
+                "task.created",
+                "task.created",
+                "task.updated",
+                "task.deleted",
+                "label.created",
+                "task.updated",
+                "task.updated",
+            ]
+        );
+        assert_eq!(received[0].json()["data"]["name"], "label");
+        assert_eq!(received[2].json()["data"]["id"], next.id);
+        assert_eq!(received[2].json()["data"]["due_date"], "2024-01-02");
+        assert_eq!(received[3].json()["data"]["completed"], true);
+        assert_eq!(received[4].json()["data"]["id"], task.id);
+        assert_eq!(received[7].json()["data"]["id"], next.id);
+        assert_eq!(received[7].json()["data"]["labels"][0]["id"], label.id);
+    }
+
+    #[tokio::test]
+    async fn should_emit_label_events_for_merge() {
+        let receiver = Receiver::spawn(0);
+        let store = MemoryStore::new();
+        let webhook_repository = WebhookRepositoryForMemory::with_store(store.clone());
+        webhook_repository
+            .create(CreateWebhook::new(
+                receiver.url.clone(),
+                "0123456789abcdef".to_string(),
+                vec![EventType::LabelUpdated, EventType::LabelDeleted],
+            ))
+            .await
+            .unwrap();
+        let webhooks = Webhooks::new(
+            webhook_repository,
+            WebhookConfig::new(1, Duration::ZERO, 10),
+        );
+        let labels = Webhooked::new(LabelRepositoryForMemory::with_store(store), webhooks);
+        let target = labels
+            .create(CreateLabel::new("target".to_string()))
+            .await
+            .unwrap();
+        let source = labels
+            .create(CreateLabel::new("source".to_string()))
+            .await
+            .unwrap();
+        let child = labels
+            .create(CreateLabel::new("child".to_string()).with_parent(Some(source.id)))
+            .await
+            .unwrap();
+
+        // 重複した統合元は 1 回だけ送り，付け替えた子は更新として送る
+        labels
+            .merge(target.id, &[source.id, source.id])
+            .await
+            .unwrap();
+        receiver.wait_for(2).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut received = receiver.received();
+        received.sort_by_key(|r| r.header(EVENT_HEADER).to_string());
+        assert_eq!(
+            received
+                .iter()
+                .map(|r| r.header(EVENT_HEADER))
+                .collect::<Vec<_>>(),
+            vec!["label.deleted", "label.updated"]
+        );
+        assert_eq!(received[0].json()["data"]["id"], source.id);
+        assert_eq!(received[1].json()["data"]["id"], child.id);
+        assert_eq!(received[1].json()["data"]["parent_id"], target.id);
+    }
+}
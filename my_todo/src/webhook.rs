@@ -0,0 +1,473 @@
+use std::{
+    collections::VecDeque,
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+use utoipa::ToSchema;
+
+use crate::repositories::webhook::{EventType, Webhook, WebhookRepository};
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+/// 署名した時刻 (UNIX 秒)，受信側は古いものを再送として拒否できる
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const EVENT_HEADER: &str = "x-webhook-event";
+pub const DELIVERY_HEADER: &str = "x-webhook-delivery";
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_BACKOFF_MILLIS: u64 = 1000;
+const DEFAULT_LOG_CAPACITY: usize = 100;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    max_attempts: u32,
+    backoff: Duration,
+    log_capacity: usize,
+}
+
+impl WebhookConfig {
+    pub fn new(max_attempts: u32, backoff: Duration, log_capacity: usize) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff,
+            log_capacity,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|attempts| attempts.parse().ok())
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let backoff = env::var("WEBHOOK_BACKOFF_MS")
+            .ok()
+            .and_then(|backoff| backoff.parse().ok())
+            .unwrap_or(DEFAULT_BACKOFF_MILLIS);
+        let log_capacity = env::var("WEBHOOK_LOG_CAPACITY")
+            .ok()
+            .and_then(|capacity| capacity.parse().ok())
+            .unwrap_or(DEFAULT_LOG_CAPACITY);
+        Self::new(max_attempts, Duration::from_millis(backoff), log_capacity)
+    }
+
+    /// `attempt` 回目の失敗の後に待つ時間，1 回ごとに倍にする
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff * 2u32.saturating_pow(attempt - 1)
+    }
+}
+
+/// 送信する JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub id: String,
+    pub event: EventType,
+    pub occurred_at: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// 送信 1 回分の結果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Attempt {
+    pub webhook_id: i32,
+    pub event_id: String,
+    pub event: EventType,
+    /// 1 始まり
+    pub attempt: u32,
+    /// レスポンスを受け取れなかった場合は無い
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl Attempt {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 購読している webhook へイベントを送る，送信はバックグラウンドで行う
+#[derive(Clone)]
+pub struct Webhooks<W> {
+    repository: W,
+    client: reqwest::Client,
+    config: WebhookConfig,
+    /// 直近の送信結果，古いものから捨てる
+    log: Arc<Mutex<VecDeque<Attempt>>>,
+}
+
+impl<W: WebhookRepository> Webhooks<W> {
+    pub fn new(repository: W, config: WebhookConfig) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT)
+            .build()
+            .expect("fail build webhook client");
+        Self {
+            repository,
+            client,
+            config,
+            log: Arc::default(),
+        }
+    }
+
+    pub fn repository(&self) -> &W {
+        &self.repository
+    }
+
+    /// 新しいものから返す
+    pub fn attempts(&self) -> Vec<Attempt> {
+        self.log.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// 呼び出し元を待たせないよう送信は spawn する
+    pub fn emit(&self, event: EventType, data: &impl Serialize) {
+        let event = match serde_json::to_value(data) {
+            Ok(data) => Event {
+                id: uuid::Uuid::new_v4().to_string(),
+                event,
+                occurred_at: Utc::now(),
+                data,
+            },
+            Err(e) => {
+                tracing::error!("fail serialize {} event: {}", event, e);
+                return;
+            }
+        };
+        let webhooks = self.clone();
+        tokio::spawn(async move { webhooks.dispatch(event).await });
+    }
+
+    /// 購読している webhook それぞれへ並行して送り，リトライも含めて終わるまで待つ
+    pub async fn dispatch(&self, event: Event) {
+        let subscribers = match self.repository.all().await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                tracing::error!("fail load webhooks for {} event: {:#}", event.event, e);
+                return;
+            }
+        };
+        let body = match serde_json::to_vec(&event) {
+            Ok(body) => Arc::new(body),
+            Err(e) => {
+                tracing::error!("fail serialize {} event: {}", event.event, e);
+                return;
+            }
+        };
+        let event = Arc::new(event);
+        let handles = subscribers
+            .into_iter()
+            .filter(|webhook| webhook.subscribes(event.event))
+            .map(|webhook| {
+                let webhooks = self.clone();
+                let event = event.clone();
+                let body = body.clone();
+                tokio::spawn(async move { webhooks.deliver(&webhook, &event, &body).await })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+
+    /// 2xx が返るまで指数的に間隔を空けて `max_attempts` 回まで送る
+    async fn deliver(&self, webhook: &Webhook, event: &Event, body: &[u8]) {
+        for attempt in 1..=self.config.max_attempts {
+            // 再送のたびに時刻を付け直して署名する
+            let timestamp = Utc::now().timestamp();
+            let res = self
+                .client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, body))
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(EVENT_HEADER, event.event.as_str())
+                .header(DELIVERY_HEADER, &event.id)
+                .body(body.to_vec())
+                .send()
+                .await;
+            let (status, error) = match res {
+                Ok(res) if res.status().is_success() => (Some(res.status().as_u16()), None),
+                Ok(res) => (
+                    Some(res.status().as_u16()),
+                    Some(format!("unexpected status {}", res.status())),
+                ),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            let succeeded = error.is_none();
+            self.record(Attempt {
+                webhook_id: webhook.id,
+                event_id: event.id.clone(),
+                event: event.event,
+                attempt,
+                status,
+                error,
+                attempted_at: Utc::now(),
+            });
+            if succeeded {
+                return;
+            }
+            if attempt < self.config.max_attempts {
+                tokio::time::sleep(self.config.delay(attempt)).await;
+            }
+        }
+        tracing::warn!(
+            "give up delivering {} event {} to webhook {}",
+            event.event,
+            event.id,
+            webhook.id
+        );
+    }
+
+    fn record(&self, attempt: Attempt) {
+        let mut log = self.log.lock().unwrap();
+        log.push_back(attempt);
+        while log.len() > self.config.log_capacity {
+            log.pop_front();
+        }
+    }
+}
+
+/// `<timestamp>.<body>` の HMAC-SHA256 を `sha256=<hex>` の形で返す，受信側は同じ計算で検証する
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    hmac_sha256(secret, &[format!("{}.", timestamp).as_bytes(), body])
+}
+
+fn hmac_sha256(secret: &str, message: &[&[u8]]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    for part in message {
+        mac.update(part);
+    }
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    use super::*;
+    use axum::{
+        body::Bytes,
+        extract::Extension,
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use std::{
+        net::SocketAddr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// 受信側が受け取ったリクエスト
+    #[derive(Debug, Clone)]
+    pub struct Received {
+        pub headers: HeaderMap,
+        pub body: Bytes,
+    }
+
+    impl Received {
+        pub fn json(&self) -> Value {
+            serde_json::from_slice(&self.body).unwrap()
+        }
+
+        pub fn header(&self, name: &str) -> &str {
+            self.headers[name].to_str().unwrap()
+        }
+    }
+
+    #[derive(Default)]
+    struct ReceiverState {
+        received: Mutex<Vec<Received>>,
+        failures: AtomicUsize,
+    }
+
+    /// 受け取ったものを記録する受信側，最初の `failures` 回は 500 を返す
+    pub struct Receiver {
+        pub url: String,
+        state: Arc<ReceiverState>,
+    }
+
+    impl Receiver {
+        pub fn spawn(failures: usize) -> Self {
+            let state = Arc::new(ReceiverState {
+                failures: AtomicUsize::new(failures),
+                ..Default::default()
+            });
+            let app = Router::new()
+                .route(
+                    "/hook",
+                    post(
+                        |Extension(state): Extension<Arc<ReceiverState>>,
+                         headers: HeaderMap,
+                         body: Bytes| async move {
+                            state
+                                .received
+                                .lock()
+                                .unwrap()
+                                .push(Received { headers, body });
+                            let fail = state
+                                .failures
+                                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                                    n.checked_sub(1)
+                                })
+                                .is_ok();
+                            match fail {
+                                true => StatusCode::INTERNAL_SERVER_ERROR,
+                                false => StatusCode::NO_CONTENT,
+                            }
+                        },
+                    ),
+                )
+                .layer(Extension(state.clone()));
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr: SocketAddr = listener.local_addr().unwrap();
+            tokio::spawn(
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(app.into_make_service()),
+            );
+            Self {
+                url: format!("http://{}/hook", addr),
+                state,
+            }
+        }
+
+        pub fn received(&self) -> Vec<Received> {
+            self.state.received.lock().unwrap().clone()
+        }
+
+        /// `count` 件届くまで待つ
+        pub async fn wait_for(&self, count: usize) -> Vec<Received> {
+            for _ in 0..200 {
+                let received = self.received();
+                if received.len() >= count {
+                    return received;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("received only {} requests", self.received().len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::test_utils::Receiver;
+    use super::*;
+    use crate::repositories::webhook::{CreateWebhook, WebhookRepositoryForMemory};
+    use serde_json::json;
+
+    const SECRET: &str = "0123456789abcdef";
+
+    async fn webhooks(url: &str, events: Vec<EventType>) -> Webhooks<WebhookRepositoryForMemory> {
+        let repository = WebhookRepositoryForMemory::default();
+        repository
+            .create(CreateWebhook::new(
+                url.to_string(),
+                SECRET.to_string(),
+                events,
+            ))
+            .await
+            .unwrap();
+        Webhooks::new(
+            repository,
+            WebhookConfig::new(3, Duration::from_millis(10), 10),
+        )
+    }
+
+    fn event(event: EventType) -> Event {
+        Event {
+            id: "event-1".to_string(),
+            event,
+            occurred_at: Utc::now(),
+            data: json!({ "id": 1 }),
+        }
+    }
+
+    #[test]
+    fn should_sign_with_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hmac_sha256("Jefe", &[b"what do ya want", b" for nothing?"]),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // 時刻が違えば同じ body でも署名が変わる
+        assert_ne!(sign("Jefe", 1, b"{}"), sign("Jefe", 2, b"{}"));
+    }
+
+    #[test]
+    fn should_double_backoff() {
+        let config = WebhookConfig::new(5, Duration::from_millis(100), 10);
+        assert_eq!(config.delay(1), Duration::from_millis(100));
+        assert_eq!(config.delay(2), Duration::from_millis(200));
+        assert_eq!(config.delay(4), Duration::from_millis(800));
+    }
+
+    #[tokio::test]
+    async fn should_post_signed_event() {
+        let receiver = Receiver::spawn(0);
+        let webhooks = webhooks(&receiver.url, vec![EventType::TaskCreated]).await;
+
+        webhooks.dispatch(event(EventType::TaskCreated)).await;
+        // 購読していないイベントは送らない
+        webhooks.dispatch(event(EventType::LabelCreated)).await;
+
+        let received = receiver.received();
+        assert_eq!(received.len(), 1);
+        let timestamp = received[0].header(TIMESTAMP_HEADER).parse::<i64>().unwrap();
+        assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+        assert_eq!(
+            received[0].header(SIGNATURE_HEADER),
+            sign(SECRET, timestamp, &received[0].body)
+        );
+        assert_eq!(received[0].header(EVENT_HEADER), "task.created");
+        assert_eq!(received[0].header(DELIVERY_HEADER), "event-1");
+        assert_eq!(received[0].json()["event"], json!("task.created"));
+        assert_eq!(received[0].json()["data"], json!({ "id": 1 }));
+        let attempts = webhooks.attempts();
+        assert_eq!(attempts.len(), 1);
+        assert!(attempts[0].succeeded());
+        assert_eq!(attempts[0].status, Some(204));
+    }
+
+    #[tokio::test]
+    async fn should_retry_until_success() {
+        let receiver = Receiver::spawn(2);
+        let webhooks = webhooks(&receiver.url, vec![EventType::TaskUpdated]).await;
+
+        webhooks.dispatch(event(EventType::TaskUpdated)).await;
+
+        assert_eq!(receiver.received().len(), 3);
+        let attempts = webhooks.attempts();
+        assert_eq!(
+            attempts
+                .iter()
+                .map(|attempt| (attempt.attempt, attempt.status))
+                .collect::<Vec<_>>(),
+            vec![(3, Some(204)), (2, Some(500)), (1, Some(500))]
+        );
+        // 同じイベントは同じ id と署名で送り直す
+        let received = receiver.received();
+        assert!(received
+            .iter()
+            .all(|r| r.body == received[0].body && r.header(DELIVERY_HEADER) == "event-1"));
+    }
+
+    #[tokio::test]
+    async fn should_give_up_after_max_attempts() {
+        let receiver = Receiver::spawn(10);
+        let webhooks = webhooks(&receiver.url, vec![EventType::TaskDeleted]).await;
+
+        webhooks.dispatch(event(EventType::TaskDeleted)).await;
+
+        assert_eq!(receiver.received().len(), 3);
+        assert!(webhooks
+            .attempts()
+            .iter()
+            .all(|attempt| !attempt.succeeded()));
+    }
+}
@@ -2,6 +2,7 @@ export type Task = {
     id: number;
     text: string;
     completed: boolean;
+    version: number;
     labels: Label[];
 };
 